@@ -0,0 +1,89 @@
+//! Benchmarks of raw bus throughput, reading and writing the DMG memory map directly.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+use dotmatrix::Bus;
+
+/// How many accesses to make per iteration.
+const ACCESSES: usize = 0x1_0000;
+
+/// Addresses from `start` onwards, wrapping around within `len` bytes.
+fn sequential(start: u16, len: u16) -> Vec<u16> {
+    (0..ACCESSES)
+        .map(|offset| start + (offset as u16 % len))
+        .collect()
+}
+
+/// Addresses scattered pseudo-randomly from `start` onwards, within `len` bytes. Uses a fixed
+/// xorshift so every run accesses the same addresses.
+fn random(start: u16, len: u16) -> Vec<u16> {
+    let mut state: u32 = 0x2545_F491;
+
+    (0..ACCESSES)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+
+            start.wrapping_add((state % len as u32) as u16)
+        })
+        .collect()
+}
+
+fn bench_reads(c: &mut Criterion, name: &str, addrs: &[u16]) {
+    let bus = Bus::new_dmg();
+    let mut group = c.benchmark_group("read");
+
+    group.throughput(Throughput::Elements(addrs.len() as u64));
+    group.bench_function(name, |b| {
+        b.iter(|| {
+            for &addr in addrs {
+                black_box(bus.read(black_box(addr)));
+            }
+        })
+    });
+    group.finish();
+}
+
+fn bench_writes(c: &mut Criterion, name: &str, addrs: &[u16]) {
+    let mut bus = Bus::new_dmg();
+    let mut group = c.benchmark_group("write");
+
+    group.throughput(Throughput::Elements(addrs.len() as u64));
+    group.bench_function(name, |b| {
+        b.iter(|| {
+            for &addr in addrs {
+                bus.write(black_box(addr), addr as u8);
+            }
+        })
+    });
+    group.finish();
+}
+
+fn sequential_reads(c: &mut Criterion) {
+    // WRAM, the most common target of reads that aren't instruction fetches
+    bench_reads(c, "sequential", &sequential(0xC000, 0x2000));
+}
+
+fn random_reads(c: &mut Criterion) {
+    // Anywhere at all, including cartridge ROM and the IO registers
+    bench_reads(c, "random", &random(0x0000, 0xFFFF));
+}
+
+fn sequential_writes(c: &mut Criterion) {
+    bench_writes(c, "sequential", &sequential(0xC000, 0x2000));
+}
+
+fn random_writes(c: &mut Criterion) {
+    // VRAM through WRAM, as writes elsewhere can have side effects like starting an OAM DMA
+    bench_writes(c, "random", &random(0x8000, 0x6000));
+}
+
+criterion_group!(
+    benches,
+    sequential_reads,
+    random_reads,
+    sequential_writes,
+    random_writes
+);
+criterion_main!(benches);
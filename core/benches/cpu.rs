@@ -0,0 +1,65 @@
+//! Benchmarks of raw CPU throughput, running tight loops on a flat bus.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+use dotmatrix::DotMatrix;
+
+/// How many m-cycles to run per iteration.
+const M_CYCLES: usize = 10_000;
+
+/// Run a [DotMatrix] with a flat bus for [M_CYCLES], after writing `program` at PC.
+fn bench_program(c: &mut Criterion, name: &str, program: &[u8]) {
+    let mut dmg = DotMatrix::new_with_flat_bus();
+
+    // Keep interrupts from firing, the flat bus starts out with every bit of IF and IE set.
+    dmg.bus.write(0xFFFF, 0x00);
+
+    for (offset, &byte) in program.iter().enumerate() {
+        dmg.bus.write(dmg.cpu.pc.wrapping_add(offset as u16), byte);
+    }
+
+    c.bench_function(name, |b| {
+        b.iter(|| {
+            for _ in 0..M_CYCLES {
+                black_box(dmg.cpu.exec_m_cycle(&mut dmg.bus));
+            }
+        })
+    });
+}
+
+/// A [DotMatrix] with a flat bus that's a NOP sled all the way around the address space.
+fn nop_sled() -> DotMatrix {
+    let mut dmg = DotMatrix::new_with_flat_bus();
+    dmg.bus.write(0xFFFF, 0x00);
+
+    for addr in 0x0000..=0xFFFE {
+        dmg.bus.write(addr, 0x00);
+    }
+
+    dmg
+}
+
+fn nop_loop(c: &mut Criterion) {
+    // Each NOP takes a single m-cycle, so every m-cycle fetches a new instruction and looks up its
+    // m-code. Reported in instructions per second.
+    let mut dmg = nop_sled();
+    let mut group = c.benchmark_group("nop_loop");
+
+    group.throughput(Throughput::Elements(M_CYCLES as u64));
+    group.bench_function("instructions", |b| {
+        b.iter(|| {
+            for _ in 0..M_CYCLES {
+                black_box(dmg.cpu.exec_m_cycle(&mut dmg.bus));
+            }
+        })
+    });
+    group.finish();
+}
+
+fn jr_loop(c: &mut Criterion) {
+    // NOP; JR -3
+    bench_program(c, "jr_loop", &[0x00, 0x18, 0xFD]);
+}
+
+criterion_group!(benches, nop_loop, jr_loop);
+criterion_main!(benches);
@@ -1,31 +1,277 @@
+use std::fmt::{Debug, Display, Write};
+
+use crate::cartridge::Cartridge;
+use crate::timer::Timer;
+use crate::{Interrupt, Model};
+pub use device::{BusDevice, DeviceId};
+use hdma::{Hdma, BLOCK_SIZE, HDMA1, HDMA5};
+pub use io::IoRegister;
+use io::{Io, IO_SIZE};
+pub use state::{BusState, StateError, STATE_VERSION};
+
+mod device;
+mod hdma;
+mod io;
+mod state;
+
 const ADDRESS_SPACE: usize = 0x1_0000;
 const PAGE_SIZE: usize = 0x100;
 const PAGE_COUNT: usize = ADDRESS_SPACE / PAGE_SIZE;
 
-/// A 256-item chunk of address space, indexed by a `u8`. Can be wired to RAM, ROM, or specialized
-/// hardware.
+/// The address of the divider register, `DIV`.
+pub const DIV: u16 = 0xFF04;
+
+/// The address of the interrupt flag register, `IF`.
+pub const IF: u16 = 0xFF0F;
+
+/// The address of the LCD status register, `STAT`. Its low two bits are the PPU's mode.
+pub const STAT: u16 = 0xFF41;
+
+/// The address of the register that starts an OAM DMA transfer, `DMA`.
+pub const DMA: u16 = 0xFF46;
+
+/// The address of the CGB register that prepares a speed switch, `KEY1`.
+pub const KEY1: u16 = 0xFF4D;
+
+/// The address of the CGB register that selects the VRAM bank, `VBK`.
+pub const VBK: u16 = 0xFF4F;
+
+/// The address of the register that unmaps the boot ROM, `BANK`.
+pub const BANK: u16 = 0xFF50;
+
+/// The size of the DMG boot ROM, which is mapped over the start of the cartridge ROM.
+pub const BOOT_ROM_SIZE: usize = 0x100;
+
+/// The address of the CGB register that selects the WRAM bank at 0xD000–0xDFFF, `SVBK`.
+pub const SVBK: u16 = 0xFF70;
+
+/// The address of the interrupt enable register, `IE`.
+pub const IE: u16 = 0xFFFF;
+
+/// A bulk write that would have landed on memory that's read-only, like cartridge ROM.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ReadOnlyError {
+    /// The first read-only address the write would have touched.
+    pub addr: u16,
+}
+
+impl Display for ReadOnlyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04X} is read-only", self.addr)
+    }
+}
+
+impl std::error::Error for ReadOnlyError {}
+
+/// Whether a [BusActivity] read from or wrote to the bus.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReadWrite {
+    Read,
+    Write,
+}
+
+/// A single access to the bus, at most one of which happens each m-cycle.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BusActivity {
+    /// The address that was accessed.
+    pub addr: u16,
+
+    /// The value that was read or written.
+    pub value: u8,
+
+    /// Whether the value was read or written.
+    pub kind: ReadWrite,
+}
+
+impl BusActivity {
+    /// A read of `value` from `addr`.
+    pub const fn read(addr: u16, value: u8) -> Self {
+        Self {
+            addr,
+            value,
+            kind: ReadWrite::Read,
+        }
+    }
+
+    /// A write of `value` to `addr`.
+    pub const fn write(addr: u16, value: u8) -> Self {
+        Self {
+            addr,
+            value,
+            kind: ReadWrite::Write,
+        }
+    }
+}
+
+/// What drove an access to the bus.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AccessSource {
+    /// The CPU, or anything else going through [Bus::read] and [Bus::write].
+    Cpu,
+
+    /// OAM DMA or the CGB's VRAM DMA, copying a byte on its own.
+    Dma,
+}
+
+/// An access to the bus, as handed to the hook set with [Bus::set_watch].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BusAccess {
+    /// The address that was accessed.
+    pub addr: u16,
+
+    /// The value that was read or written. For a write, this is the value written even if nothing
+    /// at `addr` took it.
+    pub value: u8,
+
+    /// Whether the value was read or written.
+    pub kind: ReadWrite,
+
+    /// What drove the access.
+    pub source: AccessSource,
+}
+
+/// The size of each bank of VRAM, of which the CGB has 2.
+const VRAM_BANK_SIZE: usize = 0x2000;
+
+/// The size of each bank of WRAM, of which the CGB has 8.
+const WRAM_BANK_SIZE: usize = 0x1000;
+
+/// The size of OAM, the rest of its page is the unusable region.
+const OAM_SIZE: usize = 0xA0;
+
+/// What a 256-item chunk of address space is wired to, indexed by the high byte of an address.
+/// Pages of plain memory keep their contents in the flat backing memory of the [Bus], at their own
+/// addresses, so that accessing them is a single index. Only other pages need any dispatch.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 enum Page {
     /// Readable and writable memory.
-    Ram([u8; PAGE_SIZE]),
+    Ram,
+
+    /// Wired to the cartridge, which decides what reads and writes do. Handled by the [Bus], as
+    /// reads and writes both go through the cartridge it has attached.
+    Cartridge,
+
+    /// Wired to the cartridge's external RAM at 0xA000–0xBFFF, if it has any. Handled like
+    /// [Page::Cartridge], but writes here are data rather than control writes.
+    CartridgeRam,
+
+    /// The CGB's VRAM at 0x8000–0x9FFF, in the bank selected by [VBK]. Handled by the [Bus], which
+    /// holds both banks.
+    BankedVram,
+
+    /// The CGB's switchable WRAM bank at 0xD000–0xDFFF, selected by [SVBK]. Handled by the [Bus],
+    /// which holds every bank.
+    BankedWram,
+
+    /// Object attribute memory at 0xFE00–0xFE9F, followed by the unusable region up to 0xFEFF.
+    /// Reads from the unusable region depend on the [Model], and writes to it are ignored.
+    Oam(Model),
+
+    /// The IO registers at 0xFF00–0xFF7F, followed by HRAM at 0xFF80–0xFFFE and `IE` at 0xFFFF,
+    /// which are plain memory.
+    Mmio,
+
+    /// Mirrors another page, reads and writes go to the page numbered here instead. Resolved by
+    /// the [Bus] before any access.
+    Mirror(u8),
+
+    /// Handed to a mounted [BusDevice]. Handled by the [Bus], as the device lives there.
+    Device(DeviceId),
 }
 
 impl Page {
-    fn read(&self, addr: u8) -> u8 {
+    /// What the page is wired to, for the [Debug] output of the [Bus].
+    fn describe(&self) -> String {
         match self {
-            Self::Ram(ram) => ram[addr as usize],
+            Self::Ram => "RAM".into(),
+            Self::Cartridge => "cartridge".into(),
+            Self::CartridgeRam => "cartridge RAM".into(),
+            Self::BankedVram => "banked VRAM".into(),
+            Self::BankedWram => "banked WRAM".into(),
+            Self::Oam(_) => "OAM".into(),
+            Self::Mmio => "IO/HRAM/IE".into(),
+            Self::Mirror(_) => "mirror".into(),
+            Self::Device(DeviceId(id)) => format!("device {id}"),
         }
     }
+}
 
-    fn write(&mut self, addr: u8, value: u8) {
-        match self {
-            Self::Ram(ram) => ram[addr as usize] = value,
+/// An OAM DMA transfer, copying 160 bytes from the start of a page into OAM one byte per m-cycle.
+/// While bytes are being copied the CPU is locked out of everything below the IO registers, where
+/// reads return the byte last copied and writes are lost.
+///
+/// See the [Pan Docs](https://gbdev.io/pandocs/OAM_DMA_Transfer.html).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+struct OamDma {
+    /// The page being copied from, as written to [DMA].
+    source: u8,
+
+    /// How many m-cycles the transfer has been running for. The first only sets it up, then a byte
+    /// is copied each m-cycle, and the bus is released the m-cycle after the last byte.
+    cycles: u8,
+
+    /// The byte last copied, which is what the CPU sees on the bus it's locked out of.
+    value: u8,
+}
+
+impl OamDma {
+    const fn new(source: u8) -> Self {
+        Self {
+            source,
+            cycles: 0,
+            value: 0xFF,
+        }
+    }
+
+    /// The byte the CPU reads from `addr` instead of memory, while it's locked out.
+    const fn conflict(&self, addr: u16) -> Option<u8> {
+        if self.cycles > 1 && addr < 0xFF00 {
+            Some(self.value)
+        } else {
+            None
         }
     }
 }
 
-impl Page {
-    const fn new_ram() -> Self {
-        Self::Ram([0xFF; PAGE_SIZE])
+/// Backing memory for the whole address space, filled with 0xFF. Built on the heap rather than
+/// moved there, as it's too large to comfortably go on the stack first.
+fn new_memory() -> Box<[u8; ADDRESS_SPACE]> {
+    vec![0xFF; ADDRESS_SPACE]
+        .into_boxed_slice()
+        .try_into()
+        .expect("the backing memory is exactly the size of the address space")
+}
+
+/// The CGB's speed switch, which runs the CPU at double speed. Requested by setting bit 0 of
+/// [KEY1], which takes effect when the CPU executes `STOP`.
+///
+/// See the [Pan Docs](https://gbdev.io/pandocs/CGB_Registers.html#ff4d--key1-cgb-mode-only-prepare-speed-switch).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+struct SpeedSwitch {
+    /// Whether the CPU is running at double speed, read back from bit 7 of [KEY1].
+    double_speed: bool,
+
+    /// Whether the next `STOP` switches speed, bit 0 of [KEY1].
+    armed: bool,
+}
+
+impl SpeedSwitch {
+    const fn read(&self) -> u8 {
+        0x7E | (self.double_speed as u8) << 7 | self.armed as u8
+    }
+}
+
+/// What reads from the unusable region at 0xFEA0–0xFEFF return, which depends on the model.
+///
+/// The DMG returns 0x00, as long as OAM isn't being corrupted or blocked by the PPU. Revision E of
+/// the CGB repeats the high nibble of the low byte of the address, such as 0xAA from 0xFEA5. Earlier
+/// CGB revisions behave differently again, and aren't emulated.
+const fn read_unusable(model: Model, addr: u8) -> u8 {
+    match model {
+        Model::Dmg => 0x00,
+        Model::Cgb => (addr & 0xF0) | (addr >> 4),
     }
 }
 
@@ -33,27 +279,500 @@ impl Page {
 /// Addresses are 16 bits wide and values are 8 bits wide.
 ///
 /// [Memory Map]: https://gbdev.io/pandocs/Memory_Map.html
-pub struct Bus([Page; PAGE_COUNT]);
+pub struct Bus {
+    /// The contents of every page of plain memory, at their own addresses. Whatever's here for
+    /// other pages goes unused.
+    memory: Box<[u8; ADDRESS_SPACE]>,
+
+    pages: [Page; PAGE_COUNT],
+
+    /// The IO registers of the [Page::Mmio] page. A bus without that page never touches them.
+    io: Box<Io>,
+
+    /// The cartridge inserted into the [Page::Cartridge] and [Page::CartridgeRam] pages. Without
+    /// one, they read as open bus.
+    cartridge: Option<Cartridge>,
+
+    /// The boot ROM, mapped over 0x0000–0x00FF until a write to [BANK] unmaps it.
+    boot_rom: Option<Box<[u8; BOOT_ROM_SIZE]>>,
+
+    /// Every device mounted with [Bus::mount] or [Bus::mount_io], indexed by [DeviceId].
+    devices: Vec<Box<dyn BusDevice>>,
+
+    /// The OAM DMA transfer in progress, started by writing to [DMA].
+    dma: Option<OamDma>,
+
+    /// The CGB's VRAM DMA, controlled through `HDMA1`–`HDMA5`. Only exists on the CGB.
+    hdma: Option<Hdma>,
+
+    /// The CGB's speed switch, controlled through [KEY1]. Only exists on the CGB.
+    speed_switch: Option<SpeedSwitch>,
+
+    /// Both VRAM banks of the CGB, one after the other. Only allocated for the CGB, the DMG's VRAM
+    /// is plain RAM.
+    vram_banks: Option<Box<[u8; VRAM_BANK_SIZE * 2]>>,
+
+    /// WRAM banks 1–7 of the CGB, switched in at 0xD000–0xDFFF. Bank 0 is always at 0xC000–0xCFFF,
+    /// and the DMG only has the one bank at 0xD000–0xDFFF, so this is only allocated for the CGB.
+    wram_banks: Option<Box<[u8; WRAM_BANK_SIZE * 7]>>,
+
+    /// The hook set with [Bus::set_watch], called on every access by the CPU or DMA.
+    watch: Option<Box<dyn Fn(BusAccess) + Send>>,
+}
 
 impl Bus {
+    /// Insert a cartridge into the cartridge pages of the memory map, returning the one it
+    /// replaces, if any.
+    pub fn insert_cartridge(&mut self, cartridge: Cartridge) -> Option<Cartridge> {
+        self.cartridge.replace(cartridge)
+    }
+
+    /// The cartridge inserted with [Bus::insert_cartridge], if any.
+    pub fn cartridge(&self) -> Option<&Cartridge> {
+        self.cartridge.as_ref()
+    }
+
+    /// The cartridge inserted with [Bus::insert_cartridge], if any. Changing its state here is
+    /// the same as a write through the bus, without any [Bus::set_watch] hook seeing it.
+    pub fn cartridge_mut(&mut self) -> Option<&mut Cartridge> {
+        self.cartridge.as_mut()
+    }
+
+    /// The index of the page that accesses to `page` end up at, following any [Page::Mirror].
+    fn resolve(&self, page: u8) -> usize {
+        match self.pages[page as usize] {
+            Page::Mirror(target) => target as usize,
+            _ => page as usize,
+        }
+    }
+
+    /// Give the IO register at `addr` its own behavior, replacing what it did before. Does nothing
+    /// on a bus without IO registers, like [Bus::flat].
+    pub fn map_io(&mut self, addr: u16, register: IoRegister) {
+        self.io.map(addr, register);
+    }
+
+    /// Mount `device` over every page in `pages`, numbered by the high byte of their addresses.
+    /// Replaces whatever the pages were wired to before, including any mirror of another page.
+    pub fn mount(
+        &mut self,
+        pages: std::ops::RangeInclusive<u8>,
+        device: Box<dyn BusDevice>,
+    ) -> DeviceId {
+        let id = self.add_device(device);
+
+        for page in pages {
+            self.pages[page as usize] = Page::Device(id);
+        }
+
+        id
+    }
+
+    /// Mount `device` over the IO registers at `addrs`, replacing what they did before. Does
+    /// nothing to a bus without IO registers, like [Bus::flat], other than ticking the device.
+    pub fn mount_io(
+        &mut self,
+        addrs: std::ops::RangeInclusive<u16>,
+        device: Box<dyn BusDevice>,
+    ) -> DeviceId {
+        let id = self.add_device(device);
+
+        for addr in addrs {
+            self.map_io(addr, IoRegister::Device(id));
+        }
+
+        id
+    }
+
+    fn add_device(&mut self, device: Box<dyn BusDevice>) -> DeviceId {
+        self.devices.push(device);
+
+        DeviceId(self.devices.len() - 1)
+    }
+
+    /// Advance any OAM DMA transfer and every mounted device by one m-cycle, raising any interrupts
+    /// the devices request in [IF].
+    pub fn tick(&mut self) {
+        self.tick_dma();
+        self.tick_hdma();
+
+        let requested = self
+            .devices
+            .iter_mut()
+            .fold(0x00, |requested, device| requested | device.tick());
+
+        for interrupt in Interrupt::ALL {
+            if requested & interrupt.mask() != 0 {
+                self.request_interrupt(interrupt);
+            }
+        }
+    }
+
+    /// Copy the next byte of the OAM DMA transfer in progress, if there is one.
+    fn tick_dma(&mut self) {
+        // Taken out of the bus while copying, so the transfer doesn't conflict with itself
+        let Some(mut dma) = self.dma.take() else {
+            return;
+        };
+
+        if let Some(index) = (dma.cycles as usize).checked_sub(1) {
+            if index == OAM_SIZE {
+                return;
+            }
+
+            // Sources in 0xE000–0xFFFF read from WRAM, like echo RAM
+            let source = match dma.source {
+                page @ 0xE0.. => page - 0x20,
+                page => page,
+            };
+
+            let addr = u16::from_be_bytes([source, index as u8]);
+
+            dma.value = self.read_unwatched(addr);
+            self.memory[0xFE00 + index] = dma.value;
+
+            self.notify(addr, dma.value, ReadWrite::Read, AccessSource::Dma);
+            self.notify(
+                0xFE00 + index as u16,
+                dma.value,
+                ReadWrite::Write,
+                AccessSource::Dma,
+            );
+        }
+
+        dma.cycles += 1;
+        self.dma = Some(dma);
+    }
+
+    /// The index into [Bus::vram_banks] of `addr` in VRAM `bank`.
+    fn vram_bank_index(bank: u8, addr: u16) -> usize {
+        (bank & 0x01) as usize * VRAM_BANK_SIZE + addr as usize % VRAM_BANK_SIZE
+    }
+
+    /// Read `addr` from either VRAM bank, regardless of which is selected by [VBK], for the PPU.
+    /// The DMG only has bank 0, and reads from bank 1 return 0xFF.
+    pub fn read_vram(&self, bank: u8, addr: u16) -> u8 {
+        match (&self.vram_banks, bank) {
+            (Some(banks), _) => banks[Self::vram_bank_index(bank, addr)],
+            (None, 0) => self.memory[0x8000 + addr as usize % VRAM_BANK_SIZE],
+            (None, _) => 0xFF,
+        }
+    }
+
+    /// The index into [Bus::wram_banks] of `addr` in the WRAM bank currently selected by [SVBK].
+    fn wram_bank_index(&self, addr: u16) -> usize {
+        // Selecting bank 0 selects bank 1 instead
+        let bank = match self.read_unwatched(SVBK) & 0x07 {
+            0 => 1,
+            bank => bank as usize,
+        };
+
+        (bank - 1) * WRAM_BANK_SIZE + addr as usize % WRAM_BANK_SIZE
+    }
+
+    /// Copy a block of the HBlank DMA in progress if the PPU just entered HBlank, and count down
+    /// any stall from blocks already copied.
+    fn tick_hdma(&mut self) {
+        let in_hblank = self.read_unwatched(STAT) & 0x03 == 0;
+
+        let Some(hdma) = &mut self.hdma else {
+            return;
+        };
+
+        hdma.tick();
+
+        if hdma.hblank(in_hblank) {
+            self.copy_hdma_block();
+        }
+    }
+
+    /// Copy the next block of the VRAM DMA in progress into the VRAM bank selected by [VBK].
+    fn copy_hdma_block(&mut self) {
+        let speed_factor = self.speed_factor();
+
+        let Some((source, destination)) =
+            self.hdma.as_mut().map(|hdma| hdma.next_block(speed_factor))
+        else {
+            return;
+        };
+
+        for offset in 0..BLOCK_SIZE {
+            let value = self.read_unwatched(source.wrapping_add(offset));
+
+            self.write_unwatched(destination + offset, value);
+
+            self.notify(
+                source.wrapping_add(offset),
+                value,
+                ReadWrite::Read,
+                AccessSource::Dma,
+            );
+            self.notify(
+                destination + offset,
+                value,
+                ReadWrite::Write,
+                AccessSource::Dma,
+            );
+        }
+    }
+
+    /// Whether the CPU is running at double speed, which only the CGB can switch to.
+    pub fn double_speed(&self) -> bool {
+        self.speed_switch
+            .is_some_and(|speed_switch| speed_switch.double_speed)
+    }
+
+    /// How many CPU m-cycles pass for each m-cycle of everything that doesn't run at double speed,
+    /// like the PPU. The timer, OAM DMA, and anything else ticked by [Bus::tick] keeps pace with the
+    /// CPU instead.
+    pub fn speed_factor(&self) -> u8 {
+        match self.double_speed() {
+            true => 2,
+            false => 1,
+        }
+    }
+
+    /// Handle the CPU executing `STOP`, which resets `DIV` and switches speed if a switch was
+    /// requested through [KEY1]. Returns whether it switched speed.
+    pub fn stop(&mut self) -> bool {
+        // A flat bus has no DIV to reset
+        if self.pages[0xFF] == Page::Mmio {
+            self.write_io(DIV, 0x00);
+        }
+
+        match &mut self.speed_switch {
+            Some(speed_switch) if speed_switch.armed => {
+                speed_switch.double_speed = !speed_switch.double_speed;
+                speed_switch.armed = false;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the CPU is stalled this m-cycle, while VRAM DMA copies blocks on the CGB.
+    pub fn cpu_stalled(&self) -> bool {
+        self.hdma.as_ref().is_some_and(Hdma::stalled)
+    }
+
+    /// Map a boot ROM over 0x0000–0x00FF, until it unmaps itself by writing to [BANK].
+    pub fn map_boot_rom(&mut self, boot_rom: &[u8; BOOT_ROM_SIZE]) {
+        self.boot_rom = Some(Box::new(*boot_rom));
+    }
+
+    /// Whether a boot ROM is still mapped over the start of the cartridge ROM.
+    pub fn boot_rom_mapped(&self) -> bool {
+        self.boot_rom.is_some()
+    }
+
+    /// Call `watch` on every access to the bus by the CPU or DMA, replacing any hook already set.
+    /// For tracing and watchpoints, as in a debugger. [Bus::peek] and the bus's own bookkeeping,
+    /// like checking for pending interrupts, never call it.
+    ///
+    /// The hook only gets a shared reference to what it captures, and has to be [Send] so the bus
+    /// still can be, so anything it records needs to go in a [std::sync::Mutex] or an atomic.
+    pub fn set_watch(&mut self, watch: impl Fn(BusAccess) + Send + 'static) {
+        self.watch = Some(Box::new(watch));
+    }
+
+    /// Remove the hook set with [Bus::set_watch].
+    pub fn clear_watch(&mut self) {
+        self.watch = None;
+    }
+
+    /// Hand an access to the hook set with [Bus::set_watch], if any.
+    fn notify(&self, addr: u16, value: u8, kind: ReadWrite, source: AccessSource) {
+        if let Some(watch) = &self.watch {
+            watch(BusAccess {
+                addr,
+                value,
+                kind,
+                source,
+            });
+        }
+    }
+
     /// Read an 8-bit value from the specified address.
     pub fn read(&self, addr: u16) -> u8 {
-        let [index, page] = addr.to_le_bytes();
+        let value = self.read_unwatched(addr);
 
-        self.0[page as usize].read(index)
+        self.notify(addr, value, ReadWrite::Read, AccessSource::Cpu);
+        value
+    }
+
+    /// Read an 8-bit value from the specified address, without calling the hook set with
+    /// [Bus::set_watch].
+    fn read_unwatched(&self, addr: u16) -> u8 {
+        if let Some(value) = self.dma.and_then(|dma| dma.conflict(addr)) {
+            return value;
+        }
+
+        match self.pages[addr as usize >> 8] {
+            Page::Ram => self.memory[addr as usize],
+            _ => self.read_mapped(addr, false),
+        }
     }
 
     /// Read a 16-bit little-endian value from the specified address.
     pub fn read16(&self, addr: u16) -> u16 {
         // Note that `addr + 1` could cross a page boundary or wrap around to 0x0000.
-        u16::from_le_bytes([self.read(addr), self.read(addr + 1)])
+        u16::from_le_bytes([self.read(addr), self.read(addr.wrapping_add(1))])
+    }
+
+    /// The value a read from the specified address would return, without any of the side effects of
+    /// reading it. For debuggers and test harnesses that need to look without disturbing anything.
+    pub fn peek(&self, addr: u16) -> u8 {
+        if let Some(value) = self.dma.and_then(|dma| dma.conflict(addr)) {
+            return value;
+        }
+
+        self.read_mapped(addr, true)
+    }
+
+    /// Peek at a 16-bit little-endian value from the specified address, see [Bus::peek].
+    pub fn peek16(&self, addr: u16) -> u16 {
+        u16::from_le_bytes([self.peek(addr), self.peek(addr.wrapping_add(1))])
+    }
+
+    /// Read from a page that isn't plain memory, or [Bus::peek] at it if `peek` is set.
+    fn read_mapped(&self, addr: u16, peek: bool) -> u8 {
+        let [index, page] = addr.to_le_bytes();
+        let resolved = self.resolve(page);
+
+        match self.pages[resolved] {
+            Page::Ram => self.memory[(resolved << 8) | index as usize],
+            Page::Cartridge | Page::CartridgeRam => match (&self.boot_rom, page) {
+                (Some(boot_rom), 0x00) => boot_rom[index as usize],
+                _ => self
+                    .cartridge
+                    .as_ref()
+                    .map_or(0xFF, |cartridge| cartridge.read(addr)),
+            },
+            Page::BankedVram => self.read_vram(self.read_unwatched(VBK), addr),
+            Page::BankedWram => self
+                .wram_banks
+                .as_ref()
+                .map_or(0xFF, |banks| banks[self.wram_bank_index(addr)]),
+            Page::Oam(model) => match index as usize {
+                0..OAM_SIZE => self.memory[addr as usize],
+                _ => read_unusable(model, index),
+            },
+            Page::Mmio if index as usize >= IO_SIZE => self.memory[addr as usize],
+            Page::Mmio => match (&self.hdma, &self.speed_switch, self.io.device(index)) {
+                (Some(hdma), _, _) if addr == HDMA5 => hdma.read(),
+                (_, Some(speed_switch), _) if addr == KEY1 => speed_switch.read(),
+                (_, _, Some(DeviceId(id))) if peek => self.devices[id].peek(addr),
+                (_, _, Some(DeviceId(id))) => self.devices[id].read(addr),
+                _ if peek => self.io.peek(index),
+                _ => self.io.read(index),
+            },
+            Page::Device(DeviceId(id)) if peek => self.devices[id].peek(addr),
+            Page::Device(DeviceId(id)) => self.devices[id].read(addr),
+            Page::Mirror(_) => 0xFF,
+        }
+    }
+
+    /// The interrupts that are both requested in `IF` and enabled in `IE`.
+    pub fn pending_interrupts(&self) -> u8 {
+        self.read_unwatched(IF) & self.read_unwatched(IE) & 0x1F
+    }
+
+    /// Request `interrupt` by setting its bit in [IF]. This is how peripherals raise interrupts.
+    pub fn request_interrupt(&mut self, interrupt: Interrupt) {
+        self.write_unwatched(IF, self.read_unwatched(IF) | interrupt.mask());
+    }
+
+    /// Clear the bit of `interrupt` in [IF], as the CPU does when it services the interrupt.
+    pub fn acknowledge_interrupt(&mut self, interrupt: Interrupt) {
+        self.write_unwatched(IF, self.read_unwatched(IF) & !interrupt.mask());
     }
 
     /// Write an 8-bit value to the specified address.
     pub fn write(&mut self, addr: u16, value: u8) {
+        self.write_unwatched(addr, value);
+        self.notify(addr, value, ReadWrite::Write, AccessSource::Cpu);
+    }
+
+    /// Write an 8-bit value to the specified address, without calling the hook set with
+    /// [Bus::set_watch].
+    fn write_unwatched(&mut self, addr: u16, value: u8) {
+        if self.dma.and_then(|dma| dma.conflict(addr)).is_some() {
+            return;
+        }
+
+        match self.pages[addr as usize >> 8] {
+            Page::Ram => self.memory[addr as usize] = value,
+            _ => self.write_mapped(addr, value),
+        }
+    }
+
+    /// Write to a page that isn't plain memory.
+    fn write_mapped(&mut self, addr: u16, value: u8) {
         let [index, page] = addr.to_le_bytes();
+        let resolved = self.resolve(page);
+
+        match self.pages[resolved] {
+            Page::Ram => self.memory[(resolved << 8) | index as usize] = value,
+            Page::Cartridge | Page::CartridgeRam => {
+                if let Some(cartridge) = &mut self.cartridge {
+                    cartridge.write(addr, value);
+                }
+            }
+            Page::BankedVram => {
+                let index = Self::vram_bank_index(self.read_unwatched(VBK), addr);
+
+                if let Some(banks) = &mut self.vram_banks {
+                    banks[index] = value;
+                }
+            }
+            Page::BankedWram => {
+                let index = self.wram_bank_index(addr);
 
-        self.0[page as usize].write(index, value);
+                if let Some(banks) = &mut self.wram_banks {
+                    banks[index] = value;
+                }
+            }
+            Page::Oam(_) => {
+                if (index as usize) < OAM_SIZE {
+                    self.memory[addr as usize] = value;
+                }
+            }
+            Page::Mmio if index as usize >= IO_SIZE => self.memory[addr as usize] = value,
+            Page::Mmio => self.write_io(addr, value),
+            Page::Device(DeviceId(id)) => self.devices[id].write(addr, value),
+            Page::Mirror(_) => (),
+        }
+    }
+
+    /// Write to one of the IO registers at 0xFF00–0xFF7F.
+    fn write_io(&mut self, addr: u16, value: u8) {
+        // Unmapping the boot ROM is permanent, there's no way to map it back in
+        if addr == BANK && value & 0x01 != 0 {
+            self.boot_rom = None;
+        }
+
+        if let (Some(hdma), HDMA1..=HDMA5) = (&mut self.hdma, addr) {
+            // A general-purpose DMA copies everything right away
+            for _ in 0..hdma.write(addr, value) {
+                self.copy_hdma_block();
+            }
+
+            return;
+        }
+
+        if let (Some(speed_switch), KEY1) = (&mut self.speed_switch, addr) {
+            speed_switch.armed = value & 0x01 != 0;
+            return;
+        }
+
+        if addr == DMA {
+            self.dma = Some(OamDma::new(value));
+        }
+
+        match self.io.device(addr as u8) {
+            Some(DeviceId(id)) => self.devices[id].write(addr, value),
+            None => self.io.write(addr as u8, value),
+        }
     }
 
     /// Write a 16-bit little-endian value to the specified address.
@@ -62,25 +781,1027 @@ impl Bus {
 
         // Note that `addr + 1` could cross a page boundary or wrap around to 0x0000.
         self.write(addr, low);
-        self.write(addr + 1, high);
+        self.write(addr.wrapping_add(1), high);
+    }
+
+    /// Read `len` bytes starting from the specified address, wrapping around to 0x0000 past the
+    /// end of the address space.
+    pub fn read_range(&self, addr: u16, len: usize) -> Vec<u8> {
+        (0..len)
+            .map(|offset| self.read(addr.wrapping_add(offset as u16)))
+            .collect()
+    }
+
+    /// Write `data` starting from the specified address, wrapping around to 0x0000 past the end of
+    /// the address space.
+    ///
+    /// Nothing is written if any of it would land on cartridge ROM, where a write would be taken
+    /// as a control write to the memory bank controller rather than data.
+    pub fn write_slice(&mut self, addr: u16, data: &[u8]) -> Result<(), ReadOnlyError> {
+        let addrs = (0..data.len()).map(|offset| addr.wrapping_add(offset as u16));
+
+        for addr in addrs.clone() {
+            let [_, page] = addr.to_le_bytes();
+
+            if self.pages[self.resolve(page)] == Page::Cartridge {
+                return Err(ReadOnlyError { addr });
+            }
+        }
+
+        for (addr, &value) in addrs.zip(data) {
+            self.write(addr, value);
+        }
+
+        Ok(())
+    }
+
+    /// Dump `range` as hex, 16 bytes per line with an ASCII gutter, like `hexdump -C`. Uses
+    /// [Bus::peek], so dumping memory doesn't disturb it. Inclusive, so that it can reach IE at
+    /// 0xFFFF.
+    pub fn hexdump(&self, range: std::ops::RangeInclusive<u16>) -> String {
+        let mut dump = String::new();
+        let bytes: Vec<u8> = range.clone().map(|addr| self.peek(addr)).collect();
+
+        for (addr, line) in range.step_by(0x10).zip(bytes.chunks(0x10)) {
+            let _ = write!(dump, "{addr:04X} ");
+
+            for column in 0..0x10 {
+                // An extra space halfway through, to make columns easier to count
+                if column % 8 == 0 {
+                    dump.push(' ');
+                }
+
+                match line.get(column) {
+                    Some(value) => _ = write!(dump, "{value:02X} "),
+                    None => dump.push_str("   "),
+                }
+            }
+
+            let ascii: String = line
+                .iter()
+                .map(|&value| match value {
+                    0x20..=0x7E => value as char,
+                    _ => '.',
+                })
+                .collect();
+
+            let _ = writeln!(dump, " |{ascii}|");
+        }
+
+        dump
+    }
+}
+
+/// A summary of which regions of memory are wired to what, rather than all 64 KiB of it.
+impl Debug for Bus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Bus {{ ")?;
+
+        let mut start = 0;
+
+        for page in 0..PAGE_COUNT {
+            let description = self.pages[page].describe();
+
+            // Keep going until the end of a run of pages wired to the same thing
+            if self
+                .pages
+                .get(page + 1)
+                .is_some_and(|next| next.describe() == description)
+            {
+                continue;
+            }
+
+            write!(
+                f,
+                "{:04X}–{:04X}:{description}",
+                start << 8,
+                page << 8 | 0xFF
+            )?;
+
+            if let (Page::Mirror(first), Page::Mirror(last)) =
+                (&self.pages[start], &self.pages[page])
+            {
+                write!(
+                    f,
+                    " of {:04X}–{:04X}",
+                    (*first as u16) << 8,
+                    ((*last as u16) << 8) | 0xFF
+                )?;
+            }
+
+            write!(f, " ")?;
+            start = page + 1;
+        }
+
+        if self.cartridge.is_none() {
+            write!(f, "no cartridge ")?;
+        }
+
+        if self.boot_rom.is_some() {
+            write!(f, "boot ROM mapped ")?;
+        }
+
+        if let Some(dma) = &self.dma {
+            write!(f, "OAM DMA from {:02X}00 ", dma.source)?;
+        }
+
+        write!(f, "}}")
     }
 }
 
 impl Bus {
-    /// Create a new [Bus] with the standard memory map for the DMG.
+    /// Create a new [Bus] with the standard memory map for the DMG, with no cartridge inserted.
     pub fn new_dmg() -> Self {
-        // TODO: Proper memory map
-        const RAM: Page = Page::new_ram();
+        Self::new(Model::Dmg)
+    }
+
+    /// Create a new [Bus] with the standard [Memory Map][] for `model`, with no cartridge inserted.
+    ///
+    /// [Memory Map]: https://gbdev.io/pandocs/Memory_Map.html
+    pub fn new(model: Model) -> Self {
+        let pages = std::array::from_fn(|page| match page << 8 {
+            // Cartridge ROM
+            0x0000..=0x7FFF => Page::Cartridge,
+            // VRAM, which is banked on the CGB
+            0x8000..=0x9FFF if model == Model::Cgb => Page::BankedVram,
+            0x8000..=0x9FFF => Page::Ram,
+            // Cartridge RAM
+            0xA000..=0xBFFF => Page::CartridgeRam,
+            // WRAM, where the second half is banked on the CGB
+            0xD000..=0xDFFF if model == Model::Cgb => Page::BankedWram,
+            0xC000..=0xDFFF => Page::Ram,
+            // Echo RAM, mirroring 0xC000–0xDDFF
+            0xE000..=0xFDFF => Page::Mirror(page as u8 - 0x20),
+            // OAM and the unusable region
+            0xFE00 => Page::Oam(model),
+            // IO registers, HRAM, and IE
+            _ => Page::Mmio,
+        });
+
+        let mut bus = Self {
+            memory: new_memory(),
+            pages,
+            io: Box::new(Io::new_dmg()),
+            cartridge: None,
+            boot_rom: None,
+            devices: Vec::new(),
+            dma: None,
+            hdma: None,
+            speed_switch: None,
+            vram_banks: None,
+            wram_banks: None,
+            watch: None,
+        };
 
-        Self([RAM; PAGE_COUNT])
+        // IE is cleared by the boot ROM, unlike the rest of HRAM
+        bus.memory[IE as usize] = 0x00;
+        bus.mount_io(DIV..=0xFF07, Box::new(Timer::new_dmg()));
+
+        if model == Model::Cgb {
+            bus.hdma = Some(Hdma::new());
+            bus.speed_switch = Some(SpeedSwitch::default());
+            bus.vram_banks = Some(Box::new([0xFF; VRAM_BANK_SIZE * 2]));
+            bus.map_io(VBK, IoRegister::masked(0x01, 0x01, 0x00));
+            bus.wram_banks = Some(Box::new([0xFF; WRAM_BANK_SIZE * 7]));
+            bus.map_io(SVBK, IoRegister::masked(0x07, 0x07, 0x00));
+        }
+
+        bus
     }
 
     /// Create a new [Bus] with nothing but RAM for use with the [Single Step Tests][].
     ///
     /// [Single Step Tests]: https://github.com/SingleStepTests/sm83
     pub fn flat() -> Self {
-        const RAM: Page = Page::new_ram();
+        Self {
+            memory: new_memory(),
+            pages: [Page::Ram; PAGE_COUNT],
+            io: Box::new(Io::new()),
+            cartridge: None,
+            boot_rom: None,
+            devices: Vec::new(),
+            dma: None,
+            hdma: None,
+            speed_switch: None,
+            vram_banks: None,
+            wram_banks: None,
+            watch: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    /// Write to every address of a region, returning whether each one reads back what was written.
+    fn writable(bus: &mut Bus, range: std::ops::RangeInclusive<u16>) -> Vec<bool> {
+        range
+            .map(|addr| {
+                // Flip every bit, so the write can't be mistaken for what was already there
+                let value = !bus.read(addr);
+                bus.write(addr, value);
+                bus.read(addr) == value
+            })
+            .collect()
+    }
+
+    #[test]
+    fn access_16_bit_wraps_at_top_of_memory() {
+        let mut bus = Bus::flat();
+
+        bus.write16(0xFFFF, 0xABCD);
+
+        assert_eq!(0xCD, bus.read(0xFFFF));
+        assert_eq!(0xAB, bus.read(0x0000));
+        assert_eq!(0xABCD, bus.read16(0xFFFF));
+    }
+
+    #[test]
+    fn access_16_bit_crosses_pages() {
+        let mut bus = Bus::flat();
+
+        bus.write16(0x00FF, 0x1234);
+
+        assert_eq!(0x34, bus.read(0x00FF));
+        assert_eq!(0x12, bus.read(0x0100));
+        assert_eq!(0x1234, bus.read16(0x00FF));
+    }
+
+    #[test]
+    fn peek_matches_read() {
+        let mut bus = Bus::new_dmg();
+        let mut rom = vec![0x00; 0x8000];
+
+        rom[0x0150] = 0x12;
+        bus.insert_cartridge(Cartridge::new_unchecked(rom.into()));
+        bus.write16(0xC000, 0xABCD);
+
+        for addr in [
+            0x0150, 0x8000, 0xA000, 0xC000, 0xE001, 0xFEA0, IF, 0xFF03, 0xFF80, IE,
+        ] {
+            assert_eq!(bus.read(addr), bus.peek(addr), "{addr:04X}");
+        }
+
+        assert_eq!(0xABCD, bus.peek16(0xC000));
+        assert_eq!(0xABCD, bus.peek16(0xE000));
+    }
+
+    #[test]
+    fn slices_cross_pages_and_wrap() {
+        let mut bus = Bus::flat();
+        let data: Vec<u8> = (0..=0xFF)
+            .chain(0..=0xFF)
+            .map(|value| value as u8)
+            .collect();
+
+        bus.write_slice(0x12F0, &data).unwrap();
+
+        assert_eq!(data, bus.read_range(0x12F0, data.len()));
+        assert_eq!(0x0F, bus.read(0x12FF));
+        assert_eq!(0x10, bus.read(0x1300));
+        assert_eq!(0xFF, bus.read(0x14EF));
+
+        bus.write_slice(0xFFFE, &[0x01, 0x02, 0x03, 0x04]).unwrap();
+
+        assert_eq!(vec![0x01, 0x02, 0x03, 0x04], bus.read_range(0xFFFE, 4));
+        assert_eq!(0x0403, bus.read16(0x0000));
+    }
+
+    #[test]
+    fn write_slice_rejects_rom() {
+        let mut bus = Bus::new_dmg();
+
+        assert_eq!(
+            Err(ReadOnlyError { addr: 0x0000 }),
+            bus.write_slice(0xFFFE, &[0x01, 0x02, 0x03])
+        );
+
+        // Nothing was written, not even the part before the ROM
+        assert_eq!(0xFF, bus.read(0xFFFE));
+
+        assert_eq!(
+            Err(ReadOnlyError { addr: 0x7FFE }),
+            bus.write_slice(0x7FFE, &[0x01, 0x02, 0x03])
+        );
+        assert_eq!(Ok(()), bus.write_slice(0x8000, &[0x01, 0x02, 0x03]));
+    }
+
+    #[test]
+    fn dmg_memory_map() {
+        let mut bus = Bus::new_dmg();
+
+        // IO registers are left out, each one has its own behavior
+
+        let regions = [
+            ("ROM", 0x0000..=0x7FFF, false),
+            ("VRAM", 0x8000..=0x9FFF, true),
+            ("cartridge RAM", 0xA000..=0xBFFF, false),
+            ("WRAM", 0xC000..=0xDFFF, true),
+            ("echo RAM", 0xE000..=0xFDFF, true),
+            ("OAM", 0xFE00..=0xFE9F, true),
+            ("unusable", 0xFEA0..=0xFEFF, false),
+            ("HRAM", 0xFF80..=0xFFFE, true),
+            ("IE", 0xFFFF..=0xFFFF, true),
+        ];
+
+        for (name, range, expected) in regions {
+            let writable = writable(&mut bus, range.clone());
+
+            assert!(
+                writable.iter().all(|&writable| writable == expected),
+                "{name} ({:04X}–{:04X}) should be {}",
+                range.start(),
+                range.end(),
+                if expected { "writable" } else { "read-only" }
+            );
+        }
+    }
+
+    #[test]
+    fn rom_ignores_writes() {
+        let mut bus = Bus::new_dmg();
+
+        bus.write(0x2000, 0x01);
+        bus.write16(0x0100, 0x1234);
+
+        assert_eq!(0xFF, bus.read(0x2000));
+        assert_eq!(0xFFFF, bus.read16(0x0100));
+    }
+
+    #[test]
+    fn echo_ram_mirrors_wram() {
+        let mut bus = Bus::new_dmg();
+
+        bus.write(0xC123, 0x12);
+        bus.write(0xE456, 0x34);
+
+        assert_eq!(0x12, bus.read(0xE123));
+        assert_eq!(0x34, bus.read(0xC456));
+
+        // Mirroring stops at 0xFDFF, which is 0xDDFF, and OAM takes over from 0xFE00
+        bus.write(0xFDFF, 0x56);
+        bus.write(0xDE00, 0x78);
+        bus.write(0xFE00, 0x9A);
+
+        assert_eq!(0x56, bus.read(0xDDFF));
+        assert_eq!(0x78, bus.read(0xDE00));
+        assert_eq!(0x9A, bus.read(0xFE00));
+    }
+
+    #[test]
+    fn cartridge_is_mapped_to_rom() {
+        let mut bus = Bus::new_dmg();
+        let mut rom = vec![0x00; 0x8000];
+
+        rom[0x0100..0x0104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+        rom[0x7FFF] = 0x42;
+        bus.insert_cartridge(Cartridge::new_unchecked(rom.into()));
+
+        assert_eq!(0x00, bus.read(0x0100));
+        assert_eq!(0x0150, bus.read16(0x0102));
+        assert_eq!(0x42, bus.read(0x7FFF));
+
+        // Writes go to the cartridge, and never change its ROM
+        bus.write(0x0100, 0xFF);
+
+        assert_eq!(0x00, bus.read(0x0100));
+    }
+
+    #[test]
+    fn io_registers_are_mapped() {
+        let mut bus = Bus::new_dmg();
+
+        bus.write(IF, 0x00);
+        bus.write(0xFF03, 0x00);
+
+        assert_eq!(0xE0, bus.read(IF));
+        assert_eq!(0xFF, bus.read(0xFF03));
+
+        bus.map_io(0xFF03, IoRegister::storage(0x00));
+        bus.write(0xFF03, 0x42);
+
+        assert_eq!(0x42, bus.read(0xFF03));
+    }
+
+    #[test]
+    fn boot_rom_overlay() {
+        let mut bus = Bus::new_dmg();
+        let mut rom = vec![0x00; 0x8000];
+
+        rom[0x0000] = 0x11;
+        rom[0x0100] = 0x22;
+        bus.insert_cartridge(Cartridge::new_unchecked(rom.into()));
+        bus.map_boot_rom(&[0x31; BOOT_ROM_SIZE]);
+
+        assert_eq!(0x31, bus.read(0x0000));
+        assert_eq!(0x31, bus.read(0x00FF));
+        assert_eq!(0x22, bus.read(0x0100));
+
+        // Only writes with bit 0 set unmap the boot ROM
+        bus.write(BANK, 0x00);
+
+        assert!(bus.boot_rom_mapped());
+
+        bus.write(BANK, 0x01);
+
+        assert!(!bus.boot_rom_mapped());
+        assert_eq!(0x11, bus.read(0x0000));
+
+        // And it stays unmapped
+        bus.write(BANK, 0x00);
+
+        assert_eq!(0x11, bus.read(0x0000));
+    }
+
+    #[test]
+    fn missing_cartridge_ram_reads_open_bus() {
+        let mut bus = Bus::new_dmg();
+
+        bus.write(0xA000, 0x00);
+
+        assert_eq!(0xFF, bus.read(0xA000));
+        assert_eq!(0xFF, bus.read(0xBFFF));
+    }
+
+    #[test]
+    fn cartridge_ram_is_mapped() {
+        let mut bus = Bus::new_dmg();
+        let mut rom = vec![0x00; 0x8000];
+
+        // MBC1+RAM with 8 KiB of RAM
+        rom[0x0147] = 0x02;
+        rom[0x0149] = 0x02;
+        bus.insert_cartridge(Cartridge::new(rom.into()).unwrap());
+
+        bus.write(0x0000, 0x0A);
+        assert_eq!(Ok(()), bus.write_slice(0xBFFE, &[0x12, 0x34]));
+
+        assert_eq!(0x3412, bus.read16(0xBFFE));
+    }
+
+    #[test]
+    fn cartridge_ram_needs_enable() {
+        let mut bus = Bus::new_dmg();
+        let mut rom = vec![0x00; 0x8000];
+
+        rom[0x0147] = 0x03;
+        rom[0x0149] = 0x02;
+        bus.insert_cartridge(Cartridge::new(rom.into()).unwrap());
+
+        // Disabled RAM is open bus, and writes to it are dropped
+        bus.write(0xA000, 0x12);
+        assert_eq!(0xFF, bus.read(0xA000));
+
+        bus.write(0x1FFF, 0x0A);
+        assert_eq!(0xFF, bus.read(0xA000));
+
+        bus.write(0xA000, 0x12);
+        assert_eq!(0x12, bus.read(0xA000));
+
+        bus.write(0x0000, 0x00);
+        assert_eq!(0xFF, bus.read(0xA000));
+
+        // Control writes still reach the cartridge under the boot ROM
+        bus.map_boot_rom(&[0x00; BOOT_ROM_SIZE]);
+        bus.write(0x0000, 0x0A);
+        assert_eq!(0x12, bus.read(0xA000));
+    }
+
+    #[test]
+    fn unusable_region_dmg() {
+        let mut bus = Bus::new(Model::Dmg);
+
+        for addr in 0xFEA0..=0xFEFF {
+            bus.write(addr, 0xFF);
+
+            assert_eq!(0x00, bus.read(addr), "{addr:04X}");
+        }
+
+        // OAM right below it is unaffected
+        assert_eq!(0xFF, bus.read(0xFE9F));
+    }
+
+    #[test]
+    fn unusable_region_cgb() {
+        let mut bus = Bus::new(Model::Cgb);
+
+        bus.write(0xFEA5, 0x00);
+        bus.write(0xFEFF, 0x00);
+
+        assert_eq!(0xAA, bus.read(0xFEA5));
+        assert_eq!(0xBB, bus.read(0xFEB0));
+        assert_eq!(0xFF, bus.read(0xFEFF));
+    }
+
+    /// Plain RAM as a device, covering the whole address space like [Bus::flat].
+    struct Ram(Box<[u8; ADDRESS_SPACE]>);
+
+    impl BusDevice for Ram {
+        fn read(&self, addr: u16) -> u8 {
+            self.0[addr as usize]
+        }
+
+        fn write(&mut self, addr: u16, value: u8) {
+            self.0[addr as usize] = value;
+        }
+    }
+
+    #[test]
+    fn ram_device_matches_flat() {
+        let mut flat = Bus::flat();
+        let mut mounted = Bus::flat();
+
+        mounted.mount(0x00..=0xFF, Box::new(Ram(Box::new([0xFF; ADDRESS_SPACE]))));
+
+        assert_eq!(
+            writable(&mut flat, 0x0000..=0xFFFF),
+            writable(&mut mounted, 0x0000..=0xFFFF)
+        );
+        assert_eq!(
+            flat.read_range(0x0000, ADDRESS_SPACE),
+            mounted.read_range(0x0000, ADDRESS_SPACE)
+        );
+    }
+
+    #[test]
+    fn timer_is_mounted() {
+        let mut bus = Bus::new_dmg();
+
+        bus.write(IF, 0x00);
+        bus.write(0xFF04, 0x12);
+        bus.write(0xFF05, 0xFF);
+        // Enabled, incrementing every 4 m-cycles
+        bus.write(0xFF07, 0x05);
+
+        assert_eq!(0x00, bus.read(0xFF04));
+        assert_eq!(0xFD, bus.read(0xFF07));
+
+        for _ in 0..5 {
+            bus.tick();
+        }
+
+        assert_eq!(0x00, bus.read(0xFF05));
+        assert_eq!(0xE4, bus.read(IF));
+    }
+
+    #[test]
+    fn oam_dma_copies_into_oam() {
+        let mut bus = Bus::new_dmg();
+        let data: Vec<u8> = (0..OAM_SIZE as u8).collect();
+
+        bus.write_slice(0xC100, &data).unwrap();
+        bus.write(DMA, 0xC1);
+
+        // One m-cycle to set up, then one for each byte
+        for _ in 0..=OAM_SIZE {
+            bus.tick();
+        }
+
+        // The bus is still held until the next m-cycle
+        assert_eq!(0x9F, bus.read(0xFE00));
+
+        bus.tick();
+
+        assert_eq!(data, bus.read_range(0xFE00, OAM_SIZE));
+        assert_eq!(0xC1, bus.read(DMA));
+    }
+
+    #[test]
+    fn oam_dma_locks_cpu_out() {
+        let mut bus = Bus::new_dmg();
+
+        bus.write_slice(0xC000, &[0x11, 0x22, 0x33]).unwrap();
+        bus.write(0xC800, 0x44);
+        bus.write(0xFF80, 0x55);
+        bus.write(DMA, 0xC0);
+
+        // Nothing is locked out while the transfer is being set up
+        bus.tick();
+        assert_eq!(0x44, bus.read(0xC800));
+
+        bus.tick();
+        bus.tick();
+
+        // Reads see the byte the transfer last copied, and writes are lost
+        assert_eq!(0x22, bus.read(0xC800));
+        assert_eq!(0x22, bus.read(0x0000));
+        bus.write(0xC800, 0x66);
+
+        // HRAM is still accessible
+        assert_eq!(0x55, bus.read(0xFF80));
+
+        for _ in 0..OAM_SIZE {
+            bus.tick();
+        }
+
+        assert_eq!(0x44, bus.read(0xC800));
+    }
+
+    #[test]
+    fn cgb_wram_banks() {
+        let mut bus = Bus::new(Model::Cgb);
+
+        for bank in 1..8 {
+            bus.write(SVBK, bank);
+            bus.write_slice(0xD000, &[bank; 0x10]).unwrap();
+            bus.write(0xDFFF, bank << 4);
+        }
+
+        for bank in 1..8 {
+            bus.write(SVBK, bank);
+
+            assert_eq!(0xF8 | bank, bus.read(SVBK));
+            assert_eq!(
+                vec![bank; 0x10],
+                bus.read_range(0xD000, 0x10),
+                "bank {bank}"
+            );
+            assert_eq!(bank << 4, bus.read(0xDFFF), "bank {bank}");
+            // Echo RAM mirrors whichever bank is selected
+            assert_eq!(bank, bus.read(0xF000), "bank {bank}");
+        }
+
+        // Selecting bank 0 selects bank 1
+        bus.write(SVBK, 0x00);
+        assert_eq!(0xF8, bus.read(SVBK));
+        assert_eq!(0x01, bus.read(0xD000));
+
+        // Bank 0 is fixed
+        bus.write(0xC000, 0x42);
+        bus.write(SVBK, 0x05);
+        assert_eq!(0x42, bus.read(0xC000));
+    }
+
+    #[test]
+    fn dmg_has_no_svbk() {
+        let mut bus = Bus::new(Model::Dmg);
+
+        bus.write(0xD000, 0x12);
+        bus.write(SVBK, 0x02);
+
+        assert_eq!(0xFF, bus.read(SVBK));
+        assert_eq!(0x12, bus.read(0xD000));
+    }
+
+    #[test]
+    fn cgb_vram_banks() {
+        let mut bus = Bus::new(Model::Cgb);
+
+        bus.write(VBK, 0x00);
+        bus.write(0x8000, 0x12);
+        bus.write(0x9FFF, 0x34);
+
+        bus.write(VBK, 0xFF);
+        assert_eq!(0xFF, bus.read(VBK));
+        assert_ne!(0x12, bus.read(0x8000));
+        bus.write(0x8000, 0x56);
+
+        bus.write(VBK, 0x00);
+        assert_eq!(0xFE, bus.read(VBK));
+        assert_eq!(0x12, bus.read(0x8000));
+        assert_eq!(0x34, bus.read(0x9FFF));
+
+        // The PPU can fetch from either bank, whichever is selected
+        assert_eq!(0x12, bus.read_vram(0, 0x8000));
+        assert_eq!(0x56, bus.read_vram(1, 0x8000));
+    }
+
+    #[test]
+    fn dmg_has_one_vram_bank() {
+        let mut bus = Bus::new(Model::Dmg);
+
+        bus.write(0x8000, 0x12);
+        bus.write(VBK, 0x01);
+
+        assert_eq!(0xFF, bus.read(VBK));
+        assert_eq!(0x12, bus.read(0x8000));
+        assert_eq!(0x12, bus.read_vram(0, 0x8000));
+        assert_eq!(0xFF, bus.read_vram(1, 0x8000));
+    }
+
+    #[test]
+    fn general_purpose_hdma() {
+        let mut bus = Bus::new(Model::Cgb);
+        let data: Vec<u8> = (0..0x20).collect();
+
+        bus.write_slice(0xC000, &data).unwrap();
+        bus.write(VBK, 0x01);
+        bus.write(0xFF51, 0xC0);
+        bus.write(0xFF52, 0x00);
+        bus.write(0xFF53, 0x01);
+        bus.write(0xFF54, 0x00);
+        bus.write(HDMA5, 0x01);
+
+        assert_eq!(data, bus.read_range(0x8100, 0x20));
+        assert_eq!(0xFF, bus.read(HDMA5));
+
+        // The CPU is stalled for 8 m-cycles per block, including the m-cycle of the write
+        let stalled: Vec<bool> = (0..16)
+            .map(|_| {
+                bus.tick();
+                bus.cpu_stalled()
+            })
+            .collect();
+
+        assert_eq!([vec![true; 15], vec![false]].concat(), stalled);
+    }
+
+    #[test]
+    fn hblank_hdma() {
+        let mut bus = Bus::new(Model::Cgb);
+        let data: Vec<u8> = (0..0x30).collect();
+
+        // Stand in for the PPU's mode in STAT
+        bus.map_io(STAT, IoRegister::storage(0x83));
+        bus.write_slice(0xC000, &data).unwrap();
+        bus.write(0xFF51, 0xC0);
+        bus.write(0xFF52, 0x00);
+        bus.write(0xFF53, 0x00);
+        bus.write(0xFF54, 0x00);
+        bus.write(HDMA5, 0x82);
+
+        // Nothing is copied until HBlank
+        bus.tick();
+        assert_eq!(0x02, bus.read(HDMA5));
+        assert_ne!(data[..0x10], bus.read_range(0x8000, 0x10));
+
+        // One block is copied on entering HBlank, not for every m-cycle in it
+        bus.write(STAT, 0x80);
+        bus.tick();
+        bus.tick();
+        assert_eq!(0x01, bus.read(HDMA5));
+        assert_eq!(data[..0x10], bus.read_range(0x8000, 0x10));
+        assert!(bus.cpu_stalled());
+
+        bus.write(STAT, 0x83);
+        bus.tick();
+        bus.write(STAT, 0x80);
+        bus.tick();
+        assert_eq!(0x00, bus.read(HDMA5));
+
+        // Cancelling leaves the last block uncopied
+        bus.write(HDMA5, 0x00);
+        assert_eq!(0x80, bus.read(HDMA5));
+
+        bus.write(STAT, 0x83);
+        bus.tick();
+        bus.write(STAT, 0x80);
+        bus.tick();
+        assert_eq!(data[..0x20], bus.read_range(0x8000, 0x20));
+        assert_ne!(data[0x20..], bus.read_range(0x8020, 0x10));
+    }
+
+    #[test]
+    fn dmg_has_no_hdma() {
+        let mut bus = Bus::new(Model::Dmg);
+
+        bus.write(HDMA5, 0x00);
+
+        assert_eq!(0xFF, bus.read(HDMA5));
+        assert!(!bus.cpu_stalled());
+    }
+
+    #[test]
+    fn hexdump() {
+        let mut bus = Bus::flat();
+
+        bus.write_slice(0xC000, b"Hello, world!\x00\x01\x7F")
+            .unwrap();
+        bus.write(0xC010, 0x41);
+
+        assert_eq!(
+            "\
+C000  48 65 6C 6C 6F 2C 20 77  6F 72 6C 64 21 00 01 7F  |Hello, world!...|
+C010  41 FF FF                                          |A..|
+",
+            bus.hexdump(0xC000..=0xC012)
+        );
+
+        bus.write(IE, 0x1F);
+
+        assert_eq!(
+            "FFF0  FF FF FF FF FF FF FF FF  FF FF FF FF FF FF FF 1F  |................|\n",
+            bus.hexdump(0xFFF0..=0xFFFF)
+        );
+    }
+
+    #[test]
+    fn debug_summarizes_regions() {
+        let debug = format!("{:?}", Bus::new_dmg());
+
+        assert_eq!(
+            "Bus { 0000–7FFF:cartridge 8000–9FFF:RAM A000–BFFF:cartridge RAM C000–DFFF:RAM \
+             E000–FDFF:mirror of C000–DDFF FE00–FEFF:OAM FF00–FFFF:IO/HRAM/IE no cartridge }",
+            debug
+        );
+    }
+
+    #[test]
+    fn state_round_trips() {
+        let mut bus = Bus::new(Model::Cgb);
+
+        bus.write_slice(0xC000, &[0x12; 0xA0]).unwrap();
+        bus.write(SVBK, 0x03);
+        bus.write(0xD000, 0x34);
+        bus.write(0xFF80, 0x56);
+        bus.write(0xFF07, 0x05);
+        bus.write(DMA, 0xC0);
+
+        // Partway through the OAM DMA
+        for _ in 0..10 {
+            bus.tick();
+        }
+
+        let state = bus.save_state();
+        let mut restored = Bus::new(Model::Cgb);
+
+        restored.load_state(&state).unwrap();
+
+        assert_eq!(state, restored.save_state());
+
+        for _ in 0..0x200 {
+            bus.tick();
+            restored.tick();
+        }
+
+        assert_eq!(
+            bus.read_range(0x0000, ADDRESS_SPACE),
+            restored.read_range(0x0000, ADDRESS_SPACE)
+        );
+        assert_eq!(bus.save_state(), restored.save_state());
+    }
+
+    #[test]
+    fn state_includes_cartridge() {
+        let mut bus = Bus::new_dmg();
+        let mut rom = vec![0x00; 4 * 0x4000];
+
+        // MBC1+RAM+BATTERY with 64 KiB of ROM and 32 KiB of RAM
+        rom[0x0147] = 0x03;
+        rom[0x0148] = 0x01;
+        rom[0x0149] = 0x03;
+
+        for bank in 0..4 {
+            rom[bank * 0x4000 + 0x1000] = bank as u8;
+        }
+
+        bus.insert_cartridge(Cartridge::new(rom.clone().into()).unwrap());
+        bus.write(0x0000, 0x0A);
+        bus.write(0x2000, 0x02);
+        bus.write(0x6000, 0x01);
+        bus.write(0x4000, 0x01);
+        bus.write(0xA000, 0x12);
+
+        let state = bus.save_state();
+
+        // Switch banks and scribble over RAM before going back
+        bus.write(0xA000, 0x34);
+        bus.write(0x2000, 0x03);
+        bus.write(0x4000, 0x02);
+        bus.write(0xA000, 0x56);
+        bus.write(0x0000, 0x00);
+
+        assert_eq!(Ok(()), bus.load_state(&state));
+        assert_eq!(0x02, bus.read(0x5000));
+        assert_eq!(0x12, bus.read(0xA000));
+        assert_eq!(state, bus.save_state());
+
+        bus.write(0x4000, 0x02);
+        assert_eq!(0xFF, bus.read(0xA000));
+
+        // The state only fits the same kind of cartridge
+        let mut other = Bus::new_dmg();
+        assert_eq!(Err(StateError::Layout), other.load_state(&state));
+
+        rom[0x0149] = 0x02;
+        other.insert_cartridge(Cartridge::new(rom.into()).unwrap());
+        assert_eq!(Err(StateError::Layout), other.load_state(&state));
+    }
+
+    #[test]
+    fn state_must_fit_layout() {
+        let state = Bus::new(Model::Cgb).save_state();
+        let mut bus = Bus::new(Model::Dmg);
+
+        bus.write(0xC000, 0x12);
+
+        assert_eq!(Err(StateError::Layout), bus.load_state(&state));
+        assert_eq!(0x12, bus.read(0xC000));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn state_serde_round_trips() {
+        let mut bus = Bus::new_dmg();
+
+        bus.write(0xC000, 0x12);
+
+        let json = serde_json::to_string(&bus.save_state()).unwrap();
+        let state: BusState = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(bus.save_state(), state);
+    }
+
+    #[test]
+    fn key1_prepares_speed_switch() {
+        let mut bus = Bus::new(Model::Cgb);
+
+        bus.write(KEY1, 0xFF);
+        assert_eq!(0x7F, bus.read(KEY1));
+
+        assert!(bus.stop());
+        assert_eq!(0xFE, bus.read(KEY1));
+        assert_eq!(2, bus.speed_factor());
+
+        // Without preparing another switch, STOP leaves the speed alone
+        assert!(!bus.stop());
+        assert!(bus.double_speed());
+
+        let mut dmg = Bus::new(Model::Dmg);
+
+        dmg.write(KEY1, 0x01);
+        assert_eq!(0xFF, dmg.read(KEY1));
+        assert!(!dmg.stop());
+    }
+
+    #[test]
+    fn interrupt_registers() {
+        let mut bus = Bus::new(Model::Dmg);
+
+        // Only the low 5 bits of IF exist, but all 8 bits of IE read back
+        bus.write(IF, 0x00);
+        bus.write(IE, 0x00);
+        assert_eq!(0xE0, bus.read(IF));
+        assert_eq!(0x00, bus.read(IE));
+
+        bus.write(IF, 0xFF);
+        bus.write(IE, 0xFF);
+        assert_eq!(0xFF, bus.read(IF));
+        assert_eq!(0xFF, bus.read(IE));
+
+        bus.write(IF, 0x00);
+        bus.write(IE, Interrupt::Timer.mask() | Interrupt::Joypad.mask());
+
+        bus.request_interrupt(Interrupt::VBlank);
+        bus.request_interrupt(Interrupt::Timer);
+        assert_eq!(0xE5, bus.read(IF));
+        assert_eq!(Interrupt::Timer.mask(), bus.pending_interrupts());
+
+        bus.acknowledge_interrupt(Interrupt::Timer);
+        assert_eq!(0xE1, bus.read(IF));
+        assert_eq!(0x00, bus.pending_interrupts());
+    }
+
+    #[test]
+    fn no_interrupts_pending_after_boot() {
+        // IF starts with VBlank requested, but none are enabled in IE
+        for model in [Model::Dmg, Model::Cgb] {
+            let bus = Bus::new(model);
+
+            assert_eq!(0xE1, bus.peek(IF));
+            assert_eq!(0x00, bus.peek(IE));
+            assert_eq!(0x00, bus.pending_interrupts());
+        }
+    }
+
+    #[test]
+    fn watch_sees_dma() {
+        let mut bus = Bus::new_dmg();
+        let accesses = Arc::new(Mutex::new(Vec::new()));
+
+        bus.write(0xC005, 0x42);
+        bus.write(DMA, 0xC0);
+
+        let watched = Arc::clone(&accesses);
+        bus.set_watch(move |access| watched.lock().unwrap().push(access));
+
+        for _ in 0..=6 {
+            bus.tick();
+        }
+
+        // Neither peeking nor checking for interrupts is an access
+        bus.peek(0xFE05);
+        bus.pending_interrupts();
+
+        let dma = |addr, kind| BusAccess {
+            addr,
+            value: 0x42,
+            kind,
+            source: AccessSource::Dma,
+        };
+
+        assert_eq!(12, accesses.lock().unwrap().len());
+        assert_eq!(
+            [dma(0xC005, ReadWrite::Read), dma(0xFE05, ReadWrite::Write)],
+            accesses.lock().unwrap()[10..]
+        );
+
+        bus.clear_watch();
+        bus.read(0xC005);
 
-        Self([RAM; PAGE_COUNT])
+        assert_eq!(12, accesses.lock().unwrap().len());
     }
 }
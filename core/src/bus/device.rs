@@ -0,0 +1,42 @@
+use super::StateError;
+
+/// A peripheral with registers or memory of its own, mounted onto the [Bus](super::Bus) over whole
+/// pages or individual IO registers. Accesses to anywhere it's mounted are handed to it with the
+/// full address. Devices are [Send], so that the [Bus](super::Bus) can be moved to another thread.
+pub trait BusDevice: Send {
+    /// Read the value at `addr`.
+    fn read(&self, addr: u16) -> u8;
+
+    /// Write `value` to `addr`.
+    fn write(&mut self, addr: u16, value: u8);
+
+    /// The value a read from `addr` would return, without any of the side effects of reading it.
+    /// Only needs overriding by devices whose reads have side effects.
+    fn peek(&self, addr: u16) -> u8 {
+        self.read(addr)
+    }
+
+    /// Advance the device by one m-cycle, returning the interrupts it requests as a combination of
+    /// [Interrupt::mask](crate::Interrupt::mask).
+    /// Devices that don't keep time can leave this alone.
+    fn tick(&mut self) -> u8 {
+        0x00
+    }
+
+    /// The device's state for a save state, in whatever form suits it. Devices without any state
+    /// of their own can leave this alone.
+    fn save_state(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    /// Restore the device's state from what [BusDevice::save_state] saved.
+    fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        let _ = state;
+
+        Ok(())
+    }
+}
+
+/// Identifies a [BusDevice] mounted onto a [Bus](super::Bus), as returned when it was mounted.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DeviceId(pub(super) usize);
@@ -0,0 +1,177 @@
+/// The address of the first of the CGB's VRAM DMA registers, `HDMA1`–`HDMA5` at 0xFF51–0xFF55.
+pub const HDMA1: u16 = 0xFF51;
+
+/// The address of the VRAM DMA register that starts and stops transfers, `HDMA5`.
+pub const HDMA5: u16 = 0xFF55;
+
+/// The number of bytes copied at a time, with each block taking 8 m-cycles at normal speed.
+pub const BLOCK_SIZE: u16 = 0x10;
+
+/// The registers and state of the CGB's VRAM DMA, which copies to VRAM in blocks of 16 bytes. A
+/// general-purpose DMA copies every block at once, while an HBlank DMA copies one block each time
+/// the PPU enters HBlank. The CPU is stalled while blocks are copied.
+///
+/// This only keeps track of where the transfer is, the [Bus](super::Bus) does the copying.
+///
+/// See the [Pan Docs](https://gbdev.io/pandocs/CGB_Registers.html#lcd-vram-dma-transfers).
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct Hdma {
+    /// Where the next block is copied from, written through `HDMA1` and `HDMA2`.
+    source: u16,
+
+    /// Where in VRAM the next block is copied to, relative to 0x8000, written through `HDMA3` and
+    /// `HDMA4`.
+    destination: u16,
+
+    /// The number of blocks left to copy minus one, as read back from `HDMA5`. Reads 0x7F once
+    /// there's nothing left, so `HDMA5` reads 0xFF.
+    remaining: u8,
+
+    /// Whether an HBlank DMA is in progress.
+    hblank_mode: bool,
+
+    /// Whether the PPU was in HBlank last m-cycle, so that only entering HBlank copies a block.
+    in_hblank: bool,
+
+    /// The number of m-cycles the CPU remains stalled for while blocks are copied.
+    stall: u16,
+}
+
+impl Hdma {
+    pub const fn new() -> Self {
+        Self {
+            source: 0x0000,
+            destination: 0x0000,
+            remaining: 0x7F,
+            hblank_mode: false,
+            in_hblank: false,
+            stall: 0,
+        }
+    }
+
+    /// Read `HDMA5`, the only one of the registers that can be read. Bit 7 is clear while an HBlank
+    /// DMA is in progress, and the rest is the number of blocks left minus one.
+    pub const fn read(&self) -> u8 {
+        if self.hblank_mode {
+            self.remaining
+        } else {
+            0x80 | self.remaining
+        }
+    }
+
+    /// Write to one of the registers, returning the number of blocks to copy right away for a
+    /// general-purpose DMA.
+    pub fn write(&mut self, addr: u16, value: u8) -> u8 {
+        match addr {
+            0xFF51 => self.source = (self.source & 0x00FF) | (value as u16) << 8,
+            0xFF52 => self.source = (self.source & 0xFF00) | (value & 0xF0) as u16,
+            0xFF53 => self.destination = (self.destination & 0x00FF) | ((value & 0x1F) as u16) << 8,
+            0xFF54 => self.destination = (self.destination & 0xFF00) | (value & 0xF0) as u16,
+            // Clearing bit 7 during an HBlank DMA stops it, keeping the remaining length
+            HDMA5 if self.hblank_mode && value & 0x80 == 0 => self.hblank_mode = false,
+            HDMA5 => {
+                self.remaining = value & 0x7F;
+                self.hblank_mode = value & 0x80 != 0;
+
+                if !self.hblank_mode {
+                    return self.remaining + 1;
+                }
+            }
+            _ => (),
+        }
+
+        0
+    }
+
+    /// Track whether the PPU is in HBlank, returning whether it just entered HBlank during an
+    /// HBlank DMA and a block should be copied.
+    pub fn hblank(&mut self, in_hblank: bool) -> bool {
+        let entered = in_hblank && !self.in_hblank;
+
+        self.in_hblank = in_hblank;
+
+        entered && self.hblank_mode
+    }
+
+    /// Take the next block to copy, returning where it's copied from and to, and stall the CPU
+    /// while it's copied. Ends the transfer after the last block.
+    ///
+    /// Copying takes the same time at either speed, so the CPU is stalled for `speed_factor` times
+    /// as many of its own m-cycles in double speed.
+    pub fn next_block(&mut self, speed_factor: u8) -> (u16, u16) {
+        let block = (self.source, 0x8000 | self.destination);
+
+        self.source = self.source.wrapping_add(BLOCK_SIZE);
+        self.destination = (self.destination + BLOCK_SIZE) & 0x1FF0;
+        self.stall += 8 * speed_factor as u16;
+
+        match self.remaining.checked_sub(1) {
+            Some(remaining) => self.remaining = remaining,
+            None => {
+                self.remaining = 0x7F;
+                self.hblank_mode = false;
+            }
+        }
+
+        block
+    }
+
+    /// Whether the CPU is stalled while blocks are copied, counting down each m-cycle.
+    pub const fn stalled(&self) -> bool {
+        self.stall > 0
+    }
+
+    /// Advance the stall by one m-cycle.
+    pub fn tick(&mut self) {
+        self.stall = self.stall.saturating_sub(1);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hdma5_reads_remaining_length() {
+        let mut hdma = Hdma::new();
+
+        assert_eq!(0xFF, hdma.read());
+
+        hdma.write(HDMA5, 0x82);
+        assert_eq!(0x02, hdma.read());
+
+        hdma.next_block(1);
+        assert_eq!(0x01, hdma.read());
+
+        // Cancelling keeps the remaining length, with bit 7 set
+        hdma.write(HDMA5, 0x00);
+        assert_eq!(0x81, hdma.read());
+    }
+
+    #[test]
+    fn hdma5_reads_ff_when_done() {
+        let mut hdma = Hdma::new();
+
+        hdma.write(HDMA5, 0x81);
+        hdma.next_block(1);
+        hdma.next_block(1);
+
+        assert_eq!(0xFF, hdma.read());
+        assert!(!hdma.hblank(true));
+    }
+
+    #[test]
+    fn addresses_are_masked() {
+        let mut hdma = Hdma::new();
+
+        hdma.write(0xFF51, 0xC1);
+        hdma.write(0xFF52, 0x2F);
+        hdma.write(0xFF53, 0xFF);
+        hdma.write(0xFF54, 0xFF);
+
+        assert_eq!((0xC120, 0x9FF0), hdma.next_block(1));
+        // The destination wraps around within VRAM
+        assert_eq!((0xC130, 0x8000), hdma.next_block(1));
+    }
+}
@@ -0,0 +1,263 @@
+use super::DeviceId;
+
+/// The number of IO registers, at 0xFF00–0xFF7F.
+pub const IO_SIZE: usize = 0x80;
+
+/// How a single IO register behaves when it's read from or written to.
+#[derive(Clone, Copy, Debug)]
+pub enum IoRegister {
+    /// Nothing is connected, reads return 0xFF and writes are ignored.
+    Unused,
+
+    /// Plain storage. Bits outside of `readable` always read as 1, and bits outside of `writable`
+    /// can't be changed by writes.
+    Masked {
+        readable: u8,
+        writable: u8,
+        value: u8,
+    },
+
+    /// A register with behavior of its own. Reads return `read(value)`, and writes replace `value`
+    /// with `write(value, written)`. `read` only gets to see `value`, so it can't have side effects
+    /// and peeking at the register is the same as reading it.
+    Handler {
+        value: u8,
+        read: fn(u8) -> u8,
+        write: fn(u8, u8) -> u8,
+    },
+
+    /// Handed to a mounted [BusDevice](super::BusDevice). Handled by the [Bus](super::Bus), as the
+    /// device lives there rather than in the register.
+    Device(DeviceId),
+}
+
+impl IoRegister {
+    /// A register that reads back everything written to it.
+    pub const fn storage(value: u8) -> Self {
+        Self::masked(0xFF, 0xFF, value)
+    }
+
+    /// A register with only some of its bits readable or writable.
+    pub const fn masked(readable: u8, writable: u8, value: u8) -> Self {
+        Self::Masked {
+            readable,
+            writable,
+            value,
+        }
+    }
+
+    fn read(&self) -> u8 {
+        match *self {
+            Self::Unused | Self::Device(_) => 0xFF,
+            Self::Masked {
+                readable, value, ..
+            } => value | !readable,
+            Self::Handler { value, read, .. } => read(value),
+        }
+    }
+
+    /// The value behind the register, as opposed to what reading it returns.
+    const fn value(&self) -> u8 {
+        match *self {
+            Self::Unused | Self::Device(_) => 0xFF,
+            Self::Masked { value, .. } | Self::Handler { value, .. } => value,
+        }
+    }
+
+    fn write(&mut self, written: u8) {
+        match self {
+            Self::Unused | Self::Device(_) => (),
+            Self::Masked {
+                writable, value, ..
+            } => *value = (*value & !*writable) | (written & *writable),
+            Self::Handler { value, write, .. } => *value = write(*value, written),
+        }
+    }
+}
+
+/// The IO registers at 0xFF00–0xFF7F, dispatched on the low byte of the address.
+pub struct Io {
+    registers: [IoRegister; IO_SIZE],
+}
+
+impl Io {
+    /// IO registers with no behavior yet, every read returns 0xFF and writes are ignored.
+    pub const fn new() -> Self {
+        Self {
+            registers: [IoRegister::Unused; IO_SIZE],
+        }
+    }
+
+    /// The IO registers of the DMG, each masked to the bits that exist on hardware. Unused bits
+    /// read as 1, and registers that don't exist read as 0xFF. The timer is left unused, as the
+    /// [Bus](super::Bus) mounts it as a device.
+    ///
+    /// See the [Pan Docs](https://gbdev.io/pandocs/Hardware_Reg_List.html).
+    pub fn new_dmg() -> Self {
+        let mut io = Self::new();
+
+        let registers = [
+            // Joypad, the buttons aren't wired up so they read as released
+            (0x00, IoRegister::masked(0x30, 0x30, 0xCF)),
+            // Serial
+            (0x01, IoRegister::storage(0x00)),
+            (0x02, IoRegister::masked(0x81, 0x81, 0x7E)),
+            // IF
+            (0x0F, IoRegister::masked(0x1F, 0x1F, 0xE1)),
+            // LCD
+            (0x40, IoRegister::storage(0x91)),
+            (0x41, IoRegister::masked(0x7F, 0x78, 0x85)),
+            (0x42, IoRegister::storage(0x00)),
+            (0x43, IoRegister::storage(0x00)),
+            (0x44, IoRegister::masked(0xFF, 0x00, 0x00)),
+            (0x45, IoRegister::storage(0x00)),
+            (0x46, IoRegister::storage(0xFF)),
+            (0x47, IoRegister::storage(0xFC)),
+            (0x48, IoRegister::storage(0x00)),
+            (0x49, IoRegister::storage(0x00)),
+            (0x4A, IoRegister::storage(0x00)),
+            (0x4B, IoRegister::storage(0x00)),
+            // Boot ROM mapping
+            (0x50, IoRegister::masked(0x00, 0x01, 0x00)),
+        ];
+
+        for (addr, register) in registers {
+            io.registers[addr] = register;
+        }
+
+        // Audio, with the bits that always read as 1, including any that are write-only
+        let audio: [u8; 0x17] = [
+            0x80, 0x3F, 0x00, 0xFF, 0xBF, // NR10–NR14
+            0xFF, 0x3F, 0x00, 0xFF, 0xBF, // NR20–NR24
+            0x7F, 0xFF, 0x9F, 0xFF, 0xBF, // NR30–NR34
+            0xFF, 0xFF, 0x00, 0x00, 0xBF, // NR40–NR44
+            0x00, 0x00, 0x70, // NR50–NR52
+        ];
+
+        for (addr, ones) in (0x10..).zip(audio) {
+            // NR20 and NR40 don't exist
+            if matches!(addr, 0x15 | 0x1F) {
+                continue;
+            }
+
+            // Only the power bit of NR52 is writable, the rest report which channels are on
+            let writable = if addr == 0x26 { 0x80 } else { 0xFF };
+
+            io.registers[addr] = IoRegister::masked(!ones, writable, 0x00);
+        }
+
+        // Wave RAM
+        for addr in 0x30..0x40 {
+            io.registers[addr] = IoRegister::storage(0x00);
+        }
+
+        io
+    }
+
+    /// Replace the register at `addr`, where only the low byte is used. This is how subsystems
+    /// give their registers behavior.
+    pub fn map(&mut self, addr: u16, register: IoRegister) {
+        self.registers[addr as usize % IO_SIZE] = register;
+    }
+
+    /// The device the register at `addr` is handed to, if any, where only the low byte is used.
+    pub fn device(&self, addr: u8) -> Option<DeviceId> {
+        match self.registers[addr as usize % IO_SIZE] {
+            IoRegister::Device(id) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// Read the register at `addr`, where only the low byte is used.
+    pub fn read(&self, addr: u8) -> u8 {
+        self.registers[addr as usize % IO_SIZE].read()
+    }
+
+    /// The value a read from the register at `addr` would return, without any of its side effects.
+    pub fn peek(&self, addr: u8) -> u8 {
+        // None of the registers have side effects on read
+        self.read(addr)
+    }
+
+    /// Write to the register at `addr`, where only the low byte is used.
+    pub fn write(&mut self, addr: u8, value: u8) {
+        self.registers[addr as usize % IO_SIZE].write(value);
+    }
+
+    /// The value behind every register, for save states. Registers without a value of their own
+    /// save 0xFF.
+    pub fn save_state(&self) -> Vec<u8> {
+        self.registers.iter().map(IoRegister::value).collect()
+    }
+
+    /// Restore the value behind every register from [Io::save_state], bypassing their write masks
+    /// and handlers. Registers without a value of their own are left alone.
+    pub fn load_state(&mut self, state: &[u8]) {
+        for (register, &saved) in self.registers.iter_mut().zip(state) {
+            if let IoRegister::Masked { value, .. } | IoRegister::Handler { value, .. } = register {
+                *value = saved;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn unused_registers_read_ff() {
+        let mut io = Io::new_dmg();
+
+        for addr in [0x03, 0x08, 0x15, 0x1F, 0x27, 0x4C, 0x7F] {
+            io.write(addr, 0x00);
+
+            assert_eq!(0xFF, io.read(addr), "FF{addr:02X}");
+        }
+    }
+
+    #[test]
+    fn unused_bits_read_1() {
+        let mut io = Io::new_dmg();
+
+        // (register, read back after writing 0x00, read back after writing 0xFF)
+        let registers = [
+            (0x02, 0x7E, 0xFF), // SC
+            (0x0F, 0xE0, 0xFF), // IF
+            (0x41, 0x85, 0xFD), // STAT, the mode and LY=LYC bits are read-only
+            (0x10, 0x80, 0xFF), // NR10
+            (0x13, 0xFF, 0xFF), // NR13 is write-only
+            (0x26, 0x70, 0xF0), // NR52, the channel bits are read-only
+            (0x50, 0xFF, 0xFF), // Boot ROM mapping
+        ];
+
+        for (addr, zero, ones) in registers {
+            io.write(addr, 0x00);
+            assert_eq!(zero, io.read(addr), "FF{addr:02X} after writing 0x00");
+
+            io.write(addr, 0xFF);
+            assert_eq!(ones, io.read(addr), "FF{addr:02X} after writing 0xFF");
+        }
+    }
+
+    #[test]
+    fn mapped_handler() {
+        let mut io = Io::new_dmg();
+
+        // Like DIV, any write resets the register
+        io.map(
+            0xFF04,
+            IoRegister::Handler {
+                value: 0x12,
+                read: |value| value,
+                write: |_, _| 0x00,
+            },
+        );
+
+        assert_eq!(0x12, io.read(0x04));
+
+        io.write(0x04, 0xFF);
+
+        assert_eq!(0x00, io.read(0x04));
+    }
+}
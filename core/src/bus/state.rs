@@ -0,0 +1,162 @@
+use std::fmt::Display;
+
+use super::hdma::Hdma;
+use super::{Bus, OamDma, Page, SpeedSwitch, IO_SIZE, PAGE_SIZE};
+use crate::cartridge::CartridgeState;
+
+/// The version of [BusState] that [Bus::save_state] produces. Bumped whenever its layout changes,
+/// so that older states are rejected rather than loaded wrong.
+pub const STATE_VERSION: u8 = 2;
+
+/// A save state that couldn't be loaded into a [Bus].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StateError {
+    /// The state was saved by a different version of the emulator, see [STATE_VERSION].
+    Version(u8),
+
+    /// The state doesn't fit the bus it's being loaded into, such as a CGB state loaded into a DMG
+    /// bus, or a mounted device or the cartridge was handed state it doesn't understand.
+    Layout,
+}
+
+impl Display for StateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Version(version) => write!(
+                f,
+                "save state version {version} isn't supported, expected {STATE_VERSION}"
+            ),
+            Self::Layout => write!(f, "save state doesn't match the layout of the bus"),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+/// Everything about a [Bus] that changes as it runs, as saved by [Bus::save_state]. Memory is kept
+/// as raw bytes, along with the cartridge's RAM and memory bank controller. ROM is left out as it
+/// comes from the cartridge and boot ROM, as is the layout of the memory map which comes from the
+/// model and mounted devices.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct BusState {
+    version: u8,
+
+    /// The contents of every page of plain memory, in address order.
+    memory: Vec<u8>,
+
+    /// The value behind each IO register.
+    io: Vec<u8>,
+
+    /// Whether the boot ROM was still mapped, it can be unmapped but never mapped back in.
+    boot_rom_mapped: bool,
+
+    dma: Option<OamDma>,
+    hdma: Option<Hdma>,
+    speed_switch: Option<SpeedSwitch>,
+    vram_banks: Option<Vec<u8>>,
+    wram_banks: Option<Vec<u8>>,
+
+    /// The state of each mounted device, in the order they were mounted.
+    devices: Vec<Vec<u8>>,
+
+    /// The state of the inserted cartridge, if there was one.
+    cartridge: Option<CartridgeState>,
+}
+
+impl Bus {
+    /// Pages with contents in the backing memory, which are saved as part of a [BusState].
+    fn saved_pages(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.pages.len())
+            .filter(|&page| matches!(self.pages[page], Page::Ram | Page::Oam(_) | Page::Mmio))
+    }
+
+    /// Save everything about the bus that changes as it runs, for restoring with
+    /// [Bus::load_state].
+    pub fn save_state(&self) -> BusState {
+        BusState {
+            version: STATE_VERSION,
+            memory: self
+                .saved_pages()
+                .flat_map(|page| &self.memory[page * PAGE_SIZE..][..PAGE_SIZE])
+                .copied()
+                .collect(),
+            io: self.io.save_state(),
+            boot_rom_mapped: self.boot_rom.is_some(),
+            dma: self.dma,
+            hdma: self.hdma.clone(),
+            speed_switch: self.speed_switch,
+            vram_banks: self.vram_banks.as_ref().map(|banks| banks.to_vec()),
+            wram_banks: self.wram_banks.as_ref().map(|banks| banks.to_vec()),
+            devices: self
+                .devices
+                .iter()
+                .map(|device| device.save_state())
+                .collect(),
+            cartridge: self
+                .cartridge
+                .as_ref()
+                .map(|cartridge| cartridge.save_state()),
+        }
+    }
+
+    /// Restore a state saved by [Bus::save_state]. The bus needs to have the same memory map as the
+    /// one the state was saved from, with the same devices mounted in the same order. The same
+    /// cartridge needs to be inserted already, and any boot ROM mapped, as their ROM isn't part of
+    /// the state.
+    ///
+    /// Nothing is restored if the state doesn't fit the memory map. A device or the cartridge
+    /// rejecting its part of the state fails the load partway through though, leaving the bus only
+    /// partly restored.
+    pub fn load_state(&mut self, state: &BusState) -> Result<(), StateError> {
+        if state.version != STATE_VERSION {
+            return Err(StateError::Version(state.version));
+        }
+
+        let pages: Vec<usize> = self.saved_pages().collect();
+
+        if state.memory.len() != pages.len() * PAGE_SIZE
+            || state.io.len() != IO_SIZE
+            || state.hdma.is_some() != self.hdma.is_some()
+            || state.speed_switch.is_some() != self.speed_switch.is_some()
+            || state.vram_banks.as_ref().map(Vec::len) != self.vram_banks.as_ref().map(|b| b.len())
+            || state.wram_banks.as_ref().map(Vec::len) != self.wram_banks.as_ref().map(|b| b.len())
+            || state.devices.len() != self.devices.len()
+            || state.cartridge.is_some() != self.cartridge.is_some()
+        {
+            return Err(StateError::Layout);
+        }
+
+        if let (Some(cartridge), Some(saved)) = (&mut self.cartridge, &state.cartridge) {
+            cartridge.load_state(saved)?;
+        }
+
+        for (device, device_state) in self.devices.iter_mut().zip(&state.devices) {
+            device.load_state(device_state)?;
+        }
+
+        self.io.load_state(&state.io);
+
+        for (page, contents) in pages.into_iter().zip(state.memory.chunks(PAGE_SIZE)) {
+            self.memory[page * PAGE_SIZE..][..PAGE_SIZE].copy_from_slice(contents);
+        }
+
+        if !state.boot_rom_mapped {
+            self.boot_rom = None;
+        }
+
+        self.dma = state.dma;
+        self.hdma.clone_from(&state.hdma);
+        self.speed_switch = state.speed_switch;
+
+        if let (Some(banks), Some(saved)) = (&mut self.vram_banks, &state.vram_banks) {
+            banks.copy_from_slice(saved);
+        }
+
+        if let (Some(banks), Some(saved)) = (&mut self.wram_banks, &state.wram_banks) {
+            banks.copy_from_slice(saved);
+        }
+
+        Ok(())
+    }
+}
@@ -1,16 +1,946 @@
+mod header;
+mod mbc1;
+mod mbc2;
+mod mbc3;
+mod mbc5;
+mod metadata;
+mod rtc;
+mod save;
+
+use header::Header;
+pub use header::{CartridgeType, HeaderWarning, LoadError, Mbc};
+use mbc1::Mbc1;
+use mbc2::Mbc2;
+use mbc3::Mbc3;
+use mbc5::Mbc5;
+pub use metadata::{CgbSupport, Licensee};
+pub use rtc::{Clock, SystemClock};
+use rtc::{Rtc, RtcState};
+pub use save::SaveError;
+
+use crate::StateError;
+
+/// The size of each bank of ROM.
+const ROM_BANK_SIZE: usize = 0x4000;
+
+/// The size of each bank of external RAM.
+const RAM_BANK_SIZE: usize = 0x2000;
+
+/// The state of whichever memory bank controller is on the cartridge.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+enum Controller {
+    /// ROM mapped straight into 0x0000–0x7FFF.
+    None,
+    Mbc1(Mbc1),
+    Mbc2(Mbc2),
+    Mbc3(Mbc3),
+    Mbc5(Mbc5),
+}
+
+impl Controller {
+    fn new(cartridge_type: CartridgeType, rom: &[u8]) -> Self {
+        match cartridge_type.mbc {
+            Mbc::Mbc1 => Self::Mbc1(Mbc1::new(Mbc1::detect_multicart(rom))),
+            Mbc::Mbc2 => Self::Mbc2(Mbc2::new()),
+            Mbc::Mbc3 => Self::Mbc3(Mbc3::new()),
+            Mbc::Mbc5 => Self::Mbc5(Mbc5::new(cartridge_type.rumble)),
+            Mbc::None => Self::None,
+        }
+    }
+}
+
+/// Everything about a [Cartridge] that changes as it runs, as saved by [Cartridge::save_state].
+/// The ROM is left out, as it comes from the cartridge the state is loaded into.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct CartridgeState {
+    controller: Controller,
+    ram: Vec<u8>,
+    rtc: Option<RtcState>,
+    mbc_registers: [u8; 4],
+}
+
 /// A cartridge plugged into the system, with its own bus pointing to ROM, optional RAM, and other
 /// MMIO like a camera, accelerometer, or real time clock.
 pub struct Cartridge {
     rom: Box<[u8]>,
+
+    /// What the header says is on the cartridge, or nothing for a ROM loaded with
+    /// [Cartridge::new_unchecked] that doesn't have a valid header.
+    header: Option<Header>,
+
+    controller: Controller,
+
+    /// The external RAM at 0xA000–0xBFFF, sized from the header, or the RAM built into an MBC2.
+    /// Empty for a cartridge without any.
+    ram: Box<[u8]>,
+
+    /// The real time clock of an MBC3 that has one.
+    rtc: Option<Rtc>,
+
+    /// The last value written to each 0x2000 byte range of ROM, where a memory bank controller has
+    /// its control registers.
+    mbc_registers: [u8; 4],
+
+    /// Called with whether the rumble motor is on whenever it turns on or off.
+    rumble: Option<Box<dyn Fn(bool) + Send>>,
 }
 
 impl Cartridge {
-    pub fn new(data: Box<[u8]>) -> Self {
-        Self { rom: data }
+    /// A cartridge with `rom`, as long as its header makes sense and matches the size of the ROM.
+    pub fn new(rom: Box<[u8]>) -> Result<Self, LoadError> {
+        let header = Header::parse(&rom)?;
+
+        Ok(Self {
+            header: Some(header),
+            ..Self::new_unchecked(rom)
+        })
+    }
+
+    /// A cartridge with `rom`, whatever its header says. For tests that only care about a few
+    /// bytes of ROM, where reads past the end of the ROM return 0xFF.
+    pub fn new_unchecked(rom: Box<[u8]>) -> Self {
+        let header = Header::parse(&rom).ok();
+        let cartridge_type =
+            header.map_or_else(CartridgeType::default, |header| header.cartridge_type);
+
+        let ram_size = match (cartridge_type.mbc, header) {
+            (Mbc::Mbc2, _) => Mbc2::RAM_SIZE,
+            (_, Some(header)) => header.ram_size,
+            (_, None) => 0,
+        };
+
+        Self {
+            header,
+            controller: Controller::new(cartridge_type, &rom),
+            rom,
+            ram: vec![0xFF; ram_size].into(),
+            rtc: cartridge_type
+                .timer
+                .then(|| Rtc::new(Box::new(SystemClock))),
+            mbc_registers: [0x00; 4],
+            rumble: None,
+        }
+    }
+
+    /// What's on the cartridge besides its ROM, if it has a valid header.
+    pub fn cartridge_type(&self) -> Option<CartridgeType> {
+        self.header.map(|header| header.cartridge_type)
+    }
+
+    /// Whether the cartridge is an MBC1 multicart, or MBC1M, which is detected from the ROM.
+    pub fn multicart(&self) -> bool {
+        matches!(self.controller, Controller::Mbc1(mbc1) if mbc1.multicart())
+    }
+
+    /// Override whether an MBC1 cartridge is a multicart, for ROMs that fool the detection. Does
+    /// nothing for any other cartridge.
+    pub fn set_multicart(&mut self, multicart: bool) {
+        if let Controller::Mbc1(mbc1) = &mut self.controller {
+            mbc1.set_multicart(multicart);
+        }
     }
 
-    /// Read an 8-bit value from the specified address. Affected by cartridge state.
+    /// Whether the rumble motor of an MBC5 is on, for a frontend to poll. Always `false` for a
+    /// cartridge without one.
+    pub fn rumble_active(&self) -> bool {
+        matches!(self.controller, Controller::Mbc5(mbc5) if mbc5.rumble_active())
+    }
+
+    /// Call `rumble` with whether the rumble motor is on whenever it turns on or off, rather than
+    /// polling [Cartridge::rumble_active]. Never called for a cartridge without one.
+    pub fn set_rumble(&mut self, rumble: impl Fn(bool) + Send + 'static) {
+        self.rumble = Some(Box::new(rumble));
+    }
+
+    /// Remove the callback set with [Cartridge::set_rumble].
+    pub fn clear_rumble(&mut self) {
+        self.rumble = None;
+    }
+
+    /// Replace where the real time clock gets the time from, which is [SystemClock] to begin with.
+    /// Does nothing for a cartridge without one.
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) {
+        if let Some(rtc) = &mut self.rtc {
+            rtc.set_clock(Box::new(clock));
+        }
+    }
+
+    /// Read an 8-bit value from the specified address, either ROM at 0x0000–0x7FFF or external RAM
+    /// at 0xA000–0xBFFF. Affected by cartridge state.
+    ///
+    /// Banks past the end of ROM or RAM wrap around, as the memory bank controller only has as
+    /// many address lines as they need. Addresses past the end of a ROM that's too small for even
+    /// that read as open bus, as does RAM that's disabled or missing.
     pub fn read(&self, addr: u16) -> u8 {
-        self.rom[addr as usize]
+        let offset = match (addr, self.controller) {
+            (0xA000..=0xBFFF, _) => {
+                if let (Some(register), Some(rtc)) = (self.rtc_register(), &self.rtc) {
+                    return rtc.read(register);
+                }
+
+                return self.ram_offset(addr).map_or(0xFF, |offset| {
+                    // The MBC2's RAM only stores the low half of each byte, the rest read as 1,
+                    // whatever a save imported from elsewhere has there
+                    match self.controller {
+                        Controller::Mbc2(_) => self.ram[offset] | 0xF0,
+                        _ => self.ram[offset],
+                    }
+                });
+            }
+            (0x8000.., _) => return 0xFF,
+            (_, Controller::None) => addr as usize,
+            (_, Controller::Mbc1(mbc1)) => self.rom_offset(mbc1.rom_bank(addr), addr),
+            (_, Controller::Mbc2(mbc2)) => self.rom_offset(mbc2.rom_bank(addr), addr),
+            (_, Controller::Mbc3(mbc3)) => self.rom_offset(mbc3.rom_bank(addr), addr),
+            (_, Controller::Mbc5(mbc5)) => self.rom_offset(mbc5.rom_bank(addr), addr),
+        };
+
+        self.rom.get(offset).copied().unwrap_or(0xFF)
+    }
+
+    /// The offset into the ROM of `addr` in ROM `bank`, wrapping around past the end of the ROM.
+    fn rom_offset(&self, bank: usize, addr: u16) -> usize {
+        let offset = bank * ROM_BANK_SIZE + addr as usize % ROM_BANK_SIZE;
+
+        offset & (self.rom.len().next_power_of_two() - 1)
+    }
+
+    /// The offset into external RAM of `addr` in 0xA000–0xBFFF, in whichever RAM bank is mapped,
+    /// or `None` if there's no RAM or it's disabled. RAM smaller than a bank repeats through it.
+    fn ram_offset(&self, addr: u16) -> Option<usize> {
+        let bank = match self.controller {
+            // Without a memory bank controller, there's nothing to disable RAM with
+            Controller::None => Some(0),
+            Controller::Mbc1(mbc1) => mbc1.ram_bank(),
+            Controller::Mbc2(mbc2) => mbc2.ram_enabled().then_some(0),
+            Controller::Mbc3(mbc3) => mbc3.ram_bank(),
+            Controller::Mbc5(mbc5) => mbc5.ram_bank(),
+        }?;
+
+        let offset = bank * RAM_BANK_SIZE + addr as usize % RAM_BANK_SIZE;
+
+        // Every RAM size is a power of 2
+        (!self.ram.is_empty()).then(|| offset & (self.ram.len() - 1))
+    }
+
+    /// The register of the real time clock mapped over external RAM, if any.
+    fn rtc_register(&self) -> Option<usize> {
+        match self.controller {
+            Controller::Mbc3(mbc3) if self.rtc.is_some() => mbc3.rtc_register(),
+            _ => None,
+        }
+    }
+
+    /// Write an 8-bit value to the specified address. Writes to ROM never change its contents,
+    /// they're control writes to the memory bank controller instead. Writes to external RAM are
+    /// ignored while it's disabled.
+    pub fn write(&mut self, addr: u16, value: u8) {
+        if addr >= 0x8000 {
+            // Only 0xA000–0xBFFF is wired to the cartridge, like for reads
+            if !(0xA000..=0xBFFF).contains(&addr) {
+                return;
+            }
+
+            if let (Some(register), Some(rtc)) = (self.rtc_register(), &mut self.rtc) {
+                rtc.write(register, value);
+            } else if let Some(offset) = self.ram_offset(addr) {
+                // Keep the MBC2's missing top half set in exported saves too
+                self.ram[offset] = match self.controller {
+                    Controller::Mbc2(_) => value | 0xF0,
+                    _ => value,
+                };
+            }
+
+            return;
+        }
+
+        if let Some(register) = self.mbc_registers.get_mut(addr as usize >> 13) {
+            *register = value;
+        }
+
+        let rumble_active = self.rumble_active();
+
+        match &mut self.controller {
+            Controller::None => (),
+            Controller::Mbc1(mbc1) => mbc1.write(addr, value),
+            Controller::Mbc2(mbc2) => mbc2.write(addr, value),
+            Controller::Mbc3(mbc3) => mbc3.write(addr, value),
+            Controller::Mbc5(mbc5) => mbc5.write(addr, value),
+        }
+
+        if let (0x6000..=0x7FFF, Some(rtc)) = (addr, &mut self.rtc) {
+            rtc.write_latch(value);
+        }
+
+        if let (true, Some(rumble)) = (rumble_active != self.rumble_active(), &self.rumble) {
+            rumble(!rumble_active);
+        }
+    }
+
+    /// Whether the header has the Nintendo logo, without which the DMG's boot ROM won't boot it.
+    pub fn logo_valid(&self) -> bool {
+        header::logo_valid(&self.rom)
+    }
+
+    /// Whether the header checksum at 0x014D matches the header. Every boot ROM locks up on a
+    /// mismatch, but without one nothing checks it.
+    pub fn header_checksum_valid(&self) -> bool {
+        let computed = header::header_checksum(&self.rom);
+
+        computed.is_some() && computed == header::stored_header_checksum(&self.rom)
+    }
+
+    /// Whether the global checksum at 0x014E–0x014F matches the ROM. Nothing checks this, not even
+    /// the boot ROM, so plenty of ROMs get it wrong.
+    pub fn global_checksum_valid(&self) -> bool {
+        header::stored_global_checksum(&self.rom) == Some(header::global_checksum(&self.rom))
+    }
+
+    /// Everything wrong with the header that doesn't stop the cartridge from loading, but might
+    /// stop it from booting.
+    pub fn header_warnings(&self) -> Vec<HeaderWarning> {
+        let (Some(computed), Some(stored), Some(global)) = (
+            header::header_checksum(&self.rom),
+            header::stored_header_checksum(&self.rom),
+            header::stored_global_checksum(&self.rom),
+        ) else {
+            return vec![HeaderWarning::Truncated];
+        };
+
+        let mut warnings = Vec::new();
+
+        if !self.logo_valid() {
+            warnings.push(HeaderWarning::Logo);
+        }
+
+        if computed != stored {
+            warnings.push(HeaderWarning::HeaderChecksum { stored, computed });
+        }
+
+        if !self.global_checksum_valid() {
+            warnings.push(HeaderWarning::GlobalChecksum {
+                stored: global,
+                computed: header::global_checksum(&self.rom),
+            });
+        }
+
+        warnings
+    }
+
+    /// Save everything about the cartridge that changes as it runs, for restoring with
+    /// [Cartridge::load_state]. That's the state of the memory bank controller, external RAM, and
+    /// real time clock.
+    pub fn save_state(&self) -> CartridgeState {
+        CartridgeState {
+            controller: self.controller,
+            ram: self.ram.to_vec(),
+            rtc: self.rtc.as_ref().map(Rtc::save_state),
+            mbc_registers: self.mbc_registers,
+        }
+    }
+
+    /// Restore a state saved by [Cartridge::save_state]. Nothing is restored if it was saved from
+    /// a different kind of cartridge, with a different memory bank controller or amount of RAM.
+    pub fn load_state(&mut self, state: &CartridgeState) -> Result<(), StateError> {
+        if std::mem::discriminant(&self.controller) != std::mem::discriminant(&state.controller)
+            || self.ram.len() != state.ram.len()
+            || self.rtc.is_some() != state.rtc.is_some()
+        {
+            return Err(StateError::Layout);
+        }
+
+        self.controller = state.controller;
+        self.ram.copy_from_slice(&state.ram);
+        self.mbc_registers = state.mbc_registers;
+
+        if let (Some(rtc), Some(saved)) = (&mut self.rtc, &state.rtc) {
+            rtc.load_state(saved);
+        }
+
+        Ok(())
+    }
+
+    /// The value last written to the memory bank controller register at `addr`, which takes up one
+    /// of 0x0000–0x1FFF, 0x2000–0x3FFF, 0x4000–0x5FFF, or 0x6000–0x7FFF.
+    pub fn mbc_register(&self, addr: u16) -> Option<u8> {
+        self.mbc_registers.get(addr as usize >> 13).copied()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    /// A 32 KiB ROM with the logo and both checksums filled in.
+    fn rom() -> Vec<u8> {
+        let mut rom = vec![0x00; 0x8000];
+
+        rom[0x0104..0x0134].copy_from_slice(&header::LOGO);
+        rom[0x0134..0x013F].copy_from_slice(b"DOTMATRIX  ");
+        rom[0x014D] = header::header_checksum(&rom).unwrap();
+
+        let global = header::global_checksum(&rom);
+        rom[0x014E..0x0150].copy_from_slice(&global.to_be_bytes());
+
+        rom
+    }
+
+    #[test]
+    fn valid_header() {
+        let cartridge = Cartridge::new(rom().into()).unwrap();
+
+        assert!(cartridge.logo_valid());
+        assert!(cartridge.header_checksum_valid());
+        assert!(cartridge.global_checksum_valid());
+        assert_eq!(Vec::<HeaderWarning>::new(), cartridge.header_warnings());
+    }
+
+    #[test]
+    fn corrupt_header() {
+        let mut rom = rom();
+
+        // Both checksums cover the title, but only the global checksum covers the rest of the ROM
+        rom[0x0134] ^= 0x01;
+        rom[0x4000] = 0x01;
+
+        let cartridge = Cartridge::new(rom.into()).unwrap();
+
+        assert!(cartridge.logo_valid());
+        assert!(!cartridge.header_checksum_valid());
+        assert!(!cartridge.global_checksum_valid());
+        assert_eq!(
+            vec![
+                HeaderWarning::HeaderChecksum {
+                    stored: 0xEB,
+                    computed: 0xEA
+                },
+                HeaderWarning::GlobalChecksum {
+                    stored: 0x192D,
+                    computed: 0x192F
+                },
+            ],
+            cartridge.header_warnings()
+        );
+    }
+
+    #[test]
+    fn corrupt_logo() {
+        let mut rom = rom();
+
+        rom[0x0104] = 0x00;
+
+        let cartridge = Cartridge::new(rom.into()).unwrap();
+
+        assert!(!cartridge.logo_valid());
+        assert!(cartridge.header_checksum_valid());
+    }
+
+    #[test]
+    fn truncated_header() {
+        let cartridge = Cartridge::new_unchecked(vec![0x00; 0x0100].into());
+
+        assert!(!cartridge.header_checksum_valid());
+        assert!(!cartridge.global_checksum_valid());
+        assert_eq!(vec![HeaderWarning::Truncated], cartridge.header_warnings());
+    }
+
+    #[test]
+    fn load_errors() {
+        let load = |edit: fn(&mut Vec<u8>)| {
+            let mut rom = rom();
+
+            edit(&mut rom);
+            Cartridge::new(rom.into()).err()
+        };
+
+        assert_eq!(None, load(|_| ()));
+
+        assert_eq!(
+            Some(LoadError::TooShort { len: 3 }),
+            load(|rom| rom.truncate(3))
+        );
+        assert_eq!(
+            Some(LoadError::RomSize {
+                code: 0x00,
+                len: 0x10000
+            }),
+            load(|rom| rom.resize(0x10000, 0x00))
+        );
+        assert_eq!(
+            Some(LoadError::RomSize {
+                code: 0x09,
+                len: 0x8000
+            }),
+            load(|rom| rom[0x0148] = 0x09)
+        );
+        assert_eq!(
+            Some(LoadError::UnsupportedType(0xFC)),
+            load(|rom| rom[0x0147] = 0xFC)
+        );
+
+        // RAM on a cartridge without any, no RAM on one that has it, and RAM on an MBC2
+        assert_eq!(
+            Some(LoadError::RamSize {
+                type_code: 0x01,
+                ram_code: 0x02
+            }),
+            load(|rom| rom[0x0147..0x014A].copy_from_slice(&[0x01, 0x00, 0x02]))
+        );
+        assert_eq!(
+            Some(LoadError::RamSize {
+                type_code: 0x03,
+                ram_code: 0x00
+            }),
+            load(|rom| rom[0x0147] = 0x03)
+        );
+        assert_eq!(
+            Some(LoadError::RamSize {
+                type_code: 0x06,
+                ram_code: 0x03
+            }),
+            load(|rom| rom[0x0147..0x014A].copy_from_slice(&[0x06, 0x00, 0x03]))
+        );
+    }
+
+    #[test]
+    fn header_describes_hardware() {
+        let mut rom = rom();
+
+        rom.resize(0x20000, 0x00);
+        rom[0x0147..0x014A].copy_from_slice(&[0x10, 0x02, 0x03]);
+
+        let cartridge = Cartridge::new(rom.into()).unwrap();
+
+        assert_eq!(
+            Some(CartridgeType {
+                mbc: Mbc::Mbc3,
+                ram: true,
+                battery: true,
+                timer: true,
+                rumble: false,
+            }),
+            cartridge.cartridge_type()
+        );
+        assert_eq!(
+            None,
+            Cartridge::new_unchecked([0x00; 3].into()).cartridge_type()
+        );
+    }
+
+    /// A ROM of `banks` banks with the header of an MBC1 cartridge, where each bank starts with its
+    /// own number. Other cartridge types only need their type and RAM size changed.
+    fn banked_rom(banks: usize) -> Vec<u8> {
+        let mut rom = vec![0x00; banks * ROM_BANK_SIZE];
+
+        for bank in 0..banks {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+            rom[bank * ROM_BANK_SIZE + 1] = (bank >> 8) as u8;
+        }
+
+        rom[0x0147] = 0x01;
+        rom[0x0148] = banks.trailing_zeros() as u8 - 1;
+        rom
+    }
+
+    #[test]
+    fn rom_only_is_mapped_flat() {
+        let mut rom = banked_rom(2);
+        rom[0x0147] = 0x00;
+
+        let mut cartridge = Cartridge::new(rom.into()).unwrap();
+
+        // Nothing to bank with, so control writes go nowhere
+        cartridge.write(0x2000, 0x00);
+        assert_eq!(0x00, cartridge.read(0x0000));
+        assert_eq!(0x01, cartridge.read(0x4000));
+
+        cartridge.write(0xA000, 0x12);
+        assert_eq!(0xFF, cartridge.read(0xA000));
+
+        // A ROM too small for even the first 32 KiB reads as open bus past its end
+        let cartridge = Cartridge::new_unchecked(vec![0x12; 0x200].into());
+        assert_eq!(0x12, cartridge.read(0x01FF));
+        assert_eq!(0xFF, cartridge.read(0x0200));
+        assert_eq!(0xFF, cartridge.read(0x7FFF));
+    }
+
+    #[test]
+    fn rom_ram_is_always_enabled() {
+        let mut rom = banked_rom(2);
+
+        rom[0x0147] = 0x09;
+        rom[0x0149] = 0x02;
+
+        let mut cartridge = Cartridge::new(rom.into()).unwrap();
+
+        assert_eq!(Some(Mbc::None), cartridge.cartridge_type().map(|ty| ty.mbc));
+        assert_eq!(0xFF, cartridge.read(0xA000));
+
+        cartridge.write(0x0000, 0x00);
+        cartridge.write(0xA000, 0x12);
+        cartridge.write(0xBFFF, 0x34);
+
+        assert_eq!(0x12, cartridge.read(0xA000));
+        assert_eq!(0x34, cartridge.read(0xBFFF));
+        assert_eq!(Some(0x2000), cartridge.export_save().map(|save| save.len()));
+    }
+
+    #[test]
+    fn ram_is_only_at_a000() {
+        let mut rom = banked_rom(2);
+
+        rom[0x0147] = 0x09;
+        rom[0x0149] = 0x02;
+
+        let mut cartridge = Cartridge::new(rom.into()).unwrap();
+
+        cartridge.write(0xA000, 0x12);
+
+        for addr in [0x8000, 0x9FFF, 0xC000, 0xE000, 0xFFFF] {
+            cartridge.write(addr, 0x99);
+            assert_eq!(0xFF, cartridge.read(addr), "{addr:04X}");
+        }
+
+        assert_eq!(0x12, cartridge.read(0xA000));
+        assert_eq!(0xFF, cartridge.read(0xBFFF));
+    }
+
+    #[test]
+    fn mbc1_switches_rom_banks() {
+        let mut cartridge = Cartridge::new(banked_rom(128).into()).unwrap();
+
+        assert_eq!(Some(Mbc::Mbc1), cartridge.cartridge_type().map(|ty| ty.mbc));
+        assert_eq!(0x00, cartridge.read(0x0000));
+        assert_eq!(0x01, cartridge.read(0x4000));
+
+        for bank in [0x02, 0x1F, 0x21, 0x45, 0x7F] {
+            cartridge.write(0x2000, bank);
+            cartridge.write(0x4000, bank >> 5);
+
+            assert_eq!(bank, cartridge.read(0x4000));
+        }
+
+        // 0x20, 0x40, and 0x60 are only reachable at 0x0000–0x3FFF, in mode 1
+        cartridge.write(0x2000, 0x00);
+        cartridge.write(0x4000, 0x02);
+        assert_eq!(0x41, cartridge.read(0x4000));
+        assert_eq!(0x00, cartridge.read(0x0000));
+
+        cartridge.write(0x6000, 0x01);
+        assert_eq!(0x40, cartridge.read(0x0000));
+    }
+
+    #[test]
+    fn mbc1_banks_wrap_around() {
+        let mut cartridge = Cartridge::new(banked_rom(8).into()).unwrap();
+
+        cartridge.write(0x2000, 0x0B);
+        assert_eq!(0x03, cartridge.read(0x4000));
+
+        // BANK2 is past the end of a 128 KiB ROM entirely, even in mode 1
+        cartridge.write(0x4000, 0x01);
+        cartridge.write(0x6000, 0x01);
+        assert_eq!(0x03, cartridge.read(0x4000));
+        assert_eq!(0x00, cartridge.read(0x0000));
+    }
+
+    #[test]
+    fn mbc1_switches_ram_banks() {
+        let mut rom = banked_rom(4);
+
+        rom[0x0147] = 0x03;
+        rom[0x0149] = 0x03;
+
+        let mut cartridge = Cartridge::new(rom.into()).unwrap();
+
+        // Disabled RAM reads as open bus and ignores writes
+        cartridge.write(0xA000, 0x12);
+        assert_eq!(0xFF, cartridge.read(0xA000));
+
+        cartridge.write(0x0000, 0x0A);
+        cartridge.write(0x6000, 0x01);
+
+        for bank in 0..4 {
+            cartridge.write(0x4000, bank);
+            cartridge.write(0xA000, bank | 0x10);
+            cartridge.write(0xBFFF, bank | 0x20);
+        }
+
+        for bank in 0..4 {
+            cartridge.write(0x4000, bank);
+            assert_eq!(bank | 0x10, cartridge.read(0xA000));
+            assert_eq!(bank | 0x20, cartridge.read(0xBFFF));
+        }
+
+        // In mode 0, bank 0 is mapped whatever BANK2 says
+        cartridge.write(0x6000, 0x00);
+        assert_eq!(0x10, cartridge.read(0xA000));
+
+        // Disabling RAM keeps what's in it
+        cartridge.write(0x0000, 0x00);
+        cartridge.write(0xA000, 0x42);
+        assert_eq!(0xFF, cartridge.read(0xA000));
+
+        cartridge.write(0x0000, 0x0A);
+        assert_eq!(0x10, cartridge.read(0xA000));
+    }
+
+    #[test]
+    fn mbc1_mode_1_on_large_rom() {
+        let mut rom = banked_rom(64);
+
+        rom[0x0147] = 0x02;
+        rom[0x0149] = 0x02;
+
+        let mut cartridge = Cartridge::new(rom.into()).unwrap();
+
+        cartridge.write(0x0000, 0x0A);
+        cartridge.write(0xA000, 0x42);
+        cartridge.write(0x4000, 0x01);
+        cartridge.write(0x2000, 0x03);
+
+        // In mode 0, BANK2 only reaches the switchable ROM bank
+        assert_eq!(0x00, cartridge.read(0x0000));
+        assert_eq!(0x23, cartridge.read(0x4000));
+        assert_eq!(0x42, cartridge.read(0xA000));
+
+        // In mode 1 it reaches 0x0000–0x3FFF too, while 8 KiB of RAM only has the one bank
+        cartridge.write(0x6000, 0x01);
+        assert_eq!(0x20, cartridge.read(0x0000));
+        assert_eq!(0x23, cartridge.read(0x4000));
+        assert_eq!(0x42, cartridge.read(0xA000));
+    }
+
+    #[test]
+    fn mbc1_multicart_is_detected() {
+        let mut rom = banked_rom(64);
+
+        assert!(!Cartridge::new(rom.clone().into()).unwrap().multicart());
+
+        // The second game, whose header includes the logo
+        rom[0x40104..0x40134].copy_from_slice(&header::LOGO);
+
+        let mut cartridge = Cartridge::new(rom.into()).unwrap();
+        assert!(cartridge.multicart());
+
+        cartridge.write(0x4000, 0x01);
+        cartridge.write(0x2000, 0x12);
+        assert_eq!(0x12, cartridge.read(0x4000));
+
+        cartridge.write(0x4000, 0x03);
+        assert_eq!(0x32, cartridge.read(0x4000));
+
+        // As a plain MBC1, BANK1 picks bank 0x12 of the whole ROM rather than bank 2 of a game
+        cartridge.write(0x4000, 0x00);
+        cartridge.set_multicart(false);
+        assert!(!cartridge.multicart());
+        assert_eq!(0x12, cartridge.read(0x4000));
+    }
+
+    #[test]
+    fn mbc2_banks_rom_and_has_ram() {
+        let mut rom = banked_rom(16);
+
+        rom[0x0147] = 0x06;
+
+        let mut cartridge = Cartridge::new(rom.into()).unwrap();
+
+        cartridge.write(0x2100, 0x0F);
+        assert_eq!(0x0F, cartridge.read(0x4000));
+
+        // Disabled until 0x0A is written with bit 8 of the address clear
+        cartridge.write(0xA000, 0x05);
+        assert_eq!(0xFF, cartridge.read(0xA000));
+
+        cartridge.write(0x0000, 0x0A);
+
+        // Only the low half of each byte is stored
+        cartridge.write(0xA000, 0x05);
+        cartridge.write(0xA1FF, 0xAB);
+        assert_eq!(0xF5, cartridge.read(0xA000));
+        assert_eq!(0xFB, cartridge.read(0xA1FF));
+
+        // The 512 half-bytes echo through all of 0xA000–0xBFFF
+        assert_eq!(0xF5, cartridge.read(0xA200));
+        assert_eq!(0xFB, cartridge.read(0xBFFF));
+
+        cartridge.write(0xB234, 0x0C);
+        assert_eq!(0xFC, cartridge.read(0xA034));
+    }
+
+    #[test]
+    fn mbc3_banks_rom_and_ram() {
+        let mut rom = banked_rom(128);
+
+        rom[0x0147] = 0x13;
+        rom[0x0149] = 0x03;
+
+        let mut cartridge = Cartridge::new(rom.into()).unwrap();
+
+        // Every bank of 2 MiB is reachable, including the ones the MBC1 leaves out
+        for bank in 1..128 {
+            cartridge.write(0x2000, bank);
+            assert_eq!(bank, cartridge.read(0x4000));
+        }
+
+        cartridge.write(0x2000, 0x00);
+        assert_eq!(0x01, cartridge.read(0x4000));
+
+        // Disabled RAM reads as open bus and ignores writes
+        cartridge.write(0x4000, 0x02);
+        cartridge.write(0xA000, 0x12);
+        assert_eq!(0xFF, cartridge.read(0xA000));
+
+        cartridge.write(0x0000, 0x0A);
+
+        for bank in 0..4 {
+            cartridge.write(0x4000, bank);
+            cartridge.write(0xB000, bank | 0x10);
+        }
+
+        for bank in 0..4 {
+            cartridge.write(0x4000, bank);
+            assert_eq!(bank | 0x10, cartridge.read(0xB000));
+        }
+
+        cartridge.write(0x0000, 0x00);
+        assert_eq!(0xFF, cartridge.read(0xB000));
+    }
+
+    #[test]
+    fn mbc5_banks_all_of_8_mib() {
+        let mut rom = banked_rom(512);
+
+        rom[0x0147] = 0x1B;
+        rom[0x0149] = 0x04;
+
+        let mut cartridge = Cartridge::new(rom.into()).unwrap();
+        assert_eq!(Some(Mbc::Mbc5), cartridge.cartridge_type().map(|ty| ty.mbc));
+
+        for bank in [0x001_u16, 0x0FF, 0x100, 0x123, 0x1FF] {
+            cartridge.write(0x2000, bank as u8);
+            cartridge.write(0x3000, (bank >> 8) as u8);
+
+            assert_eq!(
+                bank.to_le_bytes(),
+                [cartridge.read(0x4000), cartridge.read(0x4001)]
+            );
+        }
+
+        // Bank 0 can be mapped in twice, rather than being swapped for bank 1
+        cartridge.write(0x2000, 0x00);
+        cartridge.write(0x3000, 0x00);
+        assert_eq!(
+            [0x00, 0x00],
+            [cartridge.read(0x4000), cartridge.read(0x4001)]
+        );
+
+        cartridge.write(0x0000, 0x0A);
+
+        for bank in 0..16 {
+            cartridge.write(0x4000, bank);
+            cartridge.write(0xBFFF, bank | 0x80);
+        }
+
+        for bank in 0..16 {
+            cartridge.write(0x4000, bank);
+            assert_eq!(bank | 0x80, cartridge.read(0xBFFF));
+        }
+    }
+
+    #[test]
+    fn mbc5_drives_rumble() {
+        let mut rom = banked_rom(4);
+
+        rom[0x0147] = 0x1D;
+        rom[0x0149] = 0x04;
+
+        let mut cartridge = Cartridge::new(rom.into()).unwrap();
+        let changes = Arc::new(Mutex::new(Vec::new()));
+
+        let record = Arc::clone(&changes);
+        cartridge.set_rumble(move |active| record.lock().unwrap().push(active));
+
+        cartridge.write(0x0000, 0x0A);
+
+        // Writing the same state again doesn't count as a change
+        for value in [0x08, 0x0A, 0x02, 0x03, 0x0F] {
+            cartridge.write(0x4000, value);
+            assert_eq!(value & 0x08 != 0, cartridge.rumble_active());
+        }
+
+        assert_eq!(
+            vec![true, false, true],
+            std::mem::take(&mut *changes.lock().unwrap())
+        );
+
+        // With the motor on, only 8 banks of RAM are left
+        for bank in 0..8 {
+            cartridge.write(0x4000, bank | 0x08);
+            cartridge.write(0xA000, bank);
+        }
+
+        for bank in 0..8 {
+            cartridge.write(0x4000, bank);
+            assert_eq!(bank, cartridge.read(0xA000));
+        }
+
+        cartridge.write(0x4000, 0x0F);
+        assert_eq!(0x07, cartridge.read(0xA000));
+        assert_eq!(
+            vec![false, true],
+            std::mem::take(&mut *changes.lock().unwrap())
+        );
+    }
+
+    #[test]
+    fn mbc3_maps_rtc_registers() {
+        let mut rom = banked_rom(4);
+
+        rom[0x0147] = 0x10;
+        rom[0x0149] = 0x02;
+
+        let mut cartridge = Cartridge::new(rom.into()).unwrap();
+        let time = Arc::new(AtomicU64::new(0));
+
+        let clock = Arc::clone(&time);
+        cartridge.set_clock(move || clock.load(Ordering::Relaxed));
+
+        cartridge.write(0x0000, 0x0A);
+        cartridge.write(0xA000, 0x42);
+
+        // 1 day, 01:01:01 later
+        time.store(90061, Ordering::Relaxed);
+        cartridge.write(0x6000, 0x00);
+        cartridge.write(0x6000, 0x01);
+        time.store(90071, Ordering::Relaxed);
+
+        let registers = (0x08..=0x0C).map(|register| {
+            cartridge.write(0x4000, register);
+            cartridge.read(0xA000)
+        });
+
+        assert_eq!(vec![1, 1, 1, 1, 0], registers.collect::<Vec<_>>());
+
+        // Writing to a register sets the time, without touching RAM
+        cartridge.write(0x4000, 0x08);
+        cartridge.write(0xBFFF, 0x00);
+        cartridge.write(0x6000, 0x00);
+        cartridge.write(0x6000, 0x01);
+        assert_eq!(0x00, cartridge.read(0xA000));
+
+        cartridge.write(0x4000, 0x00);
+        assert_eq!(0x42, cartridge.read(0xA000));
+
+        // Disabling RAM disables the clock too
+        cartridge.write(0x4000, 0x0B);
+        assert_eq!(0x01, cartridge.read(0xA000));
+        cartridge.write(0x0000, 0x00);
+        assert_eq!(0xFF, cartridge.read(0xA000));
     }
 }
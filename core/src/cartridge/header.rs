@@ -0,0 +1,296 @@
+use std::fmt::Display;
+
+/// Where the Nintendo logo is in the header, which the boot ROM compares against [LOGO].
+const LOGO_START: usize = 0x0104;
+
+/// The first byte covered by the header checksum, the title.
+const CHECKSUM_START: usize = 0x0134;
+
+/// Where the cartridge type is stored, see [CartridgeType].
+const CARTRIDGE_TYPE: usize = 0x0147;
+
+/// Where the ROM size code is stored, see [rom_size].
+const ROM_SIZE: usize = 0x0148;
+
+/// Where the RAM size code is stored, see [ram_size].
+const RAM_SIZE: usize = 0x0149;
+
+/// Where the header checksum is stored, just after the last byte it covers.
+const HEADER_CHECKSUM: usize = 0x014D;
+
+/// Where the big-endian global checksum is stored.
+const GLOBAL_CHECKSUM: usize = 0x014E;
+
+/// The end of the header, where every ROM needs to reach.
+pub const HEADER_END: usize = 0x0150;
+
+/// The Nintendo logo, as every licensed cartridge has it in its header. The DMG's boot ROM scrolls
+/// it onto the screen from the cartridge, then refuses to boot if it doesn't match its own copy.
+///
+/// See the [Pan Docs](https://gbdev.io/pandocs/The_Cartridge_Header.html#0104-0133--nintendo-logo).
+pub const LOGO: [u8; 48] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
+
+/// The memory bank controller a cartridge uses to switch between banks of ROM and RAM.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Mbc {
+    /// No memory bank controller, just 32 KiB of ROM and up to 8 KiB of RAM.
+    #[default]
+    None,
+    Mbc1,
+
+    /// MBC2, with 512 half-bytes of RAM built in.
+    Mbc2,
+    Mbc3,
+    Mbc5,
+}
+
+/// What's on a cartridge besides its ROM, from the cartridge type in the header. Only the
+/// combinations that were actually made exist.
+///
+/// See the [Pan Docs](https://gbdev.io/pandocs/The_Cartridge_Header.html#0147--cartridge-type).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct CartridgeType {
+    pub mbc: Mbc,
+
+    /// Whether the cartridge has external RAM, not counting the RAM built into an MBC2.
+    pub ram: bool,
+
+    /// Whether a battery keeps the external RAM, or the MBC2's built in RAM, around when the power
+    /// is off.
+    pub battery: bool,
+
+    /// Whether an MBC3 has a real time clock.
+    pub timer: bool,
+
+    /// Whether an MBC5 drives a rumble motor.
+    pub rumble: bool,
+}
+
+impl CartridgeType {
+    /// The cartridge type with `code` in the header, if it's one that's supported.
+    pub const fn from_code(code: u8) -> Option<Self> {
+        let (mbc, ram, battery, timer, rumble) = match code {
+            0x00 => (Mbc::None, false, false, false, false),
+            0x08 => (Mbc::None, true, false, false, false),
+            0x09 => (Mbc::None, true, true, false, false),
+            0x01 => (Mbc::Mbc1, false, false, false, false),
+            0x02 => (Mbc::Mbc1, true, false, false, false),
+            0x03 => (Mbc::Mbc1, true, true, false, false),
+            0x05 => (Mbc::Mbc2, false, false, false, false),
+            0x06 => (Mbc::Mbc2, false, true, false, false),
+            0x0F => (Mbc::Mbc3, false, true, true, false),
+            0x10 => (Mbc::Mbc3, true, true, true, false),
+            0x11 => (Mbc::Mbc3, false, false, false, false),
+            0x12 => (Mbc::Mbc3, true, false, false, false),
+            0x13 => (Mbc::Mbc3, true, true, false, false),
+            0x19 => (Mbc::Mbc5, false, false, false, false),
+            0x1A => (Mbc::Mbc5, true, false, false, false),
+            0x1B => (Mbc::Mbc5, true, true, false, false),
+            0x1C => (Mbc::Mbc5, false, false, false, true),
+            0x1D => (Mbc::Mbc5, true, false, false, true),
+            0x1E => (Mbc::Mbc5, true, true, false, true),
+            _ => return None,
+        };
+
+        Some(Self {
+            mbc,
+            ram,
+            battery,
+            timer,
+            rumble,
+        })
+    }
+}
+
+/// The size of the ROM with size `code` in the header, 32 KiB doubled `code` times.
+pub const fn rom_size(code: u8) -> Option<usize> {
+    match code {
+        0x00..=0x08 => Some(0x8000 << code),
+        _ => None,
+    }
+}
+
+/// The size of the external RAM with size `code` in the header. Code 0x01 was never used by a
+/// released cartridge, but is documented as 2 KiB.
+pub const fn ram_size(code: u8) -> Option<usize> {
+    match code {
+        0x00 => Some(0),
+        0x01 => Some(0x800),
+        0x02 => Some(0x2000),
+        0x03 => Some(0x8000),
+        0x04 => Some(0x20000),
+        0x05 => Some(0x10000),
+        _ => None,
+    }
+}
+
+/// What the header says about the hardware on a cartridge.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Header {
+    pub cartridge_type: CartridgeType,
+
+    /// The size of the ROM, which every ROM has to match.
+    pub rom_size: usize,
+
+    /// The size of the external RAM, zero if there isn't any.
+    pub ram_size: usize,
+}
+
+impl Header {
+    /// Read the header of `rom`, making sure it describes hardware that exists and matches the
+    /// size of the ROM.
+    pub fn parse(rom: &[u8]) -> Result<Self, LoadError> {
+        if rom.len() < HEADER_END {
+            return Err(LoadError::TooShort { len: rom.len() });
+        }
+
+        let type_code = rom[CARTRIDGE_TYPE];
+        let cartridge_type =
+            CartridgeType::from_code(type_code).ok_or(LoadError::UnsupportedType(type_code))?;
+
+        let rom_code = rom[ROM_SIZE];
+        let rom_size = rom_size(rom_code).ok_or(LoadError::RomSize {
+            code: rom_code,
+            len: rom.len(),
+        })?;
+
+        if rom_size != rom.len() {
+            return Err(LoadError::RomSize {
+                code: rom_code,
+                len: rom.len(),
+            });
+        }
+
+        // Only cartridge types with RAM can have a RAM size, and they always do
+        let ram_code = rom[RAM_SIZE];
+        let ram_size = match ram_size(ram_code) {
+            Some(0) if !cartridge_type.ram => 0,
+            Some(size @ 1..) if cartridge_type.ram => size,
+            _ => {
+                return Err(LoadError::RamSize {
+                    type_code,
+                    ram_code,
+                })
+            }
+        };
+
+        Ok(Self {
+            cartridge_type,
+            rom_size,
+            ram_size,
+        })
+    }
+}
+
+/// Whether `rom` has the Nintendo logo in its header.
+pub fn logo_valid(rom: &[u8]) -> bool {
+    rom.get(LOGO_START..LOGO_START + LOGO.len()) == Some(LOGO.as_slice())
+}
+
+/// The header checksum of `rom`, over 0x0134–0x014C, or `None` if it's too short to have one.
+pub fn header_checksum(rom: &[u8]) -> Option<u8> {
+    let covered = rom.get(CHECKSUM_START..HEADER_CHECKSUM)?;
+
+    Some(
+        covered
+            .iter()
+            .fold(0u8, |sum, &byte| sum.wrapping_sub(byte).wrapping_sub(1)),
+    )
+}
+
+/// The header checksum stored in `rom`, if it's long enough to have one.
+pub fn stored_header_checksum(rom: &[u8]) -> Option<u8> {
+    rom.get(HEADER_CHECKSUM).copied()
+}
+
+/// The global checksum of `rom`, the sum of every byte but the checksum itself.
+pub fn global_checksum(rom: &[u8]) -> u16 {
+    rom.iter()
+        .enumerate()
+        .filter(|&(addr, _)| !(GLOBAL_CHECKSUM..GLOBAL_CHECKSUM + 2).contains(&addr))
+        .fold(0u16, |sum, (_, &byte)| sum.wrapping_add(byte as u16))
+}
+
+/// The global checksum stored in `rom`, if it's long enough to have one.
+pub fn stored_global_checksum(rom: &[u8]) -> Option<u16> {
+    let stored = rom.get(GLOBAL_CHECKSUM..GLOBAL_CHECKSUM + 2)?;
+
+    Some(u16::from_be_bytes([stored[0], stored[1]]))
+}
+
+/// Why a ROM couldn't be loaded as a cartridge.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LoadError {
+    /// The ROM ends before the end of the header at 0x014F, so it can't be a cartridge.
+    TooShort { len: usize },
+
+    /// The ROM is `len` bytes, but size `code` in the header says otherwise, or isn't a size at
+    /// all.
+    RomSize { code: u8, len: usize },
+
+    /// The cartridge type in the header isn't one that's supported, or doesn't exist.
+    UnsupportedType(u8),
+
+    /// The RAM size in the header doesn't make sense for the cartridge type, like RAM on a
+    /// cartridge type without any.
+    RamSize { type_code: u8, ram_code: u8 },
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::TooShort { len } => write!(f, "ROM is {len} bytes, too short to have a header"),
+            Self::RomSize { code, len } => match rom_size(code) {
+                Some(size) => write!(f, "ROM is {len} bytes, but the header says {size}"),
+                None => write!(f, "ROM size {code:02X} in the header doesn't exist"),
+            },
+            Self::UnsupportedType(code) => write!(f, "cartridge type {code:02X} isn't supported"),
+            Self::RamSize {
+                type_code,
+                ram_code,
+            } => write!(
+                f,
+                "RAM size {ram_code:02X} doesn't make sense for cartridge type {type_code:02X}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+/// Something wrong with a cartridge header that doesn't stop it from being loaded.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HeaderWarning {
+    /// The ROM ends before the end of the header, so there's nothing to check.
+    Truncated,
+
+    /// The Nintendo logo doesn't match, so the DMG's boot ROM refuses to boot it.
+    Logo,
+
+    /// The header checksum doesn't match, so every boot ROM refuses to boot it.
+    HeaderChecksum { stored: u8, computed: u8 },
+
+    /// The global checksum doesn't match. Nothing checks it, so this is only a sign of a bad dump.
+    GlobalChecksum { stored: u16, computed: u16 },
+}
+
+impl Display for HeaderWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Truncated => write!(f, "the ROM is too short to have a header"),
+            Self::Logo => write!(f, "the Nintendo logo doesn't match"),
+            Self::HeaderChecksum { stored, computed } => write!(
+                f,
+                "header checksum is {stored:02X}, but the header sums to {computed:02X}"
+            ),
+            Self::GlobalChecksum { stored, computed } => write!(
+                f,
+                "global checksum is {stored:04X}, but the ROM sums to {computed:04X}"
+            ),
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use super::header;
+use super::ROM_BANK_SIZE;
+
+/// The state of an MBC1, which switches between up to 128 banks of ROM and 4 banks of RAM.
+///
+/// Multicarts, or MBC1M, wire it up differently. The top bit of `BANK1` is left unconnected, so
+/// `BANK2` picks one of four 16-bank games, and `BANK1` the bank within it.
+///
+/// See the [Pan Docs](https://gbdev.io/pandocs/MBC1.html).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct Mbc1 {
+    /// Whether RAM can be accessed, enabled by writing 0x0A to the low bits of 0x0000–0x1FFF.
+    ram_enabled: bool,
+
+    /// The 5-bit `BANK1` register at 0x2000–0x3FFF, the low bits of the ROM bank at 0x4000–0x7FFF.
+    /// Never 0, as writing 0 selects bank 1 instead.
+    bank1: u8,
+
+    /// The 2-bit `BANK2` register at 0x4000–0x5FFF, the high bits of the ROM bank. Also the RAM
+    /// bank, but only in mode 1.
+    bank2: u8,
+
+    /// The mode register at 0x6000–0x7FFF. When set, `BANK2` also switches the ROM bank at
+    /// 0x0000–0x3FFF and the RAM bank, which are both fixed to bank 0 otherwise.
+    mode: bool,
+
+    /// Whether it's wired up as a multicart.
+    multicart: bool,
+}
+
+impl Mbc1 {
+    /// An MBC1 as it powers on, with banks 0 and 1 mapped and RAM disabled.
+    pub const fn new(multicart: bool) -> Self {
+        Self {
+            ram_enabled: false,
+            bank1: 0x01,
+            bank2: 0x00,
+            mode: false,
+            multicart,
+        }
+    }
+
+    /// Whether `rom` looks like it's from an MBC1 multicart. They're all 8 Mbit, with each of the
+    /// games having a header of its own, so there's a Nintendo logo at the start of bank 0x10 too.
+    pub fn detect_multicart(rom: &[u8]) -> bool {
+        rom.len() == 0x100000 && header::logo_valid(&rom[0x10 * ROM_BANK_SIZE..])
+    }
+
+    /// Whether it's wired up as a multicart.
+    pub const fn multicart(&self) -> bool {
+        self.multicart
+    }
+
+    /// Wire it up as a multicart or not, overriding [Mbc1::detect_multicart].
+    pub fn set_multicart(&mut self, multicart: bool) {
+        self.multicart = multicart;
+    }
+
+    /// Write to one of the control registers at 0x0000–0x7FFF.
+    pub fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            // Only the 5 bits are checked for 0, which is what leaves banks 0x20, 0x40, and 0x60
+            // out of reach of 0x4000–0x7FFF
+            0x2000..=0x3FFF => self.bank1 = (value & 0x1F).max(0x01),
+            0x4000..=0x5FFF => self.bank2 = value & 0x03,
+            0x6000..=0x7FFF => self.mode = value & 0x01 != 0,
+            _ => (),
+        }
+    }
+
+    /// The ROM bank mapped at `addr` in 0x0000–0x7FFF, before it's masked to the size of the ROM.
+    pub const fn rom_bank(&self, addr: u16) -> usize {
+        // BANK1 is still checked for 0 with all 5 bits, so a multicart can map bank 0 of each game
+        let (high, low) = match self.multicart {
+            false => ((self.bank2 as usize) << 5, self.bank1 as usize),
+            true => ((self.bank2 as usize) << 4, self.bank1 as usize & 0x0F),
+        };
+
+        match (addr, self.mode) {
+            (0x0000..=0x3FFF, false) => 0,
+            (0x0000..=0x3FFF, true) => high,
+            _ => high | low,
+        }
+    }
+
+    /// The RAM bank mapped at 0xA000–0xBFFF, before it's masked to the size of the RAM, or `None`
+    /// if RAM is disabled.
+    pub const fn ram_bank(&self) -> Option<usize> {
+        match (self.ram_enabled, self.mode) {
+            (false, _) => None,
+            (true, false) => Some(0),
+            (true, true) => Some(self.bank2 as usize),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bank_0_selects_bank_1() {
+        let mut mbc = Mbc1::new(false);
+
+        for (written, bank) in [
+            (0x00, 0x01),
+            (0x01, 0x01),
+            (0x1F, 0x1F),
+            (0x20, 0x01),
+            (0xE2, 0x02),
+        ] {
+            mbc.write(0x2000, written);
+            assert_eq!(bank, mbc.rom_bank(0x4000), "BANK1={written:02X}");
+        }
+    }
+
+    #[test]
+    fn bank2_and_mode() {
+        let mut mbc = Mbc1::new(false);
+
+        mbc.write(0x3FFF, 0x00);
+        mbc.write(0x5FFF, 0x03);
+
+        // The holes in the middle of the ROM that 0x4000–0x7FFF can't reach
+        assert_eq!(0x61, mbc.rom_bank(0x7FFF));
+        assert_eq!(0x00, mbc.rom_bank(0x0000));
+
+        // Only in mode 1 can they be reached, at 0x0000–0x3FFF instead
+        mbc.write(0x6000, 0x01);
+        assert_eq!(0x60, mbc.rom_bank(0x3FFF));
+        assert_eq!(0x61, mbc.rom_bank(0x4000));
+    }
+
+    #[test]
+    fn ram_enable_and_bank() {
+        let mut mbc = Mbc1::new(false);
+
+        mbc.write(0x4000, 0x02);
+        assert_eq!(None, mbc.ram_bank());
+
+        // Only the low bits have to be 0x0A
+        mbc.write(0x1FFF, 0xFA);
+        assert_eq!(Some(0), mbc.ram_bank());
+
+        mbc.write(0x6000, 0x01);
+        assert_eq!(Some(2), mbc.ram_bank());
+
+        mbc.write(0x0000, 0x0B);
+        assert_eq!(None, mbc.ram_bank());
+    }
+
+    #[test]
+    fn multicart_banks() {
+        let mut mbc = Mbc1::new(true);
+
+        mbc.write(0x2000, 0x1F);
+        mbc.write(0x4000, 0x02);
+        assert_eq!(0x2F, mbc.rom_bank(0x4000));
+        assert_eq!(0x00, mbc.rom_bank(0x0000));
+
+        // Writing 0x10 gets past the check for 0, reaching the first bank of a game
+        mbc.write(0x2000, 0x10);
+        mbc.write(0x6000, 0x01);
+        assert_eq!(0x20, mbc.rom_bank(0x4000));
+        assert_eq!(0x20, mbc.rom_bank(0x0000));
+
+        mbc.set_multicart(false);
+        assert_eq!(0x50, mbc.rom_bank(0x4000));
+    }
+}
@@ -0,0 +1,82 @@
+/// The state of an MBC2, which switches between up to 16 banks of ROM, and has 512 half-bytes of
+/// RAM built in.
+///
+/// See the [Pan Docs](https://gbdev.io/pandocs/MBC2.html).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct Mbc2 {
+    /// Whether the built in RAM can be accessed, enabled by writing 0x0A to the low bits of the
+    /// RAM enable register.
+    ram_enabled: bool,
+
+    /// The 4-bit ROM bank at 0x4000–0x7FFF. Never 0, as writing 0 selects bank 1 instead.
+    rom_bank: u8,
+}
+
+impl Mbc2 {
+    /// The size of the built in RAM, 512 half-bytes which each take up a byte.
+    pub const RAM_SIZE: usize = 0x200;
+
+    /// An MBC2 as it powers on, with banks 0 and 1 mapped and RAM disabled.
+    pub const fn new() -> Self {
+        Self {
+            ram_enabled: false,
+            rom_bank: 0x01,
+        }
+    }
+
+    /// Write to the control registers at 0x0000–0x3FFF, where bit 8 of the address picks between
+    /// RAM enable when clear and the ROM bank when set. Writes to 0x4000–0x7FFF do nothing.
+    pub fn write(&mut self, addr: u16, value: u8) {
+        match (addr, addr & 0x0100 != 0) {
+            (0x0000..=0x3FFF, false) => self.ram_enabled = value & 0x0F == 0x0A,
+            (0x0000..=0x3FFF, true) => self.rom_bank = (value & 0x0F).max(0x01),
+            _ => (),
+        }
+    }
+
+    /// The ROM bank mapped at `addr` in 0x0000–0x7FFF, before it's masked to the size of the ROM.
+    pub const fn rom_bank(&self, addr: u16) -> usize {
+        match addr {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        }
+    }
+
+    /// Whether the built in RAM can be accessed.
+    pub const fn ram_enabled(&self) -> bool {
+        self.ram_enabled
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn address_bit_8_selects_register() {
+        let mut mbc = Mbc2::new();
+
+        // With bit 8 clear, it's RAM enable, even with a value that would be a ROM bank
+        mbc.write(0x0000, 0x0A);
+        assert!(mbc.ram_enabled());
+        assert_eq!(0x01, mbc.rom_bank(0x4000));
+
+        mbc.write(0x3EFF, 0x05);
+        assert!(!mbc.ram_enabled());
+        assert_eq!(0x01, mbc.rom_bank(0x4000));
+
+        // With bit 8 set, it's the ROM bank
+        mbc.write(0x0100, 0x0A);
+        assert!(!mbc.ram_enabled());
+        assert_eq!(0x0A, mbc.rom_bank(0x4000));
+
+        mbc.write(0x3FFF, 0xF0);
+        assert_eq!(0x01, mbc.rom_bank(0x7FFF));
+        assert_eq!(0x00, mbc.rom_bank(0x3FFF));
+
+        // Nothing is at 0x4000–0x7FFF
+        mbc.write(0x4100, 0x03);
+        assert_eq!(0x01, mbc.rom_bank(0x4000));
+    }
+}
@@ -0,0 +1,107 @@
+/// The state of an MBC3, which switches between up to 128 banks of ROM and 4 banks of RAM, and
+/// maps in the registers of its [Rtc](super::rtc::Rtc) if it has one.
+///
+/// See the [Pan Docs](https://gbdev.io/pandocs/MBC3.html).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct Mbc3 {
+    /// Whether RAM and the real time clock can be accessed, enabled by writing 0x0A to the low bits
+    /// of 0x0000–0x1FFF.
+    ram_enabled: bool,
+
+    /// The 7-bit ROM bank at 0x4000–0x7FFF. Never 0, as writing 0 selects bank 1 instead.
+    rom_bank: u8,
+
+    /// The register at 0x4000–0x5FFF, which selects a RAM bank with 0x00–0x03, or a register of
+    /// the real time clock with 0x08–0x0C.
+    ram_bank: u8,
+}
+
+impl Mbc3 {
+    /// An MBC3 as it powers on, with banks 0 and 1 mapped and RAM disabled.
+    pub const fn new() -> Self {
+        Self {
+            ram_enabled: false,
+            rom_bank: 0x01,
+            ram_bank: 0x00,
+        }
+    }
+
+    /// Write to one of the control registers at 0x0000–0x7FFF.
+    pub fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            // Unlike the MBC1, all 7 bits are checked for 0, so there aren't any holes
+            0x2000..=0x3FFF => self.rom_bank = (value & 0x7F).max(0x01),
+            0x4000..=0x5FFF => self.ram_bank = value,
+            _ => (),
+        }
+    }
+
+    /// The ROM bank mapped at `addr` in 0x0000–0x7FFF, before it's masked to the size of the ROM.
+    pub const fn rom_bank(&self, addr: u16) -> usize {
+        match addr {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        }
+    }
+
+    /// The RAM bank mapped at 0xA000–0xBFFF, before it's masked to the size of the RAM, or `None`
+    /// if RAM is disabled or something else is selected.
+    pub const fn ram_bank(&self) -> Option<usize> {
+        match (self.ram_enabled, self.ram_bank) {
+            (true, bank @ 0x00..=0x03) => Some(bank as usize),
+            _ => None,
+        }
+    }
+
+    /// The register of the real time clock mapped at 0xA000–0xBFFF, numbered from 0 for seconds,
+    /// or `None` if it's disabled or RAM is selected instead.
+    pub const fn rtc_register(&self) -> Option<usize> {
+        match (self.ram_enabled, self.ram_bank) {
+            (true, register @ 0x08..=0x0C) => Some(register as usize - 0x08),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rom_bank_has_no_holes() {
+        let mut mbc = Mbc3::new();
+
+        for (written, bank) in [
+            (0x00, 0x01),
+            (0x20, 0x20),
+            (0x40, 0x40),
+            (0x7F, 0x7F),
+            (0x80, 0x01),
+        ] {
+            mbc.write(0x2000, written);
+            assert_eq!(bank, mbc.rom_bank(0x4000), "{written:02X}");
+            assert_eq!(0x00, mbc.rom_bank(0x0000));
+        }
+    }
+
+    #[test]
+    fn ram_bank_needs_enable() {
+        let mut mbc = Mbc3::new();
+
+        mbc.write(0x4000, 0x03);
+        assert_eq!(None, mbc.ram_bank());
+
+        mbc.write(0x0000, 0x0A);
+        assert_eq!(Some(3), mbc.ram_bank());
+
+        // Past the RAM banks is the real time clock, which isn't RAM at all
+        mbc.write(0x4000, 0x08);
+        assert_eq!(None, mbc.ram_bank());
+        assert_eq!(Some(0), mbc.rtc_register());
+
+        mbc.write(0x4000, 0x0D);
+        assert_eq!(None, mbc.rtc_register());
+    }
+}
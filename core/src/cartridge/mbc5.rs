@@ -0,0 +1,131 @@
+/// The state of an MBC5, which switches between up to 512 banks of ROM and 16 banks of RAM, or 8
+/// banks of RAM on a cartridge with a rumble motor.
+///
+/// See the [Pan Docs](https://gbdev.io/pandocs/MBC5.html).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct Mbc5 {
+    /// Whether RAM can be accessed, enabled by writing exactly 0x0A to 0x0000–0x1FFF.
+    ram_enabled: bool,
+
+    /// The 9-bit ROM bank at 0x4000–0x7FFF, with the low 8 bits written to 0x2000–0x2FFF and the
+    /// top bit to 0x3000–0x3FFF. Unlike the other MBCs, this can be 0.
+    rom_bank: u16,
+
+    /// The 4-bit RAM bank register at 0x4000–0x5FFF. On a cartridge with a rumble motor, bit 3
+    /// turns the motor on instead of selecting a RAM bank.
+    ram_bank: u8,
+
+    /// Whether the cartridge has a rumble motor wired to bit 3 of the RAM bank register.
+    rumble: bool,
+}
+
+impl Mbc5 {
+    /// An MBC5 as it powers on, with banks 0 and 1 mapped, RAM disabled, and the rumble motor off
+    /// if it has one.
+    pub const fn new(rumble: bool) -> Self {
+        Self {
+            ram_enabled: false,
+            rom_bank: 0x001,
+            ram_bank: 0x00,
+            rumble,
+        }
+    }
+
+    /// Write to one of the control registers at 0x0000–0x7FFF.
+    pub fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            // Unlike the older MBCs, the top bits have to be clear too
+            0x0000..=0x1FFF => self.ram_enabled = value == 0x0A,
+            0x2000..=0x2FFF => self.rom_bank = self.rom_bank & 0x100 | value as u16,
+            0x3000..=0x3FFF => self.rom_bank = self.rom_bank & 0x0FF | (value as u16 & 0x01) << 8,
+            0x4000..=0x5FFF => self.ram_bank = value & 0x0F,
+            _ => (),
+        }
+    }
+
+    /// The ROM bank mapped at `addr` in 0x0000–0x7FFF, before it's masked to the size of the ROM.
+    pub const fn rom_bank(&self, addr: u16) -> usize {
+        match addr {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        }
+    }
+
+    /// The RAM bank mapped at 0xA000–0xBFFF, before it's masked to the size of the RAM, or `None`
+    /// if RAM is disabled.
+    pub const fn ram_bank(&self) -> Option<usize> {
+        let mask = if self.rumble { 0x07 } else { 0x0F };
+
+        match self.ram_enabled {
+            true => Some((self.ram_bank & mask) as usize),
+            false => None,
+        }
+    }
+
+    /// Whether the rumble motor is on, which is never for a cartridge without one.
+    pub const fn rumble_active(&self) -> bool {
+        self.rumble && self.ram_bank & 0x08 != 0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rom_bank_is_9_bits() {
+        let mut mbc = Mbc5::new(false);
+
+        assert_eq!(0x001, mbc.rom_bank(0x4000));
+
+        mbc.write(0x2000, 0x00);
+        assert_eq!(0x000, mbc.rom_bank(0x4000));
+
+        mbc.write(0x3000, 0x01);
+        assert_eq!(0x100, mbc.rom_bank(0x4000));
+
+        mbc.write(0x2FFF, 0x23);
+        assert_eq!(0x123, mbc.rom_bank(0x7FFF));
+        assert_eq!(0x000, mbc.rom_bank(0x3FFF));
+
+        // Only the bottom bit of the high register is connected
+        mbc.write(0x3FFF, 0xFE);
+        assert_eq!(0x023, mbc.rom_bank(0x4000));
+    }
+
+    #[test]
+    fn ram_needs_exact_enable() {
+        let mut mbc = Mbc5::new(false);
+
+        mbc.write(0x4000, 0x1F);
+        assert_eq!(None, mbc.ram_bank());
+
+        mbc.write(0x0000, 0x1A);
+        assert_eq!(None, mbc.ram_bank());
+
+        mbc.write(0x0000, 0x0A);
+        assert_eq!(Some(0x0F), mbc.ram_bank());
+    }
+
+    #[test]
+    fn rumble_takes_bit_3() {
+        let mut mbc = Mbc5::new(false);
+
+        mbc.write(0x0000, 0x0A);
+        mbc.write(0x4000, 0x0B);
+        assert_eq!(Some(0x0B), mbc.ram_bank());
+        assert!(!mbc.rumble_active());
+
+        let mut mbc = Mbc5::new(true);
+
+        mbc.write(0x0000, 0x0A);
+        mbc.write(0x4000, 0x0B);
+        assert_eq!(Some(0x03), mbc.ram_bank());
+        assert!(mbc.rumble_active());
+
+        mbc.write(0x4000, 0x03);
+        assert_eq!(Some(0x03), mbc.ram_bank());
+        assert!(!mbc.rumble_active());
+    }
+}
@@ -0,0 +1,453 @@
+use std::fmt::Display;
+
+use super::Cartridge;
+
+/// Where the title starts, padded to the end with NULs.
+const TITLE: usize = 0x0134;
+
+/// Where the manufacturer code starts on newer cartridges, taking up the end of the title.
+const MANUFACTURER_CODE: usize = 0x013F;
+
+/// Where the CGB flag is stored, which older cartridges use as the last byte of the title.
+const CGB_FLAG: usize = 0x0143;
+
+/// Where the two ASCII characters of the new licensee code are stored.
+const NEW_LICENSEE: usize = 0x0144;
+
+/// Where the SGB flag is stored.
+const SGB_FLAG: usize = 0x0146;
+
+/// Where the old licensee code is stored, or [USE_NEW_LICENSEE].
+const OLD_LICENSEE: usize = 0x014B;
+
+/// The old licensee code of a cartridge that uses the new licensee code instead.
+const USE_NEW_LICENSEE: u8 = 0x33;
+
+/// Whether a cartridge makes use of the CGB, from its CGB flag.
+///
+/// See the [Pan Docs](https://gbdev.io/pandocs/The_Cartridge_Header.html#0143--cgb-flag).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum CgbSupport {
+    /// A DMG cartridge, which the CGB runs in compatibility mode.
+    #[default]
+    None,
+
+    /// A cartridge that uses CGB features, but still runs on the DMG.
+    Enhanced,
+
+    /// A cartridge that only runs on the CGB.
+    Required,
+}
+
+/// The publisher of a cartridge, from its old licensee code or, on newer cartridges, its new one.
+///
+/// See the [Pan Docs](https://gbdev.io/pandocs/The_Cartridge_Header.html#01440145--new-licensee-code).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Licensee {
+    /// The licensee code is 0x00, or "00" for the new code.
+    None,
+
+    /// The published name of the licensee.
+    Known(&'static str),
+
+    /// An old licensee code that isn't in the published list.
+    Old(u8),
+
+    /// A new licensee code that isn't in the published list, as its two characters.
+    New([u8; 2]),
+}
+
+impl Licensee {
+    /// The published name of the licensee, if it's known.
+    pub const fn name(&self) -> Option<&'static str> {
+        match self {
+            Self::Known(name) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Licensee {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Known(name) => write!(f, "{name}"),
+            Self::Old(code) => write!(f, "unknown licensee {code:02X}"),
+            Self::New(code) => write!(f, "unknown licensee \"{}\"", code.escape_ascii()),
+        }
+    }
+}
+
+impl Cartridge {
+    /// The title in the header, up to the first NUL or anything else that isn't printable ASCII,
+    /// with any trailing spaces trimmed. Empty for a ROM too short to have one.
+    ///
+    /// The title takes up 16 bytes on the oldest cartridges, and 15 once the last byte became the
+    /// CGB flag. Newer CGB cartridges also put a 4-character manufacturer code at the end, leaving
+    /// 11 bytes, but nothing in the header says so. A CGB cartridge that uses the new licensee code
+    /// and has 4 uppercase letters or digits there is taken to have one, see
+    /// [Cartridge::manufacturer_code].
+    pub fn title(&self) -> &str {
+        let end = match (self.manufacturer_code(), self.cgb_support()) {
+            (Some(_), _) => MANUFACTURER_CODE,
+            (None, CgbSupport::None) => CGB_FLAG + 1,
+            (None, _) => CGB_FLAG,
+        };
+
+        let title = self.rom.get(TITLE..end).unwrap_or_default();
+        let len = title
+            .iter()
+            .position(|&byte| !(0x20..0x7F).contains(&byte))
+            .unwrap_or(title.len());
+
+        std::str::from_utf8(&title[..len])
+            .unwrap_or_default()
+            .trim_end()
+    }
+
+    /// The 4-character manufacturer code at the end of the title of newer CGB cartridges, if it
+    /// looks like there is one. See [Cartridge::title].
+    pub fn manufacturer_code(&self) -> Option<&str> {
+        let code = self.rom.get(MANUFACTURER_CODE..CGB_FLAG)?;
+
+        let has_code = self.cgb_support() != CgbSupport::None
+            && self.rom.get(OLD_LICENSEE) == Some(&USE_NEW_LICENSEE)
+            && code
+                .iter()
+                .all(|byte| byte.is_ascii_uppercase() || byte.is_ascii_digit());
+
+        has_code.then(|| std::str::from_utf8(code).unwrap_or_default())
+    }
+
+    /// The publisher of the cartridge, using the new licensee code if the old one says to.
+    pub fn licensee(&self) -> Licensee {
+        match self.rom.get(OLD_LICENSEE) {
+            None | Some(&0x00) => Licensee::None,
+            Some(&USE_NEW_LICENSEE) => {
+                let code = self.rom[NEW_LICENSEE..NEW_LICENSEE + 2].try_into().unwrap();
+
+                match (code, new_licensee(code)) {
+                    (_, Some(name)) => Licensee::Known(name),
+                    ([b'0', b'0'], None) => Licensee::None,
+                    (code, None) => Licensee::New(code),
+                }
+            }
+            Some(&code) => old_licensee(code).map_or(Licensee::Old(code), Licensee::Known),
+        }
+    }
+
+    /// Whether the cartridge runs on the DMG, the CGB, or both, from its CGB flag.
+    pub fn cgb_support(&self) -> CgbSupport {
+        match self.rom.get(CGB_FLAG) {
+            Some(0xC0) => CgbSupport::Required,
+            Some(flag) if flag & 0x80 != 0 => CgbSupport::Enhanced,
+            _ => CgbSupport::None,
+        }
+    }
+
+    /// Whether the cartridge uses SGB features. The SGB only enables them if the SGB flag is 0x03
+    /// and the cartridge uses the new licensee code.
+    pub fn sgb_support(&self) -> bool {
+        self.rom.get(SGB_FLAG) == Some(&0x03)
+            && self.rom.get(OLD_LICENSEE) == Some(&USE_NEW_LICENSEE)
+    }
+}
+
+/// The published name for the old licensee `code`, if there is one.
+fn old_licensee(code: u8) -> Option<&'static str> {
+    let name = match code {
+        0x01 | 0x31 => "Nintendo",
+        0x08 | 0x38 => "Capcom",
+        0x09 => "HOT-B",
+        0x0A | 0xE0 => "Jaleco",
+        0x0B => "Coconuts Japan",
+        0x0C | 0x6E => "Elite Systems",
+        0x13 | 0x69 => "EA (Electronic Arts)",
+        0x18 => "Hudson Soft",
+        0x19 => "ITC Entertainment",
+        0x1A => "Yanoman",
+        0x1D => "Japan Clary",
+        0x1F | 0x4A | 0x61 => "Virgin Games Ltd.",
+        0x24 => "PCM Complete",
+        0x25 => "San-X",
+        0x28 | 0x7F | 0x97 | 0xC2 => "Kemco",
+        0x29 => "SETA Corporation",
+        0x30 | 0x70 => "Infogrames",
+        0x32 | 0xA2 | 0xB2 => "Bandai",
+        0x34 | 0xA4 => "Konami",
+        0x35 => "HectorSoft",
+        0x39 | 0x9D | 0xD9 => "Banpresto",
+        0x3C => "Entertainment Interactive",
+        0x3E => "Gremlin",
+        0x41 => "Ubi Soft",
+        0x42 | 0xEB => "Atlus",
+        0x44 | 0x4D => "Malibu Interactive",
+        0x46 | 0xCF => "Angel",
+        0x47 => "Spectrum HoloByte",
+        0x49 => "Irem",
+        0x4F => "U.S. Gold",
+        0x50 => "Absolute",
+        0x51 | 0xB0 => "Acclaim Entertainment",
+        0x52 => "Activision",
+        0x53 => "Sammy USA Corporation",
+        0x54 => "GameTek",
+        0x55 => "Park Place",
+        0x56 | 0xDB | 0xFF => "LJN",
+        0x57 => "Matchbox",
+        0x59 => "Milton Bradley Company",
+        0x5A => "Mindscape",
+        0x5B => "Romstar",
+        0x5C | 0xD6 => "Naxat Soft",
+        0x5D => "Tradewest",
+        0x60 => "Titus Interactive",
+        0x67 => "Ocean Software",
+        0x6F => "Electro Brain",
+        0x71 => "Interplay Entertainment",
+        0x72 | 0xAA => "Broderbund",
+        0x73 => "Sculptured Software",
+        0x75 => "The Sales Curve Limited",
+        0x78 => "THQ",
+        0x79 => "Accolade",
+        0x7A => "Triffix Entertainment",
+        0x7C => "MicroProse",
+        0x80 => "Misawa Entertainment",
+        0x83 => "LOZC G.",
+        0x86 | 0xC4 => "Tokuma Shoten",
+        0x8B => "Bullet-Proof Software",
+        0x8C => "Vic Tokai Corp.",
+        0x8E => "Ape Inc.",
+        0x8F => "I'Max",
+        0x91 => "Chunsoft Co.",
+        0x92 => "Video System",
+        0x93 => "Tsubaraya Productions",
+        0x95 | 0xE3 => "Varie",
+        0x96 => "Yonezawa/S'Pal",
+        0x99 => "Arc",
+        0x9A => "Nihon Bussan",
+        0x9B => "Tecmo",
+        0x9C => "Imagineer",
+        0x9F => "Nova",
+        0xA1 => "Hori Electric",
+        0xA6 => "Kawada",
+        0xA7 => "Takara",
+        0xA9 => "Technos Japan",
+        0xAC => "Toei Animation",
+        0xAD => "Toho",
+        0xAF => "Namco",
+        0xB1 => "ASCII Corporation or Nexsoft",
+        0xB4 => "Square Enix",
+        0xB6 => "HAL Laboratory",
+        0xB7 => "SNK",
+        0xB9 | 0xCE => "Pony Canyon",
+        0xBA => "Culture Brain",
+        0xBB => "Sunsoft",
+        0xBD => "Sony Imagesoft",
+        0xBF => "Sammy Corporation",
+        0xC0 | 0xD0 => "Taito",
+        0xC3 => "Square",
+        0xC5 => "Data East",
+        0xC6 => "Tonkin House",
+        0xC8 => "Koei",
+        0xC9 => "UFL",
+        0xCA => "Ultra Games",
+        0xCB => "VAP, Inc.",
+        0xCC => "Use Corporation",
+        0xCD => "Meldac",
+        0xD1 => "SOFEL",
+        0xD2 => "Quest",
+        0xD3 => "Sigma Enterprises",
+        0xD4 => "ASK Kodansha Co.",
+        0xD7 => "Copya System",
+        0xDA => "Tomy",
+        0xDD => "Nippon Computer Systems",
+        0xDE => "Human Ent.",
+        0xDF => "Altron",
+        0xE1 => "Towa Chiki",
+        0xE2 => "Yutaka",
+        0xE5 => "Epoch",
+        0xE7 => "Athena",
+        0xE8 => "Asmik Ace Entertainment",
+        0xE9 => "Natsume",
+        0xEA => "King Records",
+        0xEC => "Epic/Sony Records",
+        0xEE => "IGS",
+        0xF0 => "A Wave",
+        0xF3 => "Extreme Entertainment",
+        _ => return None,
+    };
+
+    Some(name)
+}
+
+/// The published name for the new licensee `code`, if there is one.
+fn new_licensee(code: [u8; 2]) -> Option<&'static str> {
+    let name = match &code {
+        b"01" => "Nintendo Research & Development 1",
+        b"08" => "Capcom",
+        b"13" | b"69" => "EA (Electronic Arts)",
+        b"18" | b"38" => "Hudson Soft",
+        b"19" => "B-AI",
+        b"20" => "KSS",
+        b"22" => "Planning Office WADA",
+        b"24" => "PCM Complete",
+        b"25" => "San-X",
+        b"28" => "Kemco",
+        b"29" => "SETA Corporation",
+        b"30" => "Viacom",
+        b"31" => "Nintendo",
+        b"32" => "Bandai",
+        b"33" | b"93" => "Ocean Software/Acclaim Entertainment",
+        b"34" | b"54" => "Konami",
+        b"35" => "HectorSoft",
+        b"37" => "Taito",
+        b"39" => "Banpresto",
+        b"41" => "Ubi Soft",
+        b"42" => "Atlus",
+        b"44" => "Malibu Interactive",
+        b"46" => "Angel",
+        b"47" => "Bullet-Proof Software",
+        b"49" => "Irem",
+        b"50" => "Absolute",
+        b"51" => "Acclaim Entertainment",
+        b"52" => "Activision",
+        b"53" => "Sammy USA Corporation",
+        b"55" => "Hi Tech Expressions",
+        b"56" => "LJN",
+        b"57" => "Matchbox",
+        b"58" => "Mattel",
+        b"59" => "Milton Bradley Company",
+        b"60" => "Titus Interactive",
+        b"61" => "Virgin Games Ltd.",
+        b"64" => "Lucasfilm Games",
+        b"67" => "Ocean Software",
+        b"70" => "Infogrames",
+        b"71" => "Interplay Entertainment",
+        b"72" => "Broderbund",
+        b"73" => "Sculptured Software",
+        b"75" => "The Sales Curve Limited",
+        b"78" => "THQ",
+        b"79" => "Accolade",
+        b"80" => "Misawa Entertainment",
+        b"83" => "LOZC G.",
+        b"86" => "Tokuma Shoten",
+        b"87" => "Tsukuda Original",
+        b"91" => "Chunsoft Co.",
+        b"92" => "Video System",
+        b"95" => "Varie",
+        b"96" => "Yonezawa/S'Pal",
+        b"97" => "Kaneko",
+        b"99" => "Pack-In-Video",
+        b"9H" => "Bottom Up",
+        b"A4" => "Konami (Yu-Gi-Oh!)",
+        b"BL" => "MTO",
+        b"DK" => "Kodansha",
+        _ => return None,
+    };
+
+    Some(name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A ROM-only cartridge with `title` written at the start of the title.
+    fn titled(title: &[u8]) -> Vec<u8> {
+        let mut rom = vec![0x00; 0x8000];
+
+        rom[TITLE..TITLE + title.len()].copy_from_slice(title);
+        rom
+    }
+
+    #[test]
+    fn title_is_trimmed() {
+        for (title, expected) in [
+            (&b"TETRIS"[..], "TETRIS"),
+            (b"SUPER MARIOLAND\0", "SUPER MARIOLAND"),
+            (b"ALL SIXTEEN CHRS", "ALL SIXTEEN CHRS"),
+            (b"SPACES   ", "SPACES"),
+            (b"EMBEDDED\0NUL", "EMBEDDED"),
+            (b"FF PADDED\xFF\xFF\xFF", "FF PADDED"),
+            (b"", ""),
+        ] {
+            let cartridge = Cartridge::new(titled(title).into()).unwrap();
+            assert_eq!(expected, cartridge.title());
+        }
+
+        let cartridge = Cartridge::new_unchecked(vec![0x00; 0x0100].into());
+        assert_eq!("", cartridge.title());
+    }
+
+    #[test]
+    fn cgb_title_lengths() {
+        // The CGB flag takes the last byte
+        let mut rom = titled(b"POKEMON YELLOW\0");
+        rom[CGB_FLAG] = 0x80;
+
+        let cartridge = Cartridge::new(rom.into()).unwrap();
+        assert_eq!("POKEMON YELLOW", cartridge.title());
+        assert_eq!(CgbSupport::Enhanced, cartridge.cgb_support());
+        assert_eq!(None, cartridge.manufacturer_code());
+
+        // Then the manufacturer code takes 4 more, when the new licensee code says there's one
+        let mut rom = titled(b"POKEMON_SLVAAXE");
+        rom[CGB_FLAG] = 0x80;
+        rom[OLD_LICENSEE] = USE_NEW_LICENSEE;
+        rom[NEW_LICENSEE..NEW_LICENSEE + 2].copy_from_slice(b"01");
+
+        let cartridge = Cartridge::new(rom.into()).unwrap();
+        assert_eq!("POKEMON_SLV", cartridge.title());
+        assert_eq!(Some("AAXE"), cartridge.manufacturer_code());
+
+        let mut rom = titled(b"CGB ONLY");
+        rom[CGB_FLAG] = 0xC0;
+
+        let cartridge = Cartridge::new(rom.into()).unwrap();
+        assert_eq!("CGB ONLY", cartridge.title());
+        assert_eq!(CgbSupport::Required, cartridge.cgb_support());
+    }
+
+    #[test]
+    fn licensee_codes() {
+        let mut rom = titled(b"");
+
+        let cartridge = Cartridge::new(rom.clone().into()).unwrap();
+        assert_eq!(Licensee::None, cartridge.licensee());
+
+        rom[OLD_LICENSEE] = 0x01;
+        let cartridge = Cartridge::new(rom.clone().into()).unwrap();
+        assert_eq!(Some("Nintendo"), cartridge.licensee().name());
+
+        rom[OLD_LICENSEE] = 0x02;
+        let cartridge = Cartridge::new(rom.clone().into()).unwrap();
+        assert_eq!(Licensee::Old(0x02), cartridge.licensee());
+
+        // 0x33 defers to the new licensee code
+        rom[OLD_LICENSEE] = USE_NEW_LICENSEE;
+        rom[NEW_LICENSEE..NEW_LICENSEE + 2].copy_from_slice(b"A4");
+        let cartridge = Cartridge::new(rom.clone().into()).unwrap();
+        assert_eq!(Licensee::Known("Konami (Yu-Gi-Oh!)"), cartridge.licensee());
+
+        rom[NEW_LICENSEE..NEW_LICENSEE + 2].copy_from_slice(b"ZZ");
+        let cartridge = Cartridge::new(rom.clone().into()).unwrap();
+        assert_eq!(Licensee::New(*b"ZZ"), cartridge.licensee());
+        assert_eq!("unknown licensee \"ZZ\"", cartridge.licensee().to_string());
+
+        rom[NEW_LICENSEE..NEW_LICENSEE + 2].copy_from_slice(b"00");
+        let cartridge = Cartridge::new(rom.into()).unwrap();
+        assert_eq!(Licensee::None, cartridge.licensee());
+    }
+
+    #[test]
+    fn sgb_needs_new_licensee() {
+        let mut rom = titled(b"");
+        rom[SGB_FLAG] = 0x03;
+
+        let cartridge = Cartridge::new(rom.clone().into()).unwrap();
+        assert!(!cartridge.sgb_support());
+
+        rom[OLD_LICENSEE] = USE_NEW_LICENSEE;
+        let cartridge = Cartridge::new(rom.into()).unwrap();
+        assert!(cartridge.sgb_support());
+    }
+}
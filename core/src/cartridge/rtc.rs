@@ -0,0 +1,332 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The number of seconds in a day, as counted by the day counter.
+const DAY: u64 = 24 * 60 * 60;
+
+/// How many days the 9-bit day counter counts before it overflows.
+const DAYS: u64 = 512;
+
+/// Which bits of each register exist, in the order they're selected with 0x08–0x0C: seconds,
+/// minutes, hours, the low 8 bits of the day counter, and the high register with bit 8 of the day
+/// counter, the halt bit, and the carry bit.
+const MASKS: [u8; 5] = [0x3F, 0x3F, 0x1F, 0xFF, 0xC1];
+
+/// The bit of the high register that stops the clock.
+const HALT: u8 = 0x40;
+
+/// The bit of the high register that's set when the day counter overflows, until it's cleared.
+const CARRY: u8 = 0x80;
+
+/// The size of the footer that VBA and BGB append to the saves of cartridges with a real time
+/// clock, see [Rtc::save]. Older versions leave off the top half of the timestamp, for a 44-byte
+/// footer.
+pub const FOOTER_SIZE: usize = 48;
+
+/// Where a real time clock gets the time from, so that it can be swapped out for one that's
+/// controlled by tests. Any closure returning the time works as one, as long as it can be sent
+/// between threads along with the rest of the emulator.
+pub trait Clock: Send {
+    /// The current time in seconds, since some fixed point like the Unix epoch.
+    fn now(&self) -> u64;
+}
+
+impl<F: Fn() -> u64 + Send> Clock for F {
+    fn now(&self) -> u64 {
+        self()
+    }
+}
+
+/// The system's own clock, in seconds since the Unix epoch.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs())
+    }
+}
+
+/// Everything about an [Rtc] that changes as it runs, as saved by [Rtc::save_state].
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct RtcState {
+    counted: u64,
+    since: u64,
+    halted: bool,
+    carry: bool,
+    latched: [u8; 5],
+    latch_armed: bool,
+}
+
+/// The real time clock of an MBC3, counting seconds, minutes, hours, and days as long as it isn't
+/// halted. The time is only read back through registers latched from it, so it can't change
+/// halfway through being read.
+///
+/// See the [Pan Docs](https://gbdev.io/pandocs/MBC3.html#the-clock-counter-registers).
+pub struct Rtc {
+    clock: Box<dyn Clock>,
+
+    /// The seconds counted as of `since`, including whole days.
+    counted: u64,
+
+    /// When `counted` was last brought up to date, according to `clock`.
+    since: u64,
+
+    /// Whether the clock is stopped, and counted no further than `counted`.
+    halted: bool,
+
+    /// Whether the day counter has overflowed since this was last cleared.
+    carry: bool,
+
+    /// The registers as of the last latch, which is all reads ever see.
+    latched: [u8; 5],
+
+    /// Whether 0x00 was the last value written to the latch register, so 0x01 latches.
+    latch_armed: bool,
+}
+
+impl Rtc {
+    /// A clock that starts counting from 0 days, 00:00:00.
+    pub fn new(clock: Box<dyn Clock>) -> Self {
+        Self {
+            since: clock.now(),
+            clock,
+            counted: 0,
+            halted: false,
+            carry: false,
+            latched: [0x00; 5],
+            latch_armed: false,
+        }
+    }
+
+    /// Replace where the time comes from, keeping the time counted so far.
+    pub fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        self.update();
+        self.clock = clock;
+        self.since = self.clock.now();
+    }
+
+    /// The seconds counted as of `now`, wrapping the day counter around, and whether it's
+    /// overflowed.
+    fn counted_at(&self, now: u64) -> (u64, bool) {
+        let counted = match self.halted {
+            true => self.counted,
+            false => self.counted + now.saturating_sub(self.since),
+        };
+
+        (counted % (DAYS * DAY), self.carry || counted >= DAYS * DAY)
+    }
+
+    /// Count the time that's passed since `since`, setting the carry bit if the day counter
+    /// overflows.
+    fn update(&mut self) {
+        let now = self.clock.now();
+
+        (self.counted, self.carry) = self.counted_at(now);
+        self.since = now;
+    }
+
+    /// The registers as they are right now, rather than as they were last latched.
+    pub fn registers(&self) -> [u8; 5] {
+        let (counted, carry) = self.counted_at(self.clock.now());
+        let days = counted / DAY;
+        let high = (days >> 8) as u8 | if self.halted { HALT } else { 0 };
+
+        [
+            (counted % 60) as u8,
+            (counted / 60 % 60) as u8,
+            (counted / 3600 % 24) as u8,
+            days as u8,
+            high | if carry { CARRY } else { 0 },
+        ]
+    }
+
+    /// Set the time from `registers`, in the same order as [Rtc::registers], along with the halt
+    /// and carry bits. Values out of range, like 60 seconds, carry into the next register.
+    pub fn set_registers(&mut self, registers: [u8; 5]) {
+        let [seconds, minutes, hours, low, high] = registers;
+        let days = u64::from(low) | (u64::from(high & 0x01) << 8);
+
+        self.counted = u64::from(seconds & MASKS[0])
+            + u64::from(minutes & MASKS[1]) * 60
+            + u64::from(hours & MASKS[2]) * 3600
+            + days * DAY;
+        self.halted = high & HALT != 0;
+        self.carry = high & CARRY != 0;
+        self.since = self.clock.now();
+    }
+
+    /// Write to the latch register at 0x6000–0x7FFF, where writing 0x00 then 0x01 latches the time.
+    pub fn write_latch(&mut self, value: u8) {
+        if self.latch_armed && value == 0x01 {
+            self.update();
+            self.latched = self.registers();
+        }
+
+        self.latch_armed = value == 0x00;
+    }
+
+    /// Read `register` as of the last latch, numbered 0–4 for seconds, minutes, hours, and the
+    /// low and high halves of the day counter.
+    pub fn read(&self, register: usize) -> u8 {
+        self.latched[register]
+    }
+
+    /// Write to `register`, numbered like [Rtc::read], which sets the time straight away.
+    pub fn write(&mut self, register: usize, value: u8) {
+        self.update();
+
+        let mut registers = self.registers();
+
+        registers[register] = value & MASKS[register];
+        self.set_registers(registers);
+    }
+
+    /// Save everything about the clock that changes as it runs, for a save state. The clock keeps
+    /// counting from when it was saved once it's restored with [Rtc::load_state], as it does with
+    /// a battery save.
+    pub fn save_state(&self) -> RtcState {
+        RtcState {
+            counted: self.counted,
+            since: self.since,
+            halted: self.halted,
+            carry: self.carry,
+            latched: self.latched,
+            latch_armed: self.latch_armed,
+        }
+    }
+
+    /// Restore a state saved by [Rtc::save_state].
+    pub fn load_state(&mut self, state: &RtcState) {
+        self.counted = state.counted;
+        self.since = state.since;
+        self.halted = state.halted;
+        self.carry = state.carry;
+        self.latched = state.latched;
+        self.latch_armed = state.latch_armed;
+    }
+
+    /// The clock as the footer VBA and BGB append to saves, so that it keeps counting while the
+    /// emulator is closed. Every register is a 32-bit little-endian value, with the current ones
+    /// first and the latched ones after, followed by a 64-bit little-endian timestamp of when they
+    /// were saved.
+    pub fn save(&self) -> [u8; FOOTER_SIZE] {
+        let now = self.clock.now();
+        let registers = self.registers().into_iter().chain(self.latched);
+        let mut footer = [0x00; FOOTER_SIZE];
+
+        for (chunk, register) in footer.chunks_exact_mut(4).zip(registers) {
+            chunk.copy_from_slice(&u32::from(register).to_le_bytes());
+        }
+
+        footer[40..].copy_from_slice(&now.to_le_bytes());
+        footer
+    }
+
+    /// Restore the clock from a footer made by [Rtc::save], or the 44-byte footer of older
+    /// emulators. Unless it was halted, the clock counts the time that's passed since it was
+    /// saved. Returns `false` without changing anything if the footer is the wrong size.
+    pub fn load(&mut self, footer: &[u8]) -> bool {
+        if !matches!(footer.len(), 44 | FOOTER_SIZE) {
+            return false;
+        }
+
+        let mut registers = footer[..40]
+            .chunks_exact(4)
+            .enumerate()
+            .map(|(index, chunk)| chunk[0] & MASKS[index % MASKS.len()]);
+        let current = std::array::from_fn(|_| registers.next().unwrap_or_default());
+        let latched = std::array::from_fn(|_| registers.next().unwrap_or_default());
+
+        let mut timestamp = [0x00; 8];
+        timestamp[..footer.len() - 40].copy_from_slice(&footer[40..]);
+
+        self.set_registers(current);
+        self.latched = latched;
+        self.since = u64::from_le_bytes(timestamp);
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+
+    /// A clock that only moves when told to, along with the handle that moves it.
+    fn mock_clock() -> (Rtc, Arc<AtomicU64>) {
+        let time = Arc::new(AtomicU64::new(1_000_000));
+        let clock = Arc::clone(&time);
+
+        (
+            Rtc::new(Box::new(move || clock.load(Ordering::Relaxed))),
+            time,
+        )
+    }
+
+    #[test]
+    fn latching_isolates_reads() {
+        let (mut rtc, time) = mock_clock();
+
+        time.fetch_add(DAY + 3600 + 2 * 60 + 3, Ordering::Relaxed);
+        rtc.write_latch(0x00);
+        rtc.write_latch(0x01);
+
+        let latched = [3, 2, 1, 1, 0];
+        assert_eq!(latched, [0, 1, 2, 3, 4].map(|register| rtc.read(register)));
+
+        // The clock keeps going, but reads don't see it until the next latch
+        time.fetch_add(10, Ordering::Relaxed);
+        assert_eq!(latched, [0, 1, 2, 3, 4].map(|register| rtc.read(register)));
+
+        // Writing 0x01 on its own doesn't latch
+        rtc.write_latch(0x01);
+        assert_eq!(3, rtc.read(0));
+
+        rtc.write_latch(0x00);
+        rtc.write_latch(0x01);
+        assert_eq!(13, rtc.read(0));
+    }
+
+    #[test]
+    fn halt_stops_the_clock() {
+        let (mut rtc, time) = mock_clock();
+
+        time.fetch_add(5, Ordering::Relaxed);
+        rtc.write(4, HALT);
+
+        time.fetch_add(100, Ordering::Relaxed);
+        assert_eq!([5, 0, 0, 0, HALT], rtc.registers());
+
+        // Writing while halted is how games set the time
+        rtc.write(1, 30);
+        rtc.write(4, 0x00);
+
+        time.fetch_add(1, Ordering::Relaxed);
+        assert_eq!([6, 30, 0, 0, 0], rtc.registers());
+    }
+
+    #[test]
+    fn day_counter_overflow_carries() {
+        let (mut rtc, time) = mock_clock();
+
+        rtc.write(3, 0xFF);
+        rtc.write(4, 0x01);
+        assert_eq!([0, 0, 0, 0xFF, 0x01], rtc.registers());
+
+        time.fetch_add(DAY + 1, Ordering::Relaxed);
+        assert_eq!([1, 0, 0, 0x00, CARRY], rtc.registers());
+
+        // The carry bit stays set until it's cleared
+        time.fetch_add(DAY, Ordering::Relaxed);
+        assert_eq!([1, 0, 0, 0x01, CARRY], rtc.registers());
+
+        rtc.write(4, 0x00);
+        assert_eq!([1, 0, 0, 0x01, 0x00], rtc.registers());
+    }
+}
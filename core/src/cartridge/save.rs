@@ -0,0 +1,232 @@
+use std::fmt::Display;
+
+use super::rtc::FOOTER_SIZE;
+use super::Cartridge;
+
+/// A battery save that couldn't be imported into a [Cartridge].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SaveError {
+    /// There's no cartridge inserted to save with.
+    NoCartridge,
+
+    /// The cartridge doesn't have a battery, so there's nothing to save.
+    NoBattery,
+
+    /// The save is `len` bytes, which doesn't fit the `ram` bytes of RAM on the cartridge, with or
+    /// without a real time clock footer.
+    Length { len: usize, ram: usize },
+}
+
+impl Display for SaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoCartridge => write!(f, "no cartridge inserted"),
+            Self::NoBattery => write!(f, "cartridge doesn't have a battery to save with"),
+            Self::Length { len, ram } => {
+                write!(
+                    f,
+                    "save is {len} bytes, but the cartridge has {ram} bytes of RAM"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl Cartridge {
+    /// Whether the header says a battery keeps the cartridge's RAM, or its real time clock, going
+    /// while the power is off.
+    pub fn has_battery(&self) -> bool {
+        self.cartridge_type().is_some_and(|ty| ty.battery)
+    }
+
+    /// What the battery keeps around while the power is off, or `None` for a cartridge without
+    /// one. That's the raw contents of external RAM, followed by the 48-byte footer VBA and BGB
+    /// use for the real time clock of an MBC3 that has one.
+    pub fn export_save(&self) -> Option<Vec<u8>> {
+        if !self.has_battery() {
+            return None;
+        }
+
+        let mut save = self.ram.to_vec();
+
+        if let Some(rtc) = &self.rtc {
+            save.extend_from_slice(&rtc.save());
+        }
+
+        Some(save)
+    }
+
+    /// Restore what the battery kept around from [Cartridge::export_save], or another emulator.
+    /// The real time clock footer is optional, and the clock catches up on the time that's passed
+    /// since the save was exported. A footer on the save of a cartridge without a clock, which
+    /// some emulators write anyway, is ignored.
+    pub fn import_save(&mut self, save: &[u8]) -> Result<(), SaveError> {
+        if !self.has_battery() {
+            return Err(SaveError::NoBattery);
+        }
+
+        let error = SaveError::Length {
+            len: save.len(),
+            ram: self.ram.len(),
+        };
+
+        let (ram, footer) = save.split_at_checked(self.ram.len()).ok_or(error)?;
+
+        if !footer.is_empty() {
+            let loaded = match &mut self.rtc {
+                Some(rtc) => rtc.load(footer),
+                None => matches!(footer.len(), 44 | FOOTER_SIZE),
+            };
+
+            if !loaded {
+                return Err(error);
+            }
+        }
+
+        self.ram.copy_from_slice(ram);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+
+    /// An MBC3 cartridge with a battery, 8 KiB of RAM, and a real time clock that only moves when
+    /// the returned handle tells it to.
+    fn mbc3_timer() -> (Cartridge, Arc<AtomicU64>) {
+        let mut rom = vec![0x00; 0x8000];
+
+        rom[0x0147] = 0x10;
+        rom[0x0149] = 0x02;
+
+        let mut cartridge = Cartridge::new(rom.into()).unwrap();
+        let time = Arc::new(AtomicU64::new(1_700_000_000));
+
+        let clock = Arc::clone(&time);
+        cartridge.set_clock(move || clock.load(Ordering::Relaxed));
+
+        (cartridge, time)
+    }
+
+    /// Latch the clock of `cartridge` and read its day counter.
+    fn latched_days(cartridge: &mut Cartridge) -> u16 {
+        cartridge.write(0x6000, 0x00);
+        cartridge.write(0x6000, 0x01);
+
+        cartridge.write(0x4000, 0x0B);
+        let low = cartridge.read(0xA000);
+        cartridge.write(0x4000, 0x0C);
+        let high = cartridge.read(0xA000);
+
+        u16::from_le_bytes([low, high & 0x01])
+    }
+
+    #[test]
+    fn rtc_keeps_counting_while_saved() {
+        let (mut cartridge, time) = mbc3_timer();
+
+        cartridge.write(0x0000, 0x0A);
+        cartridge.write(0xA123, 0x42);
+        cartridge.write(0x4000, 0x0B);
+        cartridge.write(0xA000, 0x05);
+        assert_eq!(5, latched_days(&mut cartridge));
+
+        let save = cartridge.export_save().unwrap();
+        assert_eq!(0x2000 + FOOTER_SIZE, save.len());
+
+        // A day passes with the emulator closed
+        time.fetch_add(24 * 60 * 60, Ordering::Relaxed);
+
+        let (mut restored, clock) = mbc3_timer();
+        clock.store(time.load(Ordering::Relaxed), Ordering::Relaxed);
+        restored.import_save(&save).unwrap();
+
+        restored.write(0x0000, 0x0A);
+        assert_eq!(6, latched_days(&mut restored));
+
+        restored.write(0x4000, 0x00);
+        assert_eq!(0x42, restored.read(0xA123));
+    }
+
+    #[test]
+    fn halted_rtc_stays_put_while_saved() {
+        let (mut cartridge, time) = mbc3_timer();
+
+        cartridge.write(0x0000, 0x0A);
+        cartridge.write(0x4000, 0x0C);
+        cartridge.write(0xA000, 0x40);
+
+        let save = cartridge.export_save().unwrap();
+        time.fetch_add(24 * 60 * 60, Ordering::Relaxed);
+
+        let (mut restored, clock) = mbc3_timer();
+        clock.store(time.load(Ordering::Relaxed), Ordering::Relaxed);
+
+        // The 44-byte footer of older emulators works too
+        restored.import_save(&save[..save.len() - 4]).unwrap();
+        restored.write(0x0000, 0x0A);
+        assert_eq!(0, latched_days(&mut restored));
+    }
+
+    #[test]
+    fn mbc2_save_of_half_bytes() {
+        let mut rom = vec![0x00; 0x8000];
+        rom[0x0147] = 0x06;
+
+        let mut cartridge = Cartridge::new(rom.into()).unwrap();
+
+        // Some emulators only store the half of each byte that's there
+        let mut save = [0x05; 0x200];
+        save[0x1FF] = 0x0A;
+
+        assert_eq!(Ok(()), cartridge.import_save(&save));
+
+        cartridge.write(0x0000, 0x0A);
+        assert_eq!(0xF5, cartridge.read(0xA000));
+        assert_eq!(0xFA, cartridge.read(0xA1FF));
+    }
+
+    #[test]
+    fn save_needs_battery_and_matching_length() {
+        let (mut cartridge, _) = mbc3_timer();
+
+        assert_eq!(
+            Err(SaveError::Length {
+                len: 0x2001,
+                ram: 0x2000
+            }),
+            cartridge.import_save(&[0x00; 0x2001])
+        );
+        assert_eq!(Ok(()), cartridge.import_save(&[0x00; 0x2000]));
+
+        let mut rom = vec![0x00; 0x8000];
+
+        rom[0x0147] = 0x03;
+        rom[0x0149] = 0x02;
+
+        // An MBC1 doesn't have a clock, so the footer is ignored
+        let mut cartridge = Cartridge::new(rom.into()).unwrap();
+        let mut save = vec![0x12; 0x2000 + FOOTER_SIZE];
+
+        save[0x2000..].fill(0x34);
+        assert_eq!(Ok(()), cartridge.import_save(&save));
+        assert_eq!(Ok(()), cartridge.import_save(&save[..save.len() - 4]));
+        assert_eq!(Some(vec![0x12; 0x2000]), cartridge.export_save());
+        assert!(cartridge.import_save(&save[..save.len() - 1]).is_err());
+
+        let mut rom = vec![0x00; 0x8000];
+        rom[0x0147] = 0x01;
+
+        let mut cartridge = Cartridge::new(rom.into()).unwrap();
+
+        assert_eq!(None, cartridge.export_save());
+        assert_eq!(Err(SaveError::NoBattery), cartridge.import_save(&[]));
+    }
+}
@@ -1,10 +1,22 @@
-use std::{collections::VecDeque, fmt::Debug};
+mod alu;
+mod mcode_queue;
 
+use std::fmt::Debug;
+
+use dotmatrix_opcodes::AluOp;
+use dotmatrix_opcodes::CbOpcode;
+use dotmatrix_opcodes::Condition;
 use dotmatrix_opcodes::MCode;
 use dotmatrix_opcodes::Opcode;
+use dotmatrix_opcodes::UnaryOp;
+use dotmatrix_opcodes::R16;
+use dotmatrix_opcodes::R8;
 use proc_bitfield::bitfield;
 
+use crate::bus::BusActivity;
 use crate::Bus;
+use crate::Interrupt;
+use mcode_queue::MCodeQueue;
 
 /// The value of PC _after running the boot ROM_.
 const AFTER_BOOT_PC: u16 = 0x0100;
@@ -12,6 +24,16 @@ const AFTER_BOOT_PC: u16 = 0x0100;
 /// The value of SP _after running the boot ROM_.
 const AFTER_BOOT_SP: u16 = 0xFFFE;
 
+/// Servicing an interrupt takes the place of a fetch: two idle m-cycles, pushing `PC`, then jumping
+/// to the interrupt vector. The vector is only chosen after the high byte of `PC` is pushed.
+const INTERRUPT_DISPATCH: [MCode; 5] = [
+    MCode::InternalDelay,
+    MCode::InternalDelay,
+    MCode::Push(R8::PcHigh),
+    MCode::Dispatch,
+    MCode::Jp(R16::WZ),
+];
+
 /// The SM83 by Sharp is the CPU used in the DMG. It is distinct from a Zilog Z80 despite several
 /// similarities.
 ///
@@ -31,8 +53,34 @@ pub struct Sm83 {
     /// The instruction register holds the opcode of the currently executing instruction.
     pub ir: Opcode,
 
+    /// The `W` register temporarily holds the high byte of a 16-bit operand between the m-cycles
+    /// of an instruction.
+    pub w: u8,
+
+    /// The `Z` register temporarily holds an operand between the m-cycles of an instruction.
+    pub z: u8,
+
+    /// The interrupt master enable flag. Interrupts are only serviced while this is set.
+    pub ime: bool,
+
+    /// Set by `EI`, IME is set once the following instruction has been fetched.
+    pub ime_pending: bool,
+
+    /// Set by `HALT`, the CPU neither fetches nor executes until an interrupt is pending.
+    pub halted: bool,
+
+    /// Set when `HALT` executes with IME clear and an interrupt already pending. The DMG fails to
+    /// increment PC on the next fetch, so the byte after `HALT` is read twice.
+    pub halt_bug: bool,
+
+    /// Set by an illegal opcode, which locks up the CPU until it is reset. See [Sm83::is_locked].
+    locked: bool,
+
+    /// The bus access made during the current m-cycle, if any.
+    activity: Option<BusActivity>,
+
     /// A queue of m-codes to be executed over the next few cycles.
-    pub mcode_queue: VecDeque<MCode>,
+    pub mcode_queue: MCodeQueue,
 }
 
 impl Sm83 {
@@ -43,56 +91,408 @@ impl Sm83 {
             pc: AFTER_BOOT_PC,
             sp: AFTER_BOOT_SP,
             ir: Opcode::NOP,
-            mcode_queue: VecDeque::with_capacity(8),
+            w: 0,
+            z: 0,
+            ime: false,
+            ime_pending: false,
+            halted: false,
+            halt_bug: false,
+            locked: false,
+            activity: None,
+            mcode_queue: MCodeQueue::new(),
         }
     }
 
-    /// Execute one m-cycle worth of code on the CPU.
-    pub fn exec_m_cycle(&mut self, bus: &mut Bus) {
-        // Fetching the next instruction and executing the current overlap by one m-cycle.
-        if self.mcode_queue.len() <= 1 {
-            self.fetch(bus);
+    /// Create a new [Sm83] configured for use in a DMG, as it powers on before running the boot
+    /// ROM. Everything starts out zeroed, including PC.
+    pub fn new_dmg_power_on() -> Self {
+        Self {
+            registers: Sm83Registers(0),
+            pc: 0x0000,
+            sp: 0x0000,
+            ..Self::new_dmg()
+        }
+    }
+
+    /// Execute one m-cycle worth of code on the CPU, returning what the CPU did on the bus. The
+    /// bus is ticked first, so devices keep time even while the CPU is halted, stalled, or locked
+    /// up.
+    pub fn exec_m_cycle(&mut self, bus: &mut Bus) -> Option<BusActivity> {
+        bus.tick();
+
+        if self.locked || bus.cpu_stalled() || self.still_halted(bus) {
+            return None;
         }
 
-        let mcode = self
-            .mcode_queue
-            .pop_front()
-            .expect("Attempted to pop from empty mcode_queue");
+        if let Some(mcode) = self.mcode_queue.pop_front() {
+            self.exec_mcode(mcode, bus);
+        }
+
+        // Fetching the next instruction and executing the current overlap by one m-cycle. The fetch
+        // happens after the last m-code so that it sees any change that m-code made to PC.
+        if self.mcode_queue.is_empty() && !self.halted {
+            self.fetch(bus);
+        }
 
-        self.exec_mcode(mcode, bus);
+        self.activity.take()
     }
 
     /// Execute until the end of the current instruction. Fetches an instruction if queue is empty.
+    /// Returns the bus activity of each m-cycle, starting with the fetch if there was one.
     ///
-    /// For testing purposes, specifically SingleStepTests.
-    pub fn exec_instruction(&mut self, bus: &mut Bus) {
+    /// For testing purposes, specifically SingleStepTests. While halted this only burns a single
+    /// m-cycle, so callers stepping through a `HALT` don't spin forever.
+    pub fn exec_instruction(&mut self, bus: &mut Bus) -> Vec<Option<BusActivity>> {
+        let mut trace = Vec::with_capacity(8);
+
+        // The bus is ticked once per m-cycle, before the CPU accesses it
+        if self.locked || bus.cpu_stalled() || self.still_halted(bus) {
+            bus.tick();
+            return trace;
+        }
+
         if self.mcode_queue.is_empty() {
+            bus.tick();
             self.fetch(bus);
+            trace.push(self.activity.take());
         }
 
         while let Some(mcode) = self.mcode_queue.pop_front() {
+            bus.tick();
             self.exec_mcode(mcode, bus);
+            trace.push(self.activity.take());
         }
+
+        trace
+    }
+
+    /// Read from the bus, recording the access as this m-cycle's [BusActivity].
+    fn read(&mut self, bus: &Bus, addr: u16) -> u8 {
+        let value = bus.read(addr);
+
+        self.record(BusActivity::read(addr, value));
+
+        value
+    }
+
+    /// Write to the bus, recording the access as this m-cycle's [BusActivity].
+    fn write(&mut self, bus: &mut Bus, addr: u16, value: u8) {
+        bus.write(addr, value);
+
+        self.record(BusActivity::write(addr, value));
     }
 
-    /// Retrieve the next instruction and increment PC.
+    fn record(&mut self, activity: BusActivity) {
+        debug_assert!(
+            self.activity.is_none(),
+            "More than one bus access in a single m-cycle: {:?} then {activity:?}",
+            self.activity,
+        );
+
+        self.activity = Some(activity);
+    }
+
+    /// Whether the CPU has locked up after executing an illegal opcode. On hardware only a reset
+    /// recovers from this, so further calls to [Sm83::exec_m_cycle] do nothing.
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Whether the CPU remains halted this m-cycle. A pending interrupt wakes the CPU regardless of
+    /// IME, which only decides whether the interrupt is serviced or execution simply resumes.
+    fn still_halted(&mut self, bus: &Bus) -> bool {
+        if self.halted && bus.pending_interrupts() != 0 {
+            self.halted = false;
+        }
+
+        self.halted
+    }
+
+    /// Retrieve the next instruction and increment PC, unless the HALT bug skips the increment.
+    ///
+    /// If IME is set and an interrupt is pending, the interrupt is dispatched instead.
     pub fn fetch(&mut self, bus: &mut Bus) {
-        self.ir = bus.read(self.pc).into();
+        if self.ime && bus.pending_interrupts() != 0 {
+            self.ime = false;
+            self.mcode_queue.extend(INTERRUPT_DISPATCH);
+
+            return;
+        }
+
+        self.ir = self.read(bus, self.pc).into();
         self.ir
             .mcode()
             .iter()
             .for_each(|&mcode| self.mcode_queue.push_back(mcode));
 
-        self.pc = self.pc.wrapping_add(self.ir.length());
+        if self.halt_bug {
+            self.halt_bug = false;
+        } else {
+            self.pc = self.pc.wrapping_add(1);
+        }
+
+        if self.ime_pending {
+            self.ime_pending = false;
+            self.ime = true;
+        }
     }
 
-    fn exec_mcode(&mut self, mcode: MCode, _bus: &mut Bus) {
+    /// Execute a single m-code. [MCode] is only defined in `dotmatrix_opcodes`, and this match is
+    /// deliberately exhaustive with no wildcard arm, so adding a variant there fails to compile
+    /// until it can be executed here.
+    fn exec_mcode(&mut self, mcode: MCode, bus: &mut Bus) {
         match mcode {
-            MCode::Nop => (),
-            MCode::Illegal => panic!(
-                "Illegal instruction encountered: {:#04X} ({})",
-                self.ir as u8, self.ir
-            ),
+            MCode::Nop | MCode::InternalDelay => (),
+            MCode::Illegal => {
+                self.locked = true;
+                self.mcode_queue.clear();
+            }
+            MCode::Ld(dst, src) => self.set_r8(dst, self.r8(src)),
+            MCode::ReadImm(dst) => {
+                let value = self.read(bus, self.pc);
+                self.set_r8(dst, value);
+                self.pc = self.pc.wrapping_add(1);
+            }
+            MCode::Read(addr, dst) => {
+                let value = self.read(bus, self.r16(addr));
+                self.set_r8(dst, value);
+            }
+            MCode::Write(addr, src) => self.write(bus, self.r16(addr), self.r8(src)),
+            MCode::ReadInc(addr, dst) => {
+                let value = self.read(bus, self.r16(addr));
+                self.set_r8(dst, value);
+                self.set_r16(addr, self.r16(addr).wrapping_add(1));
+            }
+            MCode::ReadDec(addr, dst) => {
+                let value = self.read(bus, self.r16(addr));
+                self.set_r8(dst, value);
+                self.set_r16(addr, self.r16(addr).wrapping_sub(1));
+            }
+            MCode::WriteInc(addr, src) => {
+                self.write(bus, self.r16(addr), self.r8(src));
+                self.set_r16(addr, self.r16(addr).wrapping_add(1));
+            }
+            MCode::WriteDec(addr, src) => {
+                self.write(bus, self.r16(addr), self.r8(src));
+                self.set_r16(addr, self.r16(addr).wrapping_sub(1));
+            }
+            MCode::Push(src) => {
+                self.sp = self.sp.wrapping_sub(1);
+                self.write(bus, self.sp, self.r8(src));
+            }
+            MCode::Pop(dst) => {
+                let value = self.read(bus, self.sp);
+                self.set_r8(dst, value);
+                self.sp = self.sp.wrapping_add(1);
+            }
+            MCode::ReadHigh(offset, dst) => {
+                let value = self.read(bus, 0xFF00 | self.r8(offset) as u16);
+                self.set_r8(dst, value);
+            }
+            MCode::WriteHigh(offset, src) => {
+                self.write(bus, 0xFF00 | self.r8(offset) as u16, self.r8(src))
+            }
+            MCode::Alu(op, src) => self.alu(op, self.r8(src)),
+            MCode::Inc16(r16) => self.set_r16(r16, self.r16(r16).wrapping_add(1)),
+            MCode::Dec16(r16) => self.set_r16(r16, self.r16(r16).wrapping_sub(1)),
+            MCode::Unary(op, r8) => {
+                let result = self.unary(op, self.r8(r8));
+                self.set_r8(r8, result);
+            }
+            MCode::WriteUnary(op, addr) => {
+                self.z = self.unary(op, self.z);
+                self.write(bus, self.r16(addr), self.z);
+            }
+            MCode::Daa => {
+                let a = self.registers.a();
+                let result = alu::daa(&mut self.registers, a);
+                self.registers.set_a(result);
+            }
+            MCode::Cpl => alu::cpl(&mut self.registers),
+            MCode::Scf => alu::scf(&mut self.registers),
+            MCode::Ccf => alu::ccf(&mut self.registers),
+            MCode::Rlca => self.rotate_a(alu::rlc),
+            MCode::Rrca => self.rotate_a(alu::rrc),
+            MCode::Rla => self.rotate_a(alu::rl),
+            MCode::Rra => self.rotate_a(alu::rr),
+            MCode::CheckCondition(cond) => {
+                // The queue only ever holds the current instruction, the next fetch isn't queued
+                // until it runs dry, so clearing it can't drop the fetch.
+                if !self.condition(cond) {
+                    self.mcode_queue.clear();
+                }
+            }
+            MCode::Jp(addr) => self.pc = self.r16(addr),
+            MCode::Jr => self.pc = self.pc.wrapping_add(self.z as i8 as u16),
+            MCode::Rst => self.pc = self.ir as u16 & 0x0038,
+            MCode::Ime(ime) => self.ime = ime,
+            MCode::Ei => self.ime_pending = true,
+            MCode::Halt => {
+                if !self.ime && bus.pending_interrupts() != 0 {
+                    self.halt_bug = true;
+                } else {
+                    self.halted = true;
+                }
+            }
+            MCode::Stop => {
+                // STOP is meant to wait for a button press, but without a joypad it waits for any
+                // interrupt like HALT instead
+                if !bus.stop() {
+                    self.halted = true;
+                }
+            }
+            MCode::Dispatch => {
+                let vector = match Interrupt::highest(bus.pending_interrupts()) {
+                    Some(interrupt) => {
+                        bus.acknowledge_interrupt(interrupt);
+                        interrupt.vector()
+                    }
+                    None => 0x0000,
+                };
+
+                self.set_r16(R16::WZ, vector);
+                self.exec_mcode(MCode::Push(R8::PcLow), bus);
+            }
+            MCode::FetchCb => {
+                let cb_opcode: CbOpcode = self.read(bus, self.pc).into();
+                self.pc = self.pc.wrapping_add(1);
+
+                cb_opcode
+                    .mcode()
+                    .iter()
+                    .for_each(|&mcode| self.mcode_queue.push_back(mcode));
+            }
+            MCode::Bit(bit, r8) => {
+                let value = self.r8(r8);
+                alu::bit(&mut self.registers, bit, value);
+            }
+        }
+    }
+
+    /// Perform an 8-bit ALU operation with `A` and `value`, storing the result in `A` (except for
+    /// [AluOp::Cp], which only updates the flags).
+    fn alu(&mut self, op: AluOp, value: u8) {
+        let a = self.registers.a();
+        let carry = self.registers.c_flag();
+
+        let result = match op {
+            AluOp::Add => alu::add(&mut self.registers, a, value, false),
+            AluOp::Adc => alu::add(&mut self.registers, a, value, carry),
+            AluOp::Sub => alu::sub(&mut self.registers, a, value, false),
+            AluOp::Sbc => alu::sub(&mut self.registers, a, value, carry),
+            AluOp::And => alu::and(&mut self.registers, a, value),
+            AluOp::Xor => alu::xor(&mut self.registers, a, value),
+            AluOp::Or => alu::or(&mut self.registers, a, value),
+            AluOp::Cp => {
+                alu::sub(&mut self.registers, a, value, false);
+                a
+            }
+        };
+
+        self.registers.set_a(result);
+    }
+
+    /// Rotate `A` with one of the rotate operations. Unlike the CB-prefixed rotates, the `z` flag is
+    /// always cleared.
+    fn rotate_a(&mut self, rotate: fn(&mut Sm83Registers, u8) -> u8) {
+        let a = self.registers.a();
+        let result = rotate(&mut self.registers, a);
+
+        self.registers.set_a(result);
+        self.registers.set_z_flag(false);
+    }
+
+    /// Perform a single-operand ALU operation on `value`, returning the result.
+    fn unary(&mut self, op: UnaryOp, value: u8) -> u8 {
+        match op {
+            UnaryOp::Inc => alu::inc(&mut self.registers, value),
+            UnaryOp::Dec => alu::dec(&mut self.registers, value),
+            UnaryOp::Rlc => alu::rlc(&mut self.registers, value),
+            UnaryOp::Rrc => alu::rrc(&mut self.registers, value),
+            UnaryOp::Rl => alu::rl(&mut self.registers, value),
+            UnaryOp::Rr => alu::rr(&mut self.registers, value),
+            UnaryOp::Sla => alu::sla(&mut self.registers, value),
+            UnaryOp::Sra => alu::sra(&mut self.registers, value),
+            UnaryOp::Srl => alu::srl(&mut self.registers, value),
+            UnaryOp::Swap => alu::swap(&mut self.registers, value),
+            UnaryOp::Res(bit) => value & !(1 << bit),
+            UnaryOp::Set(bit) => value | 1 << bit,
+        }
+    }
+
+    /// Check whether a condition holds for the current flags.
+    fn condition(&self, cond: Condition) -> bool {
+        match cond {
+            Condition::NotZero => !self.registers.z_flag(),
+            Condition::Zero => self.registers.z_flag(),
+            Condition::NotCarry => !self.registers.c_flag(),
+            Condition::Carry => self.registers.c_flag(),
+        }
+    }
+
+    /// Read the value of an 8-bit register.
+    fn r8(&self, r8: R8) -> u8 {
+        match r8 {
+            R8::A => self.registers.a(),
+            R8::F => self.registers.f(),
+            R8::B => self.registers.b(),
+            R8::C => self.registers.c(),
+            R8::D => self.registers.d(),
+            R8::E => self.registers.e(),
+            R8::H => self.registers.h(),
+            R8::L => self.registers.l(),
+            R8::W => self.w,
+            R8::Z => self.z,
+            R8::SpLow => self.sp.to_le_bytes()[0],
+            R8::SpHigh => self.sp.to_le_bytes()[1],
+            R8::PcLow => self.pc.to_le_bytes()[0],
+            R8::PcHigh => self.pc.to_le_bytes()[1],
+        }
+    }
+
+    /// Write a value to an 8-bit register.
+    fn set_r8(&mut self, r8: R8, value: u8) {
+        match r8 {
+            R8::A => self.registers.set_a(value),
+            R8::F => self.registers.set_f(value & 0xF0),
+            R8::B => self.registers.set_b(value),
+            R8::C => self.registers.set_c(value),
+            R8::D => self.registers.set_d(value),
+            R8::E => self.registers.set_e(value),
+            R8::H => self.registers.set_h(value),
+            R8::L => self.registers.set_l(value),
+            R8::W => self.w = value,
+            R8::Z => self.z = value,
+            R8::SpLow => self.sp = (self.sp & 0xFF00) | value as u16,
+            R8::SpHigh => self.sp = (self.sp & 0x00FF) | (value as u16) << 8,
+            R8::PcLow => self.pc = (self.pc & 0xFF00) | value as u16,
+            R8::PcHigh => self.pc = (self.pc & 0x00FF) | (value as u16) << 8,
+        }
+    }
+
+    /// Read the value of a 16-bit register pair.
+    fn r16(&self, r16: R16) -> u16 {
+        match r16 {
+            R16::AF => self.registers.af(),
+            R16::BC => self.registers.bc(),
+            R16::DE => self.registers.de(),
+            R16::HL => self.registers.hl(),
+            R16::SP => self.sp,
+            R16::WZ => u16::from_le_bytes([self.z, self.w]),
+            R16::PC => self.pc,
+        }
+    }
+
+    /// Write a value to a 16-bit register pair.
+    fn set_r16(&mut self, r16: R16, value: u16) {
+        match r16 {
+            R16::AF => self.registers.set_af(value & 0xFFF0),
+            R16::BC => self.registers.set_bc(value),
+            R16::DE => self.registers.set_de(value),
+            R16::HL => self.registers.set_hl(value),
+            R16::SP => self.sp = value,
+            R16::WZ => [self.z, self.w] = value.to_le_bytes(),
+            R16::PC => self.pc = value,
         }
     }
 }
@@ -115,6 +515,13 @@ impl Debug for Sm83 {
         write!(f, "SP:{:04X} ", self.sp)?;
         write!(f, "PC:{:04X} ", self.pc)?;
 
+        // WZ only means anything partway through an instruction
+        if !self.mcode_queue.is_empty() {
+            write!(f, "WZ:{:04X} ", self.r16(R16::WZ))?;
+        }
+
+        write!(f, "queue:{:?} ", self.mcode_queue)?;
+
         write!(f, "}}")
     }
 }
@@ -241,22 +648,739 @@ impl Sm83Registers {
 
 #[cfg(test)]
 mod test {
-    use std::collections::VecDeque;
+    use dotmatrix_opcodes::Cycles;
 
     use super::*;
+    use crate::bus::{IE, IF};
 
     #[test]
     fn sm83_debug() {
-        let expected = "Sm83 { A:CD c:1 h:0 n:1 z:0 BC:89AB DE:4567 HL:0123 SP:A801 PC:532D }";
+        let expected =
+            "Sm83 { A:CD c:1 h:0 n:1 z:0 BC:89AB DE:4567 HL:0123 SP:A801 PC:532D queue:[] }";
         let registers = Sm83Registers(0x01_23_45_67_89_AB_CD_50);
         let cpu = Sm83 {
             registers,
             pc: 0x532D,
             sp: 0xA801,
             ir: Opcode::NOP,
-            mcode_queue: VecDeque::with_capacity(0),
+            w: 0,
+            z: 0,
+            ime: false,
+            ime_pending: false,
+            halted: false,
+            halt_bug: false,
+            locked: false,
+            activity: None,
+            mcode_queue: MCodeQueue::new(),
         };
 
         assert_eq!(expected, &format!("{cpu:?}"));
     }
+
+    #[test]
+    fn sm83_debug_shows_wz_mid_instruction() {
+        let mut bus = Bus::flat();
+        let mut cpu = Sm83::new_dmg();
+
+        bus.write(cpu.pc, Opcode::LD_A_a16 as u8);
+        bus.write(cpu.pc + 1, 0x34);
+        bus.write(cpu.pc + 2, 0x12);
+
+        // Fetch, then read both bytes of the address into WZ
+        for _ in 0..3 {
+            cpu.exec_m_cycle(&mut bus);
+        }
+
+        assert!(
+            format!("{cpu:?}").ends_with("PC:0103 WZ:1234 queue:[Read(WZ, A), InternalDelay] }")
+        );
+    }
+
+    #[test]
+    fn ld_a16_a_stages_address_in_wz() {
+        let mut bus = Bus::flat();
+        let mut cpu = Sm83::new_dmg();
+
+        cpu.registers.set_a(0x42);
+        bus.write(cpu.pc, Opcode::LD_a16_A as u8);
+        bus.write(cpu.pc + 1, 0x00);
+        bus.write(cpu.pc + 2, 0xC0);
+
+        cpu.exec_instruction(&mut bus);
+
+        assert_eq!(0x42, bus.read(0xC000));
+        assert_eq!(0xC000, cpu.r16(R16::WZ));
+        assert_eq!(AFTER_BOOT_PC + 3, cpu.pc);
+    }
+
+    #[test]
+    fn idu_inc_dec_pc() {
+        let mut bus = Bus::flat();
+        let mut cpu = Sm83::new_dmg();
+
+        cpu.pc = 0xFFFF;
+        cpu.exec_mcode(MCode::Inc16(R16::PC), &mut bus);
+
+        assert_eq!(0x0000, cpu.pc);
+
+        cpu.exec_mcode(MCode::Dec16(R16::PC), &mut bus);
+
+        assert_eq!(0xFFFF, cpu.pc);
+        assert!(cpu.activity.is_none());
+    }
+
+    #[test]
+    fn ld_a16_sp_wraps_address() {
+        let mut bus = Bus::flat();
+        let mut cpu = Sm83::new_dmg();
+
+        cpu.sp = 0xABCD;
+        bus.write(cpu.pc, Opcode::LD_a16_SP as u8);
+        bus.write(cpu.pc + 1, 0xFF);
+        bus.write(cpu.pc + 2, 0xFF);
+
+        cpu.exec_instruction(&mut bus);
+
+        assert_eq!(0xCD, bus.read(0xFFFF));
+        assert_eq!(0xAB, bus.read(0x0000));
+    }
+
+    #[test]
+    fn push_wraps_sp() {
+        let mut bus = Bus::flat();
+        let mut cpu = Sm83::new_dmg();
+
+        cpu.sp = 0x0000;
+        cpu.registers.set_bc(0x1234);
+        bus.write(cpu.pc, Opcode::PUSH_BC as u8);
+
+        cpu.exec_instruction(&mut bus);
+
+        assert_eq!(0xFFFE, cpu.sp);
+        assert_eq!(0x12, bus.read(0xFFFF));
+        assert_eq!(0x34, bus.read(0xFFFE));
+    }
+
+    #[test]
+    fn pop_af_masks_f() {
+        let mut bus = Bus::flat();
+        let mut cpu = Sm83::new_dmg();
+
+        cpu.sp = 0xC000;
+        bus.write16(0xC000, 0x12FF);
+        bus.write(cpu.pc, Opcode::POP_AF as u8);
+
+        cpu.exec_instruction(&mut bus);
+
+        assert_eq!(0x12F0, cpu.registers.af());
+        assert_eq!(0xF0, *cpu.registers & 0xFF);
+        assert_eq!(0xC002, cpu.sp);
+    }
+
+    #[test]
+    fn xor_a_clears_a() {
+        let mut bus = Bus::flat();
+        let mut cpu = Sm83::new_dmg();
+
+        cpu.registers.set_a(0x5A);
+        cpu.registers.set_f(0xF0);
+        bus.write(cpu.pc, Opcode::XOR_A as u8);
+
+        cpu.exec_instruction(&mut bus);
+
+        assert_eq!(0x00, cpu.registers.a());
+        assert_eq!(0x80, cpu.registers.f());
+    }
+
+    #[test]
+    fn cp_preserves_a() {
+        let mut bus = Bus::flat();
+        let mut cpu = Sm83::new_dmg();
+
+        cpu.registers.set_a(0x42);
+        cpu.registers.set_b(0x42);
+        bus.write(cpu.pc, Opcode::CP_B as u8);
+
+        cpu.exec_instruction(&mut bus);
+
+        assert_eq!(0x42, cpu.registers.a());
+        assert_eq!(0xC0, cpu.registers.f());
+    }
+
+    #[test]
+    fn inc16_dec16_wrap() {
+        let mut bus = Bus::flat();
+        let mut cpu = Sm83::new_dmg();
+
+        cpu.sp = 0xFFFF;
+        cpu.registers.set_bc(0x0000);
+        bus.write(cpu.pc, Opcode::INC_SP as u8);
+        bus.write(cpu.pc + 1, Opcode::DEC_BC as u8);
+
+        cpu.exec_instruction(&mut bus);
+        cpu.exec_instruction(&mut bus);
+
+        assert_eq!(0x0000, cpu.sp);
+        assert_eq!(0xFFFF, cpu.registers.bc());
+    }
+
+    #[test]
+    fn rotate_a_clears_z() {
+        let mut bus = Bus::flat();
+        let mut cpu = Sm83::new_dmg();
+
+        cpu.registers.set_a(0x00);
+        cpu.registers.set_f(0x80);
+        bus.write(cpu.pc, Opcode::RLCA as u8);
+
+        cpu.exec_instruction(&mut bus);
+
+        assert_eq!(0x00, cpu.registers.a());
+        assert_eq!(0x00, cpu.registers.f());
+
+        cpu.registers.set_a(0x80);
+        bus.write(cpu.pc, Opcode::RLA as u8);
+
+        cpu.exec_instruction(&mut bus);
+
+        assert_eq!(0x00, cpu.registers.a());
+        assert_eq!(0x10, cpu.registers.f());
+    }
+
+    #[test]
+    fn cb_rotate_sets_z() {
+        let mut bus = Bus::flat();
+        let mut cpu = Sm83::new_dmg();
+
+        cpu.registers.set_b(0x80);
+        cpu.registers.set_f(0x00);
+        bus.write(cpu.pc, Opcode::CB_PREFIX as u8);
+        bus.write(cpu.pc.wrapping_add(1), CbOpcode::RL_B as u8);
+
+        cpu.exec_instruction(&mut bus);
+
+        assert_eq!(0x00, cpu.registers.b());
+        assert_eq!(0x90, cpu.registers.f());
+    }
+
+    #[test]
+    fn res_set_ptr_hl_leave_flags() {
+        let mut bus = Bus::flat();
+        let mut cpu = Sm83::new_dmg();
+
+        cpu.registers.set_hl(0xC000);
+        cpu.registers.set_f(0xF0);
+        bus.write(0xC000, 0x0F);
+        bus.write(cpu.pc, Opcode::CB_PREFIX as u8);
+        bus.write(cpu.pc.wrapping_add(1), CbOpcode::SET_7_PTR_HL as u8);
+
+        cpu.exec_instruction(&mut bus);
+
+        assert_eq!(0x8F, bus.read(0xC000));
+
+        bus.write(cpu.pc, Opcode::CB_PREFIX as u8);
+        bus.write(cpu.pc.wrapping_add(1), CbOpcode::RES_0_PTR_HL as u8);
+
+        cpu.exec_instruction(&mut bus);
+
+        assert_eq!(0x8E, bus.read(0xC000));
+        assert_eq!(0xF0, cpu.registers.f());
+    }
+
+    #[test]
+    fn jp_fetches_from_new_pc() {
+        let mut bus = Bus::flat();
+        let mut cpu = Sm83::new_dmg();
+
+        bus.write(cpu.pc, Opcode::JP_a16 as u8);
+        bus.write(cpu.pc.wrapping_add(1), 0x00);
+        bus.write(cpu.pc.wrapping_add(2), 0xC0);
+        bus.write(0xC000, Opcode::INC_A as u8);
+
+        cpu.registers.set_a(0x00);
+
+        // One m-cycle to fetch the jump, then four to execute it while fetching from the target.
+        for _ in 0..5 {
+            cpu.exec_m_cycle(&mut bus);
+        }
+
+        assert_eq!(0xC001, cpu.pc);
+        assert_eq!(Opcode::INC_A, cpu.ir);
+
+        cpu.exec_m_cycle(&mut bus);
+
+        assert_eq!(0x01, cpu.registers.a());
+    }
+
+    #[test]
+    fn jp_cc_not_taken_drops_cycle() {
+        let mut bus = Bus::flat();
+        let mut cpu = Sm83::new_dmg();
+
+        cpu.registers.set_f(0x80);
+        bus.write(cpu.pc, Opcode::JP_NZ_a16 as u8);
+        bus.write(cpu.pc.wrapping_add(3), Opcode::INC_A as u8);
+
+        // One m-cycle to fetch the jump, then three to read the operand and check the condition
+        // while fetching the following instruction.
+        for _ in 0..4 {
+            cpu.exec_m_cycle(&mut bus);
+        }
+
+        assert_eq!(AFTER_BOOT_PC + 4, cpu.pc);
+        assert_eq!(Opcode::INC_A, cpu.ir);
+    }
+
+    #[test]
+    fn jr_sign_extends_offset() {
+        let mut bus = Bus::flat();
+        let mut cpu = Sm83::new_dmg();
+
+        bus.write(cpu.pc, Opcode::JR as u8);
+        bus.write(cpu.pc.wrapping_add(1), 0x7F);
+
+        cpu.exec_instruction(&mut bus);
+
+        assert_eq!(AFTER_BOOT_PC + 2 + 0x7F, cpu.pc);
+
+        bus.write(cpu.pc, Opcode::JR as u8);
+        bus.write(cpu.pc.wrapping_add(1), 0x80);
+
+        cpu.exec_instruction(&mut bus);
+
+        assert_eq!(AFTER_BOOT_PC + 2 + 0x7F + 2 - 0x80, cpu.pc);
+    }
+
+    #[test]
+    fn jr_nz_taken_cycles() {
+        let mut bus = Bus::flat();
+        let mut cpu = Sm83::new_dmg();
+
+        cpu.registers.set_f(0x00);
+        bus.write(cpu.pc, Opcode::JR_NZ as u8);
+        bus.write(cpu.pc.wrapping_add(1), 0x10);
+
+        let target = AFTER_BOOT_PC + 2 + 0x10;
+        bus.write(target, Opcode::NOP as u8);
+
+        // Fetch the opcode, read the offset, then one idle m-cycle to check the condition.
+        let opcode = cpu.exec_m_cycle(&mut bus);
+        let offset = cpu.exec_m_cycle(&mut bus);
+        let check = cpu.exec_m_cycle(&mut bus);
+
+        assert_eq!(
+            Some(BusActivity::read(AFTER_BOOT_PC, Opcode::JR_NZ as u8)),
+            opcode
+        );
+        assert_eq!(Some(BusActivity::read(AFTER_BOOT_PC + 1, 0x10)), offset);
+        assert_eq!(None, check);
+        assert_eq!(Opcode::JR_NZ, cpu.ir);
+        assert_eq!(1, cpu.mcode_queue.len());
+
+        // The jump and the fetch from the target overlap.
+        let jump = cpu.exec_m_cycle(&mut bus);
+
+        assert_eq!(Some(BusActivity::read(target, Opcode::NOP as u8)), jump);
+        assert_eq!(Opcode::NOP, cpu.ir);
+        assert_eq!(target + 1, cpu.pc);
+    }
+
+    #[test]
+    fn jr_nz_not_taken_cycles() {
+        let mut bus = Bus::flat();
+        let mut cpu = Sm83::new_dmg();
+
+        cpu.registers.set_f(0x80);
+        bus.write(cpu.pc, Opcode::JR_NZ as u8);
+        bus.write(cpu.pc.wrapping_add(1), 0x10);
+        bus.write(cpu.pc.wrapping_add(2), Opcode::INC_A as u8);
+
+        let opcode = cpu.exec_m_cycle(&mut bus);
+        let offset = cpu.exec_m_cycle(&mut bus);
+
+        assert_eq!(
+            Some(BusActivity::read(AFTER_BOOT_PC, Opcode::JR_NZ as u8)),
+            opcode
+        );
+        assert_eq!(Some(BusActivity::read(AFTER_BOOT_PC + 1, 0x10)), offset);
+
+        // The failed check drops the jump, so the following instruction is fetched right away.
+        let check = cpu.exec_m_cycle(&mut bus);
+
+        assert_eq!(
+            Some(BusActivity::read(AFTER_BOOT_PC + 2, Opcode::INC_A as u8)),
+            check
+        );
+        assert_eq!(Opcode::INC_A, cpu.ir);
+        assert_eq!(AFTER_BOOT_PC + 3, cpu.pc);
+        assert_eq!(1, cpu.mcode_queue.len());
+    }
+
+    #[test]
+    fn jr_wraps_pc() {
+        let mut bus = Bus::flat();
+        let mut cpu = Sm83::new_dmg();
+
+        cpu.pc = 0x0000;
+        bus.write(0x0000, Opcode::JR as u8);
+        bus.write(0x0001, 0xFC);
+
+        cpu.exec_instruction(&mut bus);
+
+        assert_eq!(0xFFFE, cpu.pc);
+
+        bus.write(0xFFFE, Opcode::JR as u8);
+        bus.write(0xFFFF, 0x01);
+
+        cpu.exec_instruction(&mut bus);
+
+        assert_eq!(0x0001, cpu.pc);
+    }
+
+    #[test]
+    fn call_pushes_return_address() {
+        let mut bus = Bus::flat();
+        let mut cpu = Sm83::new_dmg();
+
+        cpu.sp = 0xD000;
+        bus.write(cpu.pc, Opcode::CALL_a16 as u8);
+        bus.write16(cpu.pc.wrapping_add(1), 0xC000);
+
+        cpu.exec_instruction(&mut bus);
+
+        assert_eq!(0xC000, cpu.pc);
+        assert_eq!(0xCFFE, cpu.sp);
+        assert_eq!(0x01, bus.read(0xCFFF));
+        assert_eq!(0x03, bus.read(0xCFFE));
+    }
+
+    #[test]
+    fn call_wraps_sp() {
+        let mut bus = Bus::flat();
+        let mut cpu = Sm83::new_dmg();
+
+        cpu.sp = 0x0001;
+        bus.write(cpu.pc, Opcode::CALL_a16 as u8);
+        bus.write16(cpu.pc.wrapping_add(1), 0xC000);
+
+        cpu.exec_instruction(&mut bus);
+
+        assert_eq!(0xFFFF, cpu.sp);
+        assert_eq!(0x01, bus.read(0x0000));
+        assert_eq!(0x03, bus.read(0xFFFF));
+    }
+
+    #[test]
+    fn ret_cc_timing() {
+        let mut bus = Bus::flat();
+        let mut cpu = Sm83::new_dmg();
+
+        cpu.sp = 0xCFFE;
+        bus.write16(0xCFFE, 0xC000);
+        bus.write(cpu.pc, Opcode::RET_Z as u8);
+        bus.write(cpu.pc.wrapping_add(1), Opcode::RET_Z as u8);
+
+        // One m-cycle to fetch, then two to check the condition while fetching the next instruction.
+        cpu.registers.set_f(0x00);
+        for _ in 0..3 {
+            cpu.exec_m_cycle(&mut bus);
+        }
+
+        assert_eq!(AFTER_BOOT_PC + 2, cpu.pc);
+        assert_eq!(0xCFFE, cpu.sp);
+
+        cpu.registers.set_f(0x80);
+        cpu.exec_instruction(&mut bus);
+
+        assert_eq!(0xC000, cpu.pc);
+        assert_eq!(0xD000, cpu.sp);
+    }
+
+    #[test]
+    fn reti_sets_ime() {
+        let mut bus = Bus::flat();
+        let mut cpu = Sm83::new_dmg();
+
+        cpu.sp = 0xCFFE;
+        bus.write16(0xCFFE, 0xC000);
+        bus.write(cpu.pc, Opcode::RETI as u8);
+
+        cpu.exec_instruction(&mut bus);
+
+        assert!(cpu.ime);
+        assert_eq!(0xC000, cpu.pc);
+        assert_eq!(0xD000, cpu.sp);
+    }
+
+    #[test]
+    fn rst_vector_from_opcode() {
+        let mut bus = Bus::flat();
+        let mut cpu = Sm83::new_dmg();
+
+        cpu.sp = 0xD000;
+        bus.write(cpu.pc, Opcode::RST_28 as u8);
+
+        cpu.exec_instruction(&mut bus);
+
+        assert_eq!(0x0028, cpu.pc);
+        assert_eq!(0xCFFE, cpu.sp);
+        assert_eq!(AFTER_BOOT_PC + 1, bus.read16(0xCFFE));
+    }
+
+    #[test]
+    fn halt_waits_for_interrupt() {
+        let mut bus = Bus::flat();
+        let mut cpu = Sm83::new_dmg();
+
+        bus.write(IE, 0x04);
+        bus.write(IF, 0x00);
+        bus.write(cpu.pc, Opcode::HALT as u8);
+        bus.write(cpu.pc.wrapping_add(1), Opcode::INC_A as u8);
+        cpu.registers.set_a(0x00);
+
+        for _ in 0..10 {
+            cpu.exec_m_cycle(&mut bus);
+        }
+
+        assert!(cpu.halted);
+        assert_eq!(AFTER_BOOT_PC + 1, cpu.pc);
+
+        // With IME clear, a pending interrupt resumes execution without servicing it.
+        bus.write(IF, 0x04);
+        for _ in 0..2 {
+            cpu.exec_m_cycle(&mut bus);
+        }
+
+        assert!(!cpu.halted);
+        assert_eq!(0x01, cpu.registers.a());
+    }
+
+    #[test]
+    fn halt_bug_reads_next_byte_twice() {
+        let mut bus = Bus::flat();
+        let mut cpu = Sm83::new_dmg();
+
+        bus.write(IE, 0x01);
+        bus.write(IF, 0x01);
+        bus.write(cpu.pc, Opcode::HALT as u8);
+        bus.write(cpu.pc.wrapping_add(1), Opcode::INC_A as u8);
+        bus.write(cpu.pc.wrapping_add(2), Opcode::NOP as u8);
+        cpu.registers.set_a(0x00);
+
+        for _ in 0..4 {
+            cpu.exec_instruction(&mut bus);
+        }
+
+        assert!(!cpu.halted);
+        assert_eq!(0x02, cpu.registers.a());
+        assert_eq!(AFTER_BOOT_PC + 3, cpu.pc);
+    }
+
+    #[test]
+    fn ei_di_opens_no_window() {
+        let mut bus = Bus::flat();
+        let mut cpu = Sm83::new_dmg();
+
+        bus.write(IE, 0x01);
+        bus.write(IF, 0x01);
+        bus.write(cpu.pc, Opcode::EI as u8);
+        bus.write(cpu.pc.wrapping_add(1), Opcode::DI as u8);
+        bus.write(cpu.pc.wrapping_add(2), Opcode::NOP as u8);
+
+        cpu.exec_instruction(&mut bus);
+
+        assert!(!cpu.ime);
+
+        for _ in 0..2 {
+            cpu.exec_instruction(&mut bus);
+        }
+
+        assert!(!cpu.ime);
+        assert_eq!(AFTER_BOOT_PC + 3, cpu.pc);
+    }
+
+    #[test]
+    fn interrupt_dispatch_priority() {
+        let mut bus = Bus::flat();
+        let mut cpu = Sm83::new_dmg();
+
+        cpu.ime = true;
+        cpu.sp = 0xD000;
+        bus.write(IE, 0x1C);
+        bus.write(IF, 0x14);
+
+        cpu.fetch(&mut bus);
+
+        assert!(!cpu.ime);
+        assert_eq!(5, cpu.mcode_queue.len());
+
+        cpu.exec_instruction(&mut bus);
+
+        assert_eq!(0x0050, cpu.pc);
+        assert_eq!(0x10, bus.read(IF));
+        assert_eq!(AFTER_BOOT_PC, bus.read16(0xCFFE));
+    }
+
+    #[test]
+    fn interrupt_cancelled_by_ie_push() {
+        let mut bus = Bus::flat();
+        let mut cpu = Sm83::new_dmg();
+
+        cpu.ime = true;
+        cpu.sp = 0x0000;
+        cpu.pc = 0x0200;
+        bus.write(IE, 0x01);
+        bus.write(IF, 0x01);
+
+        // Pushing the high byte of PC writes `$02` to IE, disabling the pending VBlank interrupt.
+        cpu.exec_instruction(&mut bus);
+
+        assert_eq!(0x0000, cpu.pc);
+        assert_eq!(0x02, bus.read(IE));
+        assert_eq!(0x01, bus.read(IF));
+        assert_eq!(0xFFFE, cpu.sp);
+    }
+
+    #[test]
+    fn illegal_opcode_locks_cpu() {
+        let mut bus = Bus::flat();
+        let mut cpu = Sm83::new_dmg();
+
+        bus.write(cpu.pc, 0xD3);
+
+        cpu.exec_instruction(&mut bus);
+
+        assert!(cpu.is_locked());
+
+        for _ in 0..4 {
+            cpu.exec_m_cycle(&mut bus);
+        }
+
+        assert!(cpu.is_locked());
+        assert_eq!(AFTER_BOOT_PC + 1, cpu.pc);
+    }
+
+    #[test]
+    fn unimplemented_opcodes_lock_cpu() {
+        for opcode in Opcode::ALL.iter().filter(|opcode| !opcode.is_implemented()) {
+            let mut bus = Bus::flat();
+            let mut cpu = Sm83::new_dmg();
+
+            bus.write(cpu.pc, *opcode as u8);
+            cpu.exec_instruction(&mut bus);
+
+            assert!(!opcode.is_illegal());
+            assert!(cpu.is_locked(), "{opcode}");
+        }
+
+        assert!(Opcode::ALL.iter().any(|opcode| opcode.is_illegal()));
+        assert!(CbOpcode::ALL
+            .iter()
+            .all(|cb_opcode| cb_opcode.is_implemented()));
+        assert!(Opcode::CB_PREFIX.is_cb_prefix());
+    }
+
+    #[test]
+    fn exec_instruction_traces_bus() {
+        let mut bus = Bus::flat();
+        let mut cpu = Sm83::new_dmg();
+
+        cpu.sp = 0xD000;
+        cpu.registers.set_bc(0x1234);
+        bus.write(cpu.pc, Opcode::PUSH_BC as u8);
+
+        let trace = cpu.exec_instruction(&mut bus);
+
+        assert_eq!(
+            vec![
+                Some(BusActivity::read(AFTER_BOOT_PC, Opcode::PUSH_BC as u8)),
+                None,
+                Some(BusActivity::write(0xCFFF, 0x12)),
+                Some(BusActivity::write(0xCFFE, 0x34)),
+                None,
+            ],
+            trace
+        );
+    }
+
+    #[test]
+    fn only_nop_uses_nop_mcode() {
+        for opcode in &Opcode::ALL[1..] {
+            assert!(
+                !opcode.mcode().contains(&MCode::Nop),
+                "{opcode} pads with Nop instead of InternalDelay"
+            );
+        }
+
+        for cb_opcode in CbOpcode::ALL {
+            assert!(!cb_opcode.mcode().contains(&MCode::Nop));
+        }
+    }
+
+    #[test]
+    fn operand_reads_match_length() {
+        // The prefix counts towards the length of a CbOpcode, not the other way around
+        let reads = |mcode: &[MCode]| {
+            mcode
+                .iter()
+                .filter(|mcode| matches!(mcode, MCode::ReadImm(_)))
+                .count()
+        };
+
+        for opcode in Opcode::ALL {
+            let mcode = opcode.mcode();
+
+            if mcode != [MCode::Illegal] {
+                assert_eq!(opcode.length() as usize - 1, reads(mcode), "{opcode}");
+            }
+        }
+
+        for cb_opcode in CbOpcode::ALL {
+            assert_eq!(2, cb_opcode.length(), "{cb_opcode}");
+            assert_eq!(0, reads(cb_opcode.mcode()), "{cb_opcode}");
+        }
+    }
+
+    #[test]
+    fn m_cycles_match_execution() {
+        let mut bus = Bus::flat();
+        let mut cpu = Sm83::new_dmg();
+
+        let cycles = Opcode::CALL_NZ_a16.m_cycles();
+
+        // The trace starts with the fetch, which overlaps the m-code of the previous instruction
+        cpu.sp = 0xD000;
+        cpu.registers.set_f(0x80);
+        bus.write(cpu.pc, Opcode::CALL_NZ_a16 as u8);
+
+        assert_eq!(
+            cycles.not_taken() as usize,
+            cpu.exec_instruction(&mut bus).len() - 1
+        );
+
+        cpu.pc = AFTER_BOOT_PC;
+        cpu.registers.set_f(0x00);
+        cpu.mcode_queue.clear();
+
+        assert_eq!(
+            cycles.taken() as usize,
+            cpu.exec_instruction(&mut bus).len() - 1
+        );
+        assert_eq!(
+            Cycles::Conditional {
+                taken: 24,
+                not_taken: 12
+            },
+            cycles.to_t_cycles()
+        );
+
+        // The m-cycles of a CbOpcode include the one spent on the prefix
+        cpu.pc = 0xC000;
+        cpu.mcode_queue.clear();
+        bus.write(0xC000, Opcode::CB_PREFIX as u8);
+        bus.write(0xC001, CbOpcode::SET_0_PTR_HL as u8);
+
+        assert_eq!(Cycles::Fixed(4), CbOpcode::SET_0_PTR_HL.m_cycles());
+        assert_eq!(4, cpu.exec_instruction(&mut bus).len() - 1);
+    }
 }
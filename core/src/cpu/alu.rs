@@ -0,0 +1,704 @@
+use super::Sm83Registers;
+
+/// Add `rhs` and an optional carry to `lhs`, updating the flags and returning the result.
+///
+/// The `h` flag is set on a carry out of bit 3, and the `c` flag on a carry out of bit 7.
+pub fn add(registers: &mut Sm83Registers, lhs: u8, rhs: u8, carry: bool) -> u8 {
+    let carry = carry as u8;
+    let result = lhs.wrapping_add(rhs).wrapping_add(carry);
+
+    registers.set_z_flag(result == 0);
+    registers.set_n_flag(false);
+    registers.set_h_flag((lhs & 0x0F) + (rhs & 0x0F) + carry > 0x0F);
+    registers.set_c_flag(lhs as u16 + rhs as u16 + carry as u16 > 0xFF);
+
+    result
+}
+
+/// Subtract `rhs` and an optional carry from `lhs`, updating the flags and returning the result.
+///
+/// The `h` flag is set on a borrow from bit 4, and the `c` flag on a borrow past bit 7.
+pub fn sub(registers: &mut Sm83Registers, lhs: u8, rhs: u8, carry: bool) -> u8 {
+    let carry = carry as u8;
+    let result = lhs.wrapping_sub(rhs).wrapping_sub(carry);
+
+    registers.set_z_flag(result == 0);
+    registers.set_n_flag(true);
+    registers.set_h_flag((lhs & 0x0F) < (rhs & 0x0F) + carry);
+    registers.set_c_flag((lhs as u16) < rhs as u16 + carry as u16);
+
+    result
+}
+
+/// Bitwise AND `lhs` and `rhs`, updating the flags and returning the result.
+pub fn and(registers: &mut Sm83Registers, lhs: u8, rhs: u8) -> u8 {
+    let result = lhs & rhs;
+
+    registers.set_z_flag(result == 0);
+    registers.set_n_flag(false);
+    registers.set_h_flag(true);
+    registers.set_c_flag(false);
+
+    result
+}
+
+/// Bitwise exclusive OR `lhs` and `rhs`, updating the flags and returning the result.
+pub fn xor(registers: &mut Sm83Registers, lhs: u8, rhs: u8) -> u8 {
+    let result = lhs ^ rhs;
+
+    registers.set_z_flag(result == 0);
+    registers.set_n_flag(false);
+    registers.set_h_flag(false);
+    registers.set_c_flag(false);
+
+    result
+}
+
+/// Bitwise OR `lhs` and `rhs`, updating the flags and returning the result.
+pub fn or(registers: &mut Sm83Registers, lhs: u8, rhs: u8) -> u8 {
+    let result = lhs | rhs;
+
+    registers.set_z_flag(result == 0);
+    registers.set_n_flag(false);
+    registers.set_h_flag(false);
+    registers.set_c_flag(false);
+
+    result
+}
+
+/// Increment `value`, updating every flag except `c` and returning the result.
+pub fn inc(registers: &mut Sm83Registers, value: u8) -> u8 {
+    let result = value.wrapping_add(1);
+
+    registers.set_z_flag(result == 0);
+    registers.set_n_flag(false);
+    registers.set_h_flag(value & 0x0F == 0x0F);
+
+    result
+}
+
+/// Decrement `value`, updating every flag except `c` and returning the result.
+pub fn dec(registers: &mut Sm83Registers, value: u8) -> u8 {
+    let result = value.wrapping_sub(1);
+
+    registers.set_z_flag(result == 0);
+    registers.set_n_flag(true);
+    registers.set_h_flag(value & 0x0F == 0x00);
+
+    result
+}
+
+/// Decimal-adjust `value` using the `n`, `h`, and `c` flags left by the previous addition or
+/// subtraction, updating the flags and returning the result.
+pub fn daa(registers: &mut Sm83Registers, value: u8) -> u8 {
+    let mut adjust = 0;
+    let mut carry = registers.c_flag();
+
+    let result = if registers.n_flag() {
+        if registers.h_flag() {
+            adjust |= 0x06;
+        }
+
+        if carry {
+            adjust |= 0x60;
+        }
+
+        value.wrapping_sub(adjust)
+    } else {
+        if registers.h_flag() || value & 0x0F > 0x09 {
+            adjust |= 0x06;
+        }
+
+        if carry || value > 0x99 {
+            adjust |= 0x60;
+            carry = true;
+        }
+
+        value.wrapping_add(adjust)
+    };
+
+    registers.set_z_flag(result == 0);
+    registers.set_h_flag(false);
+    registers.set_c_flag(carry);
+
+    result
+}
+
+/// Complement every bit of `A`, setting the `n` and `h` flags.
+pub fn cpl(registers: &mut Sm83Registers) {
+    registers.set_a(!registers.a());
+    registers.set_n_flag(true);
+    registers.set_h_flag(true);
+}
+
+/// Set the `c` flag, clearing the `n` and `h` flags.
+pub fn scf(registers: &mut Sm83Registers) {
+    registers.set_n_flag(false);
+    registers.set_h_flag(false);
+    registers.set_c_flag(true);
+}
+
+/// Complement the `c` flag, clearing the `n` and `h` flags.
+pub fn ccf(registers: &mut Sm83Registers) {
+    registers.set_n_flag(false);
+    registers.set_h_flag(false);
+    registers.set_c_flag(!registers.c_flag());
+}
+
+/// Test `bit` of `value`, setting the `z` flag if it is clear. Preserves the `c` flag.
+pub fn bit(registers: &mut Sm83Registers, bit: u8, value: u8) {
+    registers.set_z_flag(value & (1 << bit) == 0);
+    registers.set_n_flag(false);
+    registers.set_h_flag(true);
+}
+
+/// Rotate `value` left, copying bit 7 into bit 0 and the `c` flag. Returns the result.
+pub fn rlc(registers: &mut Sm83Registers, value: u8) -> u8 {
+    let result = value.rotate_left(1);
+
+    set_shift_flags(registers, result, value & 0x80 != 0);
+
+    result
+}
+
+/// Rotate `value` right, copying bit 0 into bit 7 and the `c` flag. Returns the result.
+pub fn rrc(registers: &mut Sm83Registers, value: u8) -> u8 {
+    let result = value.rotate_right(1);
+
+    set_shift_flags(registers, result, value & 0x01 != 0);
+
+    result
+}
+
+/// Rotate `value` left through the `c` flag. Returns the result.
+pub fn rl(registers: &mut Sm83Registers, value: u8) -> u8 {
+    let result = value << 1 | registers.c_flag() as u8;
+
+    set_shift_flags(registers, result, value & 0x80 != 0);
+
+    result
+}
+
+/// Rotate `value` right through the `c` flag. Returns the result.
+pub fn rr(registers: &mut Sm83Registers, value: u8) -> u8 {
+    let result = value >> 1 | (registers.c_flag() as u8) << 7;
+
+    set_shift_flags(registers, result, value & 0x01 != 0);
+
+    result
+}
+
+/// Shift `value` left, shifting bit 7 into the `c` flag. Returns the result.
+pub fn sla(registers: &mut Sm83Registers, value: u8) -> u8 {
+    let result = value << 1;
+
+    set_shift_flags(registers, result, value & 0x80 != 0);
+
+    result
+}
+
+/// Shift `value` right, keeping bit 7 and shifting bit 0 into the `c` flag. Returns the result.
+pub fn sra(registers: &mut Sm83Registers, value: u8) -> u8 {
+    let result = value >> 1 | value & 0x80;
+
+    set_shift_flags(registers, result, value & 0x01 != 0);
+
+    result
+}
+
+/// Shift `value` right, clearing bit 7 and shifting bit 0 into the `c` flag. Returns the result.
+pub fn srl(registers: &mut Sm83Registers, value: u8) -> u8 {
+    let result = value >> 1;
+
+    set_shift_flags(registers, result, value & 0x01 != 0);
+
+    result
+}
+
+/// Swap the high and low nibbles of `value`. Returns the result.
+pub fn swap(registers: &mut Sm83Registers, value: u8) -> u8 {
+    let result = value.rotate_left(4);
+
+    set_shift_flags(registers, result, false);
+
+    result
+}
+
+/// Rotates and shifts all set `z` from the result, clear `n` and `h`, and set `c` to the bit that
+/// was shifted out.
+fn set_shift_flags(registers: &mut Sm83Registers, result: u8, carry: bool) {
+    registers.set_z_flag(result == 0);
+    registers.set_n_flag(false);
+    registers.set_h_flag(false);
+    registers.set_c_flag(carry);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Format the flags as `znhc` for readable assertions.
+    fn flags(registers: &Sm83Registers) -> String {
+        format!(
+            "{}{}{}{}",
+            registers.z_flag() as u8,
+            registers.n_flag() as u8,
+            registers.h_flag() as u8,
+            registers.c_flag() as u8,
+        )
+    }
+
+    #[test]
+    fn add_no_flags() {
+        let mut registers = Sm83Registers(0);
+
+        assert_eq!(0x35, add(&mut registers, 0x12, 0x23, false));
+        assert_eq!("0000", flags(&registers));
+    }
+
+    #[test]
+    fn add_clears_n() {
+        let mut registers = Sm83Registers(0);
+        registers.set_n_flag(true);
+
+        add(&mut registers, 0x01, 0x01, false);
+        assert_eq!("0000", flags(&registers));
+    }
+
+    #[test]
+    fn add_zero() {
+        let mut registers = Sm83Registers(0);
+
+        assert_eq!(0x00, add(&mut registers, 0x00, 0x00, false));
+        assert_eq!("1000", flags(&registers));
+    }
+
+    #[test]
+    fn add_half_carry() {
+        let mut registers = Sm83Registers(0);
+
+        assert_eq!(0x10, add(&mut registers, 0x0F, 0x01, false));
+        assert_eq!("0010", flags(&registers));
+    }
+
+    #[test]
+    fn add_carry() {
+        let mut registers = Sm83Registers(0);
+
+        assert_eq!(0x10, add(&mut registers, 0xF0, 0x20, false));
+        assert_eq!("0001", flags(&registers));
+    }
+
+    #[test]
+    fn add_carry_to_zero() {
+        let mut registers = Sm83Registers(0);
+
+        assert_eq!(0x00, add(&mut registers, 0xFF, 0x01, false));
+        assert_eq!("1011", flags(&registers));
+    }
+
+    #[test]
+    fn add_wraps_without_zero() {
+        let mut registers = Sm83Registers(0);
+
+        assert_eq!(0xFE, add(&mut registers, 0xFF, 0xFF, false));
+        assert_eq!("0011", flags(&registers));
+    }
+
+    #[test]
+    fn adc_half_carry_from_carry_in() {
+        let mut registers = Sm83Registers(0);
+
+        assert_eq!(0x10, add(&mut registers, 0x0F, 0x00, true));
+        assert_eq!("0010", flags(&registers));
+    }
+
+    #[test]
+    fn adc_carry_from_carry_in() {
+        let mut registers = Sm83Registers(0);
+
+        assert_eq!(0x00, add(&mut registers, 0xFF, 0x00, true));
+        assert_eq!("1011", flags(&registers));
+    }
+
+    #[test]
+    fn adc_carry_with_full_operand() {
+        let mut registers = Sm83Registers(0);
+
+        assert_eq!(0xFF, add(&mut registers, 0xFF, 0xFF, true));
+        assert_eq!("0011", flags(&registers));
+    }
+
+    #[test]
+    fn sub_no_borrow() {
+        let mut registers = Sm83Registers(0);
+
+        assert_eq!(0x11, sub(&mut registers, 0x34, 0x23, false));
+        assert_eq!("0100", flags(&registers));
+    }
+
+    #[test]
+    fn sub_zero() {
+        let mut registers = Sm83Registers(0);
+
+        assert_eq!(0x00, sub(&mut registers, 0x42, 0x42, false));
+        assert_eq!("1100", flags(&registers));
+    }
+
+    #[test]
+    fn sub_half_borrow() {
+        let mut registers = Sm83Registers(0);
+
+        assert_eq!(0x0F, sub(&mut registers, 0x10, 0x01, false));
+        assert_eq!("0110", flags(&registers));
+    }
+
+    #[test]
+    fn sub_borrow() {
+        let mut registers = Sm83Registers(0);
+
+        assert_eq!(0xF0, sub(&mut registers, 0x10, 0x20, false));
+        assert_eq!("0101", flags(&registers));
+    }
+
+    #[test]
+    fn sbc_half_borrow_from_carry_in() {
+        let mut registers = Sm83Registers(0);
+
+        assert_eq!(0x0F, sub(&mut registers, 0x10, 0x00, true));
+        assert_eq!("0110", flags(&registers));
+    }
+
+    #[test]
+    fn sbc_full_operand_with_carry_in() {
+        let mut registers = Sm83Registers(0);
+
+        assert_eq!(0x00, sub(&mut registers, 0x00, 0xFF, true));
+        assert_eq!("1111", flags(&registers));
+    }
+
+    #[test]
+    fn and_sets_h() {
+        let mut registers = Sm83Registers(0);
+        registers.set_c_flag(true);
+
+        assert_eq!(0x02, and(&mut registers, 0x0A, 0x06));
+        assert_eq!("0010", flags(&registers));
+    }
+
+    #[test]
+    fn and_zero() {
+        let mut registers = Sm83Registers(0);
+
+        assert_eq!(0x00, and(&mut registers, 0xF0, 0x0F));
+        assert_eq!("1010", flags(&registers));
+    }
+
+    #[test]
+    fn xor_clears_flags() {
+        let mut registers = Sm83Registers(0xF0);
+
+        assert_eq!(0x0C, xor(&mut registers, 0x0A, 0x06));
+        assert_eq!("0000", flags(&registers));
+    }
+
+    #[test]
+    fn xor_self_is_zero() {
+        let mut registers = Sm83Registers(0xF0);
+
+        assert_eq!(0x00, xor(&mut registers, 0x5A, 0x5A));
+        assert_eq!("1000", flags(&registers));
+    }
+
+    #[test]
+    fn or_clears_flags() {
+        let mut registers = Sm83Registers(0xF0);
+
+        assert_eq!(0x0E, or(&mut registers, 0x0A, 0x06));
+        assert_eq!("0000", flags(&registers));
+    }
+
+    #[test]
+    fn or_zero() {
+        let mut registers = Sm83Registers(0xF0);
+
+        assert_eq!(0x00, or(&mut registers, 0x00, 0x00));
+        assert_eq!("1000", flags(&registers));
+    }
+
+    #[test]
+    fn inc_half_carry() {
+        let mut registers = Sm83Registers(0);
+
+        assert_eq!(0x10, inc(&mut registers, 0x0F));
+        assert_eq!("0010", flags(&registers));
+    }
+
+    #[test]
+    fn inc_wraps_to_zero() {
+        let mut registers = Sm83Registers(0);
+
+        assert_eq!(0x00, inc(&mut registers, 0xFF));
+        assert_eq!("1010", flags(&registers));
+    }
+
+    #[test]
+    fn inc_preserves_c() {
+        let mut registers = Sm83Registers(0);
+
+        registers.set_c_flag(true);
+        inc(&mut registers, 0xFF);
+        assert!(registers.c_flag());
+
+        registers.set_c_flag(false);
+        inc(&mut registers, 0xFF);
+        assert!(!registers.c_flag());
+    }
+
+    #[test]
+    fn dec_half_borrow() {
+        let mut registers = Sm83Registers(0);
+
+        assert_eq!(0x0F, dec(&mut registers, 0x10));
+        assert_eq!("0110", flags(&registers));
+    }
+
+    #[test]
+    fn dec_to_zero() {
+        let mut registers = Sm83Registers(0);
+
+        assert_eq!(0x00, dec(&mut registers, 0x01));
+        assert_eq!("1100", flags(&registers));
+    }
+
+    #[test]
+    fn dec_preserves_c() {
+        let mut registers = Sm83Registers(0);
+
+        registers.set_c_flag(true);
+        dec(&mut registers, 0x00);
+        assert!(registers.c_flag());
+
+        registers.set_c_flag(false);
+        dec(&mut registers, 0x00);
+        assert!(!registers.c_flag());
+    }
+
+    /// A reference DAA, structured the way SameBoy computes it: adjusting in a wider integer and
+    /// taking the carry from bit 8. Returns `(result, flags)`.
+    fn daa_reference(a: u8, n: bool, h: bool, c: bool) -> (u8, u8) {
+        let mut result = a as i16;
+        let mut carry = c;
+
+        if n {
+            if h {
+                result = (result - 0x06) & 0xFF;
+            }
+
+            if c {
+                result -= 0x60;
+            }
+        } else {
+            if h || (result & 0x0F) > 0x09 {
+                result += 0x06;
+            }
+
+            if c || result > 0x9F {
+                result += 0x60;
+            }
+        }
+
+        if result & 0x100 == 0x100 {
+            carry = true;
+        }
+
+        let z = result & 0xFF == 0;
+        let flags = (z as u8) << 7 | (n as u8) << 6 | (carry as u8) << 4;
+
+        (result as u8, flags)
+    }
+
+    #[test]
+    fn daa_exhaustive() {
+        for a in 0..=0xFF {
+            for flags in 0..8 {
+                let (n, h, c) = (flags & 4 != 0, flags & 2 != 0, flags & 1 != 0);
+                let mut registers = Sm83Registers(0);
+
+                registers.set_n_flag(n);
+                registers.set_h_flag(h);
+                registers.set_c_flag(c);
+
+                let result = daa(&mut registers, a);
+                let expected = daa_reference(a, n, h, c);
+
+                assert_eq!(
+                    expected,
+                    (result, registers.f()),
+                    "DAA A:{a:02X} n:{} h:{} c:{}",
+                    n as u8,
+                    h as u8,
+                    c as u8,
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn daa_after_add() {
+        let mut registers = Sm83Registers(0);
+
+        let sum = add(&mut registers, 0x38, 0x45, false);
+        assert_eq!(0x83, daa(&mut registers, sum));
+        assert_eq!("0000", flags(&registers));
+
+        let sum = add(&mut registers, 0x99, 0x01, false);
+        assert_eq!(0x00, daa(&mut registers, sum));
+        assert_eq!("1001", flags(&registers));
+    }
+
+    #[test]
+    fn daa_after_sub() {
+        let mut registers = Sm83Registers(0);
+
+        let difference = sub(&mut registers, 0x42, 0x15, false);
+        assert_eq!(0x27, daa(&mut registers, difference));
+        assert_eq!("0100", flags(&registers));
+
+        let difference = sub(&mut registers, 0x10, 0x20, false);
+        assert_eq!(0x90, daa(&mut registers, difference));
+        assert_eq!("0101", flags(&registers));
+    }
+
+    #[test]
+    fn cpl_preserves_z_and_c() {
+        let mut registers = Sm83Registers(0);
+        registers.set_a(0x35);
+        registers.set_z_flag(true);
+        registers.set_c_flag(true);
+
+        cpl(&mut registers);
+        assert_eq!(0xCA, registers.a());
+        assert_eq!("1111", flags(&registers));
+    }
+
+    #[test]
+    fn scf_preserves_z() {
+        let mut registers = Sm83Registers(0xE0);
+
+        scf(&mut registers);
+        assert_eq!("1001", flags(&registers));
+    }
+
+    #[test]
+    fn ccf_flips_c_and_preserves_z() {
+        let mut registers = Sm83Registers(0xF0);
+
+        ccf(&mut registers);
+        assert_eq!("1000", flags(&registers));
+
+        ccf(&mut registers);
+        assert_eq!("1001", flags(&registers));
+
+        registers.set_z_flag(false);
+        ccf(&mut registers);
+        assert_eq!("0000", flags(&registers));
+    }
+
+    #[test]
+    fn rlc_copies_bit_7() {
+        let mut registers = Sm83Registers(0);
+
+        assert_eq!(0x0B, rlc(&mut registers, 0x85));
+        assert_eq!("0001", flags(&registers));
+    }
+
+    #[test]
+    fn rrc_copies_bit_0() {
+        let mut registers = Sm83Registers(0);
+
+        assert_eq!(0xC0, rrc(&mut registers, 0x81));
+        assert_eq!("0001", flags(&registers));
+    }
+
+    #[test]
+    fn rl_through_carry() {
+        let mut registers = Sm83Registers(0);
+        registers.set_c_flag(true);
+
+        assert_eq!(0x01, rl(&mut registers, 0x00));
+        assert_eq!("0000", flags(&registers));
+
+        assert_eq!(0x00, rl(&mut registers, 0x80));
+        assert_eq!("1001", flags(&registers));
+    }
+
+    #[test]
+    fn rr_through_carry() {
+        let mut registers = Sm83Registers(0);
+        registers.set_c_flag(true);
+
+        assert_eq!(0x80, rr(&mut registers, 0x00));
+        assert_eq!("0000", flags(&registers));
+
+        assert_eq!(0x00, rr(&mut registers, 0x01));
+        assert_eq!("1001", flags(&registers));
+    }
+
+    #[test]
+    fn sla_shifts_into_carry() {
+        let mut registers = Sm83Registers(0);
+
+        assert_eq!(0x00, sla(&mut registers, 0x80));
+        assert_eq!("1001", flags(&registers));
+
+        assert_eq!(0x02, sla(&mut registers, 0x81));
+        assert_eq!("0001", flags(&registers));
+    }
+
+    #[test]
+    fn sra_keeps_bit_7() {
+        let mut registers = Sm83Registers(0);
+
+        assert_eq!(0xC0, sra(&mut registers, 0x80));
+        assert_eq!("0000", flags(&registers));
+
+        assert_eq!(0xC0, sra(&mut registers, 0x81));
+        assert_eq!("0001", flags(&registers));
+    }
+
+    #[test]
+    fn srl_clears_bit_7() {
+        let mut registers = Sm83Registers(0);
+
+        assert_eq!(0x40, srl(&mut registers, 0x80));
+        assert_eq!("0000", flags(&registers));
+
+        assert_eq!(0x40, srl(&mut registers, 0x81));
+        assert_eq!("0001", flags(&registers));
+    }
+
+    #[test]
+    fn swap_nibbles() {
+        let mut registers = Sm83Registers(0);
+        registers.set_c_flag(true);
+
+        assert_eq!(0x21, swap(&mut registers, 0x12));
+        assert_eq!("0000", flags(&registers));
+
+        assert_eq!(0x00, swap(&mut registers, 0x00));
+        assert_eq!("1000", flags(&registers));
+    }
+
+    #[test]
+    fn bit_preserves_carry() {
+        let mut registers = Sm83Registers(0);
+        registers.set_c_flag(true);
+
+        bit(&mut registers, 7, 0x7F);
+        assert_eq!("1011", flags(&registers));
+
+        bit(&mut registers, 0, 0x01);
+        assert_eq!("0011", flags(&registers));
+    }
+}
@@ -0,0 +1,165 @@
+use std::fmt::Debug;
+
+use dotmatrix_opcodes::MCode;
+
+/// The longest instruction is 6 m-cycles, and a CB-prefixed instruction queues its m-code while
+/// the prefix is still executing, so 8 leaves some room to spare.
+const CAPACITY: usize = 8;
+
+/// A fixed-capacity ring buffer of [MCode]s waiting to be executed. Fetching pushes to this on
+/// every instruction, so it avoids the heap entirely.
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct MCodeQueue {
+    buffer: [MCode; CAPACITY],
+    head: usize,
+    len: usize,
+}
+
+impl MCodeQueue {
+    /// Create an empty [MCodeQueue].
+    pub const fn new() -> Self {
+        Self {
+            buffer: [MCode::Nop; CAPACITY],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// The number of m-codes in the queue.
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the queue has no m-codes left.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Add an m-code to the back of the queue.
+    pub fn push_back(&mut self, mcode: MCode) {
+        debug_assert!(self.len < CAPACITY, "MCodeQueue overflowed");
+
+        self.buffer[(self.head + self.len) % CAPACITY] = mcode;
+        self.len += 1;
+    }
+
+    /// Remove and return the m-code at the front of the queue.
+    pub fn pop_front(&mut self) -> Option<MCode> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let mcode = self.buffer[self.head];
+
+        self.head = (self.head + 1) % CAPACITY;
+        self.len -= 1;
+
+        Some(mcode)
+    }
+
+    /// Drop every m-code in the queue.
+    pub fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+    }
+
+    /// Iterate over the queued m-codes from front to back.
+    pub fn iter(&self) -> impl Iterator<Item = &MCode> {
+        (0..self.len).map(|i| &self.buffer[(self.head + i) % CAPACITY])
+    }
+}
+
+impl Default for MCodeQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Extend<MCode> for MCodeQueue {
+    fn extend<T: IntoIterator<Item = MCode>>(&mut self, iter: T) {
+        iter.into_iter().for_each(|mcode| self.push_back(mcode));
+    }
+}
+
+/// Only the queued m-codes are shown, front first.
+impl Debug for MCodeQueue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Only the queued m-codes are compared, not whatever is left over in the rest of the buffer.
+impl PartialEq for MCodeQueue {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl Eq for MCodeQueue {}
+
+#[cfg(test)]
+mod test {
+    use dotmatrix_opcodes::{R16, R8};
+
+    use super::*;
+
+    #[test]
+    fn fifo_order_wraps() {
+        let mut queue = MCodeQueue::new();
+
+        for _ in 0..3 {
+            queue.extend([MCode::Nop; 6]);
+            (0..6).for_each(|_| assert_eq!(Some(MCode::Nop), queue.pop_front()));
+        }
+
+        queue.extend([MCode::ReadImm(R8::Z), MCode::ReadImm(R8::W), MCode::Halt]);
+
+        assert_eq!(3, queue.len());
+        assert_eq!(Some(MCode::ReadImm(R8::Z)), queue.pop_front());
+        assert_eq!(Some(MCode::ReadImm(R8::W)), queue.pop_front());
+        assert_eq!(Some(MCode::Halt), queue.pop_front());
+        assert_eq!(None, queue.pop_front());
+    }
+
+    #[test]
+    fn eq_ignores_stale_entries() {
+        let mut lhs = MCodeQueue::new();
+        let mut rhs = MCodeQueue::new();
+
+        lhs.extend([MCode::Halt, MCode::Nop]);
+        lhs.pop_front();
+        rhs.push_back(MCode::Nop);
+
+        assert_eq!(lhs, rhs);
+
+        lhs.clear();
+
+        assert_eq!(MCodeQueue::new(), lhs);
+        assert_ne!(lhs, rhs);
+    }
+
+    #[test]
+    fn debug_lists_queued_mcode() {
+        let mut queue = MCodeQueue::new();
+
+        queue.extend([MCode::Halt, MCode::ReadImm(R8::Z), MCode::Jp(R16::WZ)]);
+        queue.pop_front();
+
+        assert_eq!("[ReadImm(Z), Jp(WZ)]", format!("{queue:?}"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trips() {
+        let mut queue = MCodeQueue::new();
+
+        queue.extend([MCode::Halt, MCode::ReadImm(R8::Z), MCode::Jp(R16::WZ)]);
+        queue.pop_front();
+
+        let json = serde_json::to_string(&queue).unwrap();
+        let queue: MCodeQueue = serde_json::from_str(&json).unwrap();
+
+        assert_eq!("[ReadImm(Z), Jp(WZ)]", format!("{queue:?}"));
+    }
+}
@@ -0,0 +1,222 @@
+//! Helpers for loading ROMs and battery saves straight from files, for frontends that don't need
+//! anything fancier.
+
+use std::fmt::Display;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::{DotMatrix, LoadError, SaveError};
+
+/// The largest ROM any supported cartridge can address, that of an MBC5. Anything bigger can't be
+/// a Game Boy ROM, so it's rejected before it's read into memory.
+pub const MAX_ROM_SIZE: u64 = 0x80_0000;
+
+/// A ROM or battery save that couldn't be loaded from or saved to the file at `path`.
+#[derive(Debug)]
+pub struct FileError {
+    pub path: PathBuf,
+    pub kind: FileErrorKind,
+}
+
+/// What went wrong with the file of a [FileError].
+#[derive(Debug)]
+pub enum FileErrorKind {
+    /// The file couldn't be read or written.
+    Io(io::Error),
+
+    /// The file is `len` bytes, more than [MAX_ROM_SIZE].
+    TooLarge { len: u64 },
+
+    /// The file isn't a ROM of a supported cartridge.
+    Load(LoadError),
+
+    /// The file isn't a battery save of the inserted cartridge, or it doesn't have a battery.
+    Save(SaveError),
+}
+
+impl FileError {
+    fn new(path: &Path, kind: FileErrorKind) -> Self {
+        Self {
+            path: path.to_owned(),
+            kind,
+        }
+    }
+}
+
+impl Display for FileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let path = self.path.display();
+
+        match &self.kind {
+            FileErrorKind::Io(error) => write!(f, "{path}: {error}"),
+            FileErrorKind::TooLarge { len } => write!(
+                f,
+                "{path}: file is {len} bytes, too large for a ROM of up to {MAX_ROM_SIZE} bytes"
+            ),
+            FileErrorKind::Load(error) => write!(f, "{path}: {error}"),
+            FileErrorKind::Save(error) => write!(f, "{path}: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for FileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            FileErrorKind::Io(error) => Some(error),
+            FileErrorKind::TooLarge { .. } => None,
+            FileErrorKind::Load(error) => Some(error),
+            FileErrorKind::Save(error) => Some(error),
+        }
+    }
+}
+
+/// Where the battery save of the ROM at `rom_path` goes by convention, next to it with a `.sav`
+/// extension, like most emulators.
+pub fn save_path_for(rom_path: impl AsRef<Path>) -> PathBuf {
+    rom_path.as_ref().with_extension("sav")
+}
+
+impl DotMatrix {
+    /// Insert a cartridge with the ROM read from `path`, like [DotMatrix::load].
+    pub fn load_rom_file(&mut self, path: impl AsRef<Path>) -> Result<(), FileError> {
+        let path = path.as_ref();
+        let io_error = |error| FileError::new(path, FileErrorKind::Io(error));
+
+        let len = std::fs::metadata(path).map_err(io_error)?.len();
+
+        if len > MAX_ROM_SIZE {
+            return Err(FileError::new(path, FileErrorKind::TooLarge { len }));
+        }
+
+        let rom = std::fs::read(path).map_err(io_error)?;
+
+        self.load(rom)
+            .map_err(|error| FileError::new(path, FileErrorKind::Load(error)))
+    }
+
+    /// Write the battery save of the inserted cartridge to `path`, see [DotMatrix::export_save].
+    pub fn save_battery_to(&self, path: impl AsRef<Path>) -> Result<(), FileError> {
+        let path = path.as_ref();
+
+        let save = self.export_save().ok_or_else(|| {
+            let error = match self.cartridge() {
+                Some(_) => SaveError::NoBattery,
+                None => SaveError::NoCartridge,
+            };
+
+            FileError::new(path, FileErrorKind::Save(error))
+        })?;
+
+        std::fs::write(path, save).map_err(|error| FileError::new(path, FileErrorKind::Io(error)))
+    }
+
+    /// Restore the battery of the inserted cartridge from the save at `path`, see
+    /// [DotMatrix::import_save].
+    pub fn load_battery_from(&mut self, path: impl AsRef<Path>) -> Result<(), FileError> {
+        let path = path.as_ref();
+        let save =
+            std::fs::read(path).map_err(|error| FileError::new(path, FileErrorKind::Io(error)))?;
+
+        self.import_save(&save)
+            .map_err(|error| FileError::new(path, FileErrorKind::Save(error)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A directory of its own for `test` to put files in, emptied first.
+    fn temp_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dotmatrix-{}-{test}", std::process::id()));
+
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// An MBC1+RAM+BATTERY ROM with 8 KiB of RAM.
+    fn battery_rom() -> Vec<u8> {
+        let mut rom = vec![0x00; 0x8000];
+
+        rom[0x0147] = 0x03;
+        rom[0x0149] = 0x02;
+        rom
+    }
+
+    #[test]
+    fn save_path_is_sibling() {
+        assert_eq!(
+            Path::new("roms/tetris.sav"),
+            save_path_for("roms/tetris.gb")
+        );
+        assert_eq!(Path::new("pokemon.sav"), save_path_for("pokemon"));
+    }
+
+    #[test]
+    fn battery_round_trip() {
+        let dir = temp_dir("battery_round_trip");
+        let rom_path = dir.join("game.gb");
+        std::fs::write(&rom_path, battery_rom()).unwrap();
+
+        let mut dmg = DotMatrix::new_dmg();
+        dmg.load_rom_file(&rom_path).unwrap();
+        dmg.bus.write(0x0000, 0x0A);
+        dmg.bus.write(0xA000, 0x42);
+
+        let save_path = save_path_for(&rom_path);
+        dmg.save_battery_to(&save_path).unwrap();
+        assert_eq!(0x2000, std::fs::metadata(&save_path).unwrap().len());
+
+        let mut dmg = DotMatrix::new_dmg();
+        dmg.load_rom_file(&rom_path).unwrap();
+        dmg.load_battery_from(&save_path).unwrap();
+        dmg.bus.write(0x0000, 0x0A);
+        assert_eq!(0x42, dmg.bus.read(0xA000));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn errors_include_path() {
+        let dir = temp_dir("errors_include_path");
+        let mut dmg = DotMatrix::new_dmg();
+
+        let missing = dir.join("missing.gb");
+        let error = dmg.load_rom_file(&missing).unwrap_err();
+        assert!(matches!(error.kind, FileErrorKind::Io(_)));
+        assert!(error.to_string().contains("missing.gb"), "{error}");
+
+        let huge = dir.join("huge.gb");
+        let file = std::fs::File::create(&huge).unwrap();
+        file.set_len(MAX_ROM_SIZE + 1).unwrap();
+
+        let error = dmg.load_rom_file(&huge).unwrap_err();
+        assert!(matches!(error.kind, FileErrorKind::TooLarge { .. }));
+        assert!(error.to_string().contains("huge.gb"), "{error}");
+
+        let short = dir.join("short.gb");
+        std::fs::write(&short, [0x00; 0x100]).unwrap();
+
+        let error = dmg.load_rom_file(&short).unwrap_err();
+        assert!(matches!(error.kind, FileErrorKind::Load(_)));
+
+        let error = dmg.save_battery_to(dir.join("game.sav")).unwrap_err();
+        assert!(matches!(
+            error.kind,
+            FileErrorKind::Save(SaveError::NoCartridge)
+        ));
+
+        let rom_only = dir.join("rom_only.gb");
+        std::fs::write(&rom_only, [0x00; 0x8000]).unwrap();
+        dmg.load_rom_file(&rom_only).unwrap();
+
+        let error = dmg.save_battery_to(dir.join("rom_only.sav")).unwrap_err();
+        assert!(matches!(
+            error.kind,
+            FileErrorKind::Save(SaveError::NoBattery)
+        ));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
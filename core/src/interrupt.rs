@@ -0,0 +1,67 @@
+/// The sources of interrupts, from highest priority to lowest. Each one has a bit in `IF`, which
+/// requests it, and in `IE`, which enables it.
+///
+/// See the [Pan Docs](https://gbdev.io/pandocs/Interrupt_Sources.html).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Interrupt {
+    /// The PPU entered VBlank.
+    VBlank,
+
+    /// One of the conditions selected in `STAT` was met.
+    Stat,
+
+    /// `TIMA` overflowed.
+    Timer,
+
+    /// A serial transfer finished.
+    Serial,
+
+    /// A button was pressed.
+    Joypad,
+}
+
+impl Interrupt {
+    /// Every interrupt, from highest priority to lowest.
+    pub const ALL: [Self; 5] = [
+        Self::VBlank,
+        Self::Stat,
+        Self::Timer,
+        Self::Serial,
+        Self::Joypad,
+    ];
+
+    /// The bit of `IF` and `IE` that belongs to this interrupt.
+    pub const fn mask(self) -> u8 {
+        1 << self as u8
+    }
+
+    /// The address the CPU calls to service this interrupt.
+    pub const fn vector(self) -> u16 {
+        0x0040 + 8 * self as u16
+    }
+
+    /// The highest priority interrupt set in `bits`, laid out like `IF` and `IE`.
+    pub fn highest(bits: u8) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|interrupt| bits & interrupt.mask() != 0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn priority_follows_bit_order() {
+        assert_eq!(None, Interrupt::highest(0x00));
+        assert_eq!(None, Interrupt::highest(0xE0));
+        assert_eq!(Some(Interrupt::VBlank), Interrupt::highest(0x1F));
+        assert_eq!(Some(Interrupt::Timer), Interrupt::highest(0x1C));
+        assert_eq!(Some(Interrupt::Joypad), Interrupt::highest(0xF0));
+
+        assert_eq!(0x04, Interrupt::Timer.mask());
+        assert_eq!(0x0050, Interrupt::Timer.vector());
+        assert_eq!(0x0060, Interrupt::Joypad.vector());
+    }
+}
@@ -1,17 +1,33 @@
 mod bus;
 mod cartridge;
 mod cpu;
+#[cfg(feature = "std")]
+mod file;
+mod interrupt;
+mod model;
+mod timer;
 
-use std::rc::Rc;
-
-pub use bus::Bus;
-use cartridge::Cartridge;
+pub use bus::{
+    AccessSource, Bus, BusAccess, BusActivity, BusDevice, BusState, DeviceId, IoRegister,
+    ReadOnlyError, ReadWrite, StateError, BOOT_ROM_SIZE, IE, IF, STATE_VERSION,
+};
+pub use cartridge::{
+    Cartridge, CartridgeState, CartridgeType, CgbSupport, Clock, HeaderWarning, Licensee,
+    LoadError, Mbc, SaveError, SystemClock,
+};
 use cpu::Sm83;
+#[cfg(feature = "std")]
+pub use file::{save_path_for, FileError, FileErrorKind, MAX_ROM_SIZE};
+pub use interrupt::Interrupt;
+pub use model::Model;
 
 pub struct DotMatrix {
     pub bus: Bus,
     pub cpu: Sm83,
-    pub cartridge: Option<Rc<Cartridge>>,
+
+    /// The number of t-cycles that have passed, at the speed the PPU and APU run at. In double
+    /// speed the CPU gets through two m-cycles for every 4 of these, rather than one.
+    pub t_cycles: u64,
 }
 
 impl DotMatrix {
@@ -20,7 +36,7 @@ impl DotMatrix {
         Self {
             bus: Bus::new_dmg(),
             cpu: Sm83::new_dmg(),
-            cartridge: None,
+            t_cycles: 0,
         }
     }
 
@@ -29,18 +45,385 @@ impl DotMatrix {
         Self {
             bus: Bus::flat(),
             cpu: Sm83::new_dmg(),
-            cartridge: None,
+            t_cycles: 0,
         }
     }
 
-    pub fn load(&mut self, rom: Box<[u8]>) {
-        self.cartridge = Some(Rc::new(Cartridge::new(rom)));
+    /// Insert a cartridge with the given ROM, mapping it into the address space of the [Bus]. Fails
+    /// without inserting anything if the header doesn't describe a supported cartridge, or doesn't
+    /// match the size of the ROM.
+    pub fn load(&mut self, rom: impl Into<Box<[u8]>>) -> Result<(), LoadError> {
+        self.insert(Cartridge::new(rom.into())?);
+
+        Ok(())
+    }
+
+    /// Insert a cartridge with the given ROM like [DotMatrix::load], whatever its header says. For
+    /// tests that only care about a few bytes of ROM.
+    pub fn load_unchecked(&mut self, rom: impl Into<Box<[u8]>>) {
+        self.insert(Cartridge::new_unchecked(rom.into()));
+    }
+
+    fn insert(&mut self, cartridge: Cartridge) {
+        self.bus.insert_cartridge(cartridge);
+    }
+
+    /// The cartridge inserted with [DotMatrix::load], if any.
+    pub fn cartridge(&self) -> Option<&Cartridge> {
+        self.bus.cartridge()
+    }
+
+    /// The cartridge inserted with [DotMatrix::load], if any.
+    pub fn cartridge_mut(&mut self) -> Option<&mut Cartridge> {
+        self.bus.cartridge_mut()
+    }
+
+    /// The title of the inserted cartridge, see [Cartridge::title].
+    pub fn rom_title(&self) -> Option<&str> {
+        self.cartridge().map(Cartridge::title)
+    }
+
+    /// What the battery of the inserted cartridge keeps around, as raw external RAM to write to a
+    /// `.sav` file. See [Cartridge::export_save].
+    pub fn export_save(&self) -> Option<Vec<u8>> {
+        self.cartridge()?.export_save()
     }
 
-    /// Execute until the end of the current CPU instruction. Fetches if queue is empty.
+    /// Restore the battery of the inserted cartridge from a `.sav` file, after loading its ROM.
+    /// See [Cartridge::import_save].
+    pub fn import_save(&mut self, save: &[u8]) -> Result<(), SaveError> {
+        self.cartridge_mut()
+            .ok_or(SaveError::NoCartridge)?
+            .import_save(save)
+    }
+
+    /// Run `boot_rom` on power on, mapping it over 0x0000–0x00FF until it unmaps itself. The CPU
+    /// starts from PC=0x0000 with every register zeroed, rather than the state the boot ROM leaves
+    /// behind.
+    ///
+    /// The boot ROM checks the cartridge header itself, so as on hardware, a cartridge with a bad
+    /// header checksum never boots. Without a boot ROM, it boots anyway, and
+    /// [Cartridge::header_warnings] is the only sign anything is wrong.
+    pub fn load_boot_rom(&mut self, boot_rom: &[u8; BOOT_ROM_SIZE]) {
+        self.bus.map_boot_rom(boot_rom);
+        self.cpu = Sm83::new_dmg_power_on();
+    }
+
+    /// Execute a single CPU m-cycle, returning what the CPU did on the bus.
+    pub fn exec_m_cycle(&mut self) -> Option<BusActivity> {
+        // Read before executing, a speed switch takes effect from the next m-cycle
+        let t_cycles = 4 / self.bus.speed_factor() as u64;
+        let activity = self.cpu.exec_m_cycle(&mut self.bus);
+
+        self.t_cycles += t_cycles;
+
+        activity
+    }
+
+    /// Execute until the end of the current CPU instruction. Fetches if queue is empty. Returns the
+    /// bus activity of each m-cycle.
     ///
     /// For testing purposes, specifically SingleStepTests.
-    pub fn exec_instruction(&mut self) {
-        self.cpu.exec_instruction(&mut self.bus);
+    pub fn exec_instruction(&mut self) -> Vec<Option<BusActivity>> {
+        let t_cycles = 4 / self.bus.speed_factor() as u64;
+        let trace = self.cpu.exec_instruction(&mut self.bus);
+
+        // A halted CPU still spends an m-cycle, even with nothing to trace
+        self.t_cycles += t_cycles * trace.len().max(1) as u64;
+
+        trace
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use dotmatrix_opcodes::Opcode;
+
+    use super::*;
+    use crate::bus::{DIV, IF, KEY1};
+
+    #[test]
+    fn pc_wraps_at_top_of_memory() {
+        let mut dmg = DotMatrix::new_with_flat_bus();
+
+        dmg.cpu.pc = 0xFFFF;
+        dmg.cpu.registers.set_a(0x00);
+        dmg.bus.write(0xFFFF, Opcode::INC_A as u8);
+
+        dmg.exec_instruction();
+
+        assert_eq!(0x0000, dmg.cpu.pc);
+        assert_eq!(0x01, dmg.cpu.registers.a());
+    }
+
+    #[test]
+    fn bus_16_bit_access_wraps() {
+        let mut dmg = DotMatrix::new_with_flat_bus();
+
+        dmg.bus.write16(0xFFFF, 0x1234);
+
+        assert_eq!(0x34, dmg.bus.read(0xFFFF));
+        assert_eq!(0x12, dmg.bus.read(0x0000));
+        assert_eq!(0x1234, dmg.bus.read16(0xFFFF));
+    }
+
+    #[test]
+    fn load_maps_cartridge() {
+        let mut dmg = DotMatrix::new_dmg();
+        let mut rom = vec![0x00; 0x8000];
+
+        // NOP; JP $0150
+        rom[0x0100..0x0104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+        dmg.load(rom).unwrap();
+
+        assert_eq!(Opcode::NOP as u8, dmg.bus.read(0x0100));
+        assert_eq!(Opcode::JP_a16 as u8, dmg.bus.read(0x0101));
+
+        dmg.exec_instruction();
+        dmg.exec_instruction();
+
+        assert_eq!(0x0150, dmg.cpu.pc);
+    }
+
+    #[test]
+    fn battery_save_round_trip() {
+        let mut rom = vec![0x00; 0x8000];
+
+        // MBC1+RAM+BATTERY with 8 KiB of RAM
+        rom[0x0147] = 0x03;
+        rom[0x0149] = 0x02;
+
+        let mut dmg = DotMatrix::new_dmg();
+        dmg.load(rom.clone()).unwrap();
+
+        dmg.bus.write(0x0000, 0x0A);
+        assert_eq!(Ok(()), dmg.bus.write_slice(0xA000, b"DOTMATRIX"));
+
+        let save = dmg.export_save().unwrap();
+        assert_eq!(0x2000, save.len());
+
+        let mut dmg = DotMatrix::new_dmg();
+        assert_eq!(Err(SaveError::NoCartridge), dmg.import_save(&save));
+
+        dmg.load(rom).unwrap();
+        assert_eq!(Ok(()), dmg.import_save(&save));
+
+        dmg.bus.write(0x0000, 0x0A);
+        assert_eq!(b"DOTMATRIX".to_vec(), dmg.bus.read_range(0xA000, 9));
+    }
+
+    #[test]
+    fn rom_writes_go_to_mbc() {
+        let mut dmg = DotMatrix::new_dmg();
+        let mut rom = vec![0x00; 0x8000];
+
+        rom[0x2000] = 0x42;
+        dmg.load(rom).unwrap();
+        dmg.bus.write(0x2000, 0x01);
+
+        let cartridge = dmg.cartridge().unwrap();
+
+        assert_eq!(0x42, dmg.bus.read(0x2000));
+        assert_eq!(Some(0x01), cartridge.mbc_register(0x2000));
+        assert_eq!(Some(0x00), cartridge.mbc_register(0x0000));
+    }
+
+    #[test]
+    fn boot_rom_hands_over_to_cartridge() {
+        let mut dmg = DotMatrix::new_dmg();
+        let mut boot_rom = [0x00; BOOT_ROM_SIZE];
+        let mut rom = vec![0x00; 0x8000];
+
+        // NOPs until the very end, where the boot ROM unmaps itself with `LD A, $01; LDH [$50], A`
+        boot_rom[0xFC..].copy_from_slice(&[0x3E, 0x01, 0xE0, 0x50]);
+        rom[0x00FC] = 0xFF;
+        dmg.load(rom).unwrap();
+        dmg.load_boot_rom(&boot_rom);
+
+        assert_eq!(0x0000, dmg.cpu.pc);
+        assert_eq!(0x00, dmg.cpu.registers.a());
+        assert_eq!(0x3E, dmg.bus.read(0x00FC));
+
+        while dmg.bus.boot_rom_mapped() {
+            dmg.exec_instruction();
+        }
+
+        assert_eq!(0x01, dmg.cpu.registers.a());
+        assert_eq!(0xFF, dmg.bus.read(0x00FC));
+        assert_eq!(0x0100, dmg.cpu.pc);
+    }
+
+    /// Boot a cartridge through a boot ROM that checks its header checksum like the DMG's does,
+    /// returning whether it made it to the cartridge.
+    fn boots_through_checksum(rom: Vec<u8>) -> bool {
+        let mut dmg = DotMatrix::new_dmg();
+        let mut boot_rom = [0x00; BOOT_ROM_SIZE];
+
+        // LD HL, $0134; LD B, $19; LD A, B
+        // .sum: ADD [HL]; INC L; DEC B; JR NZ, .sum
+        // ADD [HL]; .lock: JR NZ, .lock
+        boot_rom[..0x0E].copy_from_slice(&[
+            0x21, 0x34, 0x01, 0x06, 0x19, 0x78, 0x86, 0x2C, 0x05, 0x20, 0xFB, 0x86, 0x20, 0xFE,
+        ]);
+        boot_rom[0xFC..].copy_from_slice(&[0x3E, 0x01, 0xE0, 0x50]);
+        dmg.load(rom).unwrap();
+        dmg.load_boot_rom(&boot_rom);
+
+        for _ in 0..1_000 {
+            dmg.exec_instruction();
+        }
+
+        !dmg.bus.boot_rom_mapped()
+    }
+
+    #[test]
+    fn bad_header_checksum_locks_up_boot_rom() {
+        let mut rom = vec![0x00; 0x8000];
+
+        rom[0x0134..0x0138].copy_from_slice(b"TEST");
+        rom[0x014D] = 0xA7;
+
+        let cartridge = Cartridge::new(rom.clone().into()).unwrap();
+        assert!(cartridge.header_checksum_valid());
+        assert!(boots_through_checksum(rom.clone()));
+
+        rom[0x0134] = b'B';
+
+        // Without a boot ROM, nothing stops a bad header from running
+        let mut dmg = DotMatrix::new_dmg();
+        dmg.load(rom.clone()).unwrap();
+        dmg.exec_instruction();
+
+        let cartridge = dmg.cartridge().unwrap();
+        assert!(!cartridge.header_checksum_valid());
+        assert_eq!(0x0101, dmg.cpu.pc);
+
+        assert!(!boots_through_checksum(rom));
+    }
+
+    /// Run `program` from 0xC000 until the timer requests an interrupt. Returns the m-cycles and
+    /// t-cycles it took for the interrupt to arrive once the timer started, after `setup` returns
+    /// true.
+    fn time_timer_interrupt(
+        dmg: &mut DotMatrix,
+        program: &[u8],
+        setup: impl Fn(&DotMatrix) -> bool,
+    ) -> (usize, u64) {
+        dmg.cpu.pc = 0xC000;
+        dmg.bus.write_slice(0xC000, program).unwrap();
+
+        while !setup(dmg) {
+            dmg.exec_m_cycle();
+        }
+
+        dmg.bus.write(IF, 0x00);
+        dmg.bus.write(DIV, 0x00);
+        dmg.bus.write(0xFF05, 0xF0);
+        dmg.bus.write(0xFF07, 0x05);
+
+        let start = dmg.t_cycles;
+        let mut m_cycles = 0;
+
+        while dmg.bus.read(IF) & 0x04 == 0 {
+            dmg.exec_m_cycle();
+            m_cycles += 1;
+        }
+
+        (m_cycles, dmg.t_cycles - start)
+    }
+
+    #[test]
+    fn stop_switches_speed() {
+        let mut normal = DotMatrix::new_dmg();
+        let mut double = DotMatrix {
+            bus: Bus::new(Model::Cgb),
+            ..DotMatrix::new_dmg()
+        };
+
+        // JR -2
+        let (normal_m_cycles, normal_t_cycles) =
+            time_timer_interrupt(&mut normal, &[0x18, 0xFE], |_| true);
+
+        assert_eq!(0x7E, double.bus.read(KEY1));
+
+        // LD A, 0x01; LDH (KEY1), A; STOP; JR -2
+        let (double_m_cycles, double_t_cycles) = time_timer_interrupt(
+            &mut double,
+            &[0x3E, 0x01, 0xE0, 0x4D, 0x10, 0x18, 0xFE],
+            |dmg| dmg.bus.double_speed(),
+        );
+
+        assert_eq!(0xFE, double.bus.read(KEY1));
+        assert!(!double.cpu.halted);
+
+        // The timer keeps pace with the CPU, so it takes just as many m-cycles in half the time
+        assert_eq!(normal_m_cycles, double_m_cycles);
+        assert_eq!(normal_t_cycles, double_t_cycles * 2);
+    }
+
+    #[test]
+    fn stop_without_switch_waits() {
+        let mut dmg = DotMatrix {
+            bus: Bus::new(Model::Cgb),
+            ..DotMatrix::new_dmg()
+        };
+
+        // Nothing can wake the CPU back up
+        dmg.cpu.pc = 0xC000;
+        dmg.bus.write(0xC000, 0x10);
+
+        for _ in 0..4 {
+            dmg.exec_m_cycle();
+        }
+
+        assert!(dmg.cpu.halted);
+        assert!(!dmg.bus.double_speed());
+    }
+
+    #[test]
+    fn watch_sees_writes() {
+        let mut dmg = DotMatrix::new_dmg();
+        let writes = Arc::new(Mutex::new(Vec::new()));
+
+        let watched = Arc::clone(&writes);
+        dmg.bus.set_watch(move |access| {
+            if access.addr == 0xFF41 && access.kind == ReadWrite::Write {
+                watched.lock().unwrap().push(access);
+            }
+        });
+
+        // LD A, $48; LDH [$41], A
+        dmg.cpu.pc = 0xC000;
+        dmg.bus
+            .write_slice(
+                0xC000,
+                &[Opcode::LD_A_n8 as u8, 0x48, Opcode::LDH_a8_A as u8, 0x41],
+            )
+            .unwrap();
+
+        dmg.exec_instruction();
+        dmg.exec_instruction();
+
+        // Peeking is invisible to the hook
+        dmg.bus.peek(0xFF41);
+
+        let expected = BusAccess {
+            addr: 0xFF41,
+            value: 0x48,
+            kind: ReadWrite::Write,
+            source: AccessSource::Cpu,
+        };
+
+        assert_eq!(vec![expected], *writes.lock().unwrap());
+    }
+
+    #[test]
+    fn is_send() {
+        // A frontend can run the emulator on a thread of its own
+        fn assert_send<T: Send>() {}
+
+        assert_send::<DotMatrix>();
     }
 }
@@ -0,0 +1,9 @@
+/// The hardware models of the Game Boy family, for the places their behavior differs.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Model {
+    /// The original Game Boy.
+    Dmg,
+
+    /// The Game Boy Color.
+    Cgb,
+}
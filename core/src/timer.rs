@@ -0,0 +1,232 @@
+use crate::bus::BusDevice;
+use crate::{Interrupt, StateError};
+
+/// The timer at 0xFF04–0xFF07, driven by a 16-bit counter that counts t-cycles. `DIV` is the upper
+/// byte of the counter, and `TIMA` counts falling edges of the counter bit selected by `TAC`.
+///
+/// See the [Pan Docs](https://gbdev.io/pandocs/Timer_Obscure_Behaviour.html).
+pub struct Timer {
+    /// The system counter, incremented by 4 each m-cycle.
+    counter: u16,
+    tima: u8,
+    tma: u8,
+    tac: u8,
+
+    /// Whether `TIMA` overflowed last m-cycle. It reads 0x00 for the m-cycle after overflowing,
+    /// before being reloaded from `TMA` and requesting an interrupt.
+    overflowed: bool,
+}
+
+impl Timer {
+    /// A [Timer] as the DMG boot ROM leaves it.
+    pub const fn new_dmg() -> Self {
+        Self {
+            counter: 0xABCC,
+            tima: 0x00,
+            tma: 0x00,
+            tac: 0xF8,
+            overflowed: false,
+        }
+    }
+
+    /// The bit of the counter whose falling edge increments `TIMA`, ANDed with the enable bit.
+    const fn signal(&self) -> bool {
+        let bit = match self.tac & 0x03 {
+            0b00 => 9,
+            0b01 => 3,
+            0b10 => 5,
+            _ => 7,
+        };
+
+        self.tac & 0x04 != 0 && self.counter & (1 << bit) != 0
+    }
+
+    /// Change the counter or `TAC` with `update`, incrementing `TIMA` if that makes the signal
+    /// fall. This is why resetting `DIV` or disabling the timer can increment `TIMA`.
+    fn update(&mut self, update: impl FnOnce(&mut Self)) {
+        let before = self.signal();
+
+        update(self);
+
+        if before && !self.signal() {
+            let (tima, overflowed) = self.tima.overflowing_add(1);
+
+            self.tima = tima;
+            self.overflowed = overflowed;
+        }
+    }
+}
+
+impl BusDevice for Timer {
+    fn read(&self, addr: u16) -> u8 {
+        match addr & 0xFF {
+            0x04 => (self.counter >> 8) as u8,
+            0x05 => self.tima,
+            0x06 => self.tma,
+            0x07 => self.tac | 0xF8,
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        match addr & 0xFF {
+            0x04 => self.update(|timer| timer.counter = 0),
+            0x05 => {
+                // Writing TIMA in the m-cycle after it overflowed cancels the reload and interrupt
+                self.tima = value;
+                self.overflowed = false;
+            }
+            0x06 => self.tma = value,
+            0x07 => self.update(|timer| timer.tac = value | 0xF8),
+            _ => (),
+        }
+    }
+
+    fn tick(&mut self) -> u8 {
+        let mut requested = 0x00;
+
+        if self.overflowed {
+            self.overflowed = false;
+            self.tima = self.tma;
+            requested |= Interrupt::Timer.mask();
+        }
+
+        self.update(|timer| timer.counter = timer.counter.wrapping_add(4));
+
+        requested
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        let [low, high] = self.counter.to_le_bytes();
+
+        vec![
+            low,
+            high,
+            self.tima,
+            self.tma,
+            self.tac,
+            self.overflowed as u8,
+        ]
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        let &[low, high, tima, tma, tac, overflowed] = state else {
+            return Err(StateError::Layout);
+        };
+
+        self.counter = u16::from_le_bytes([low, high]);
+        self.tima = tima;
+        self.tma = tma;
+        self.tac = tac;
+        self.overflowed = overflowed != 0;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn div_counts_up_every_64_m_cycles() {
+        let mut timer = Timer::new_dmg();
+
+        timer.write(0xFF04, 0x12);
+        assert_eq!(0x00, timer.read(0xFF04));
+
+        for _ in 0..64 {
+            timer.tick();
+        }
+
+        assert_eq!(0x01, timer.read(0xFF04));
+    }
+
+    #[test]
+    fn tac_unused_bits_read_1() {
+        let mut timer = Timer::new_dmg();
+
+        timer.write(0xFF07, 0x00);
+        assert_eq!(0xF8, timer.read(0xFF07));
+
+        timer.write(0xFF07, 0xFF);
+        assert_eq!(0xFF, timer.read(0xFF07));
+    }
+
+    #[test]
+    fn tima_overflow_reloads_and_interrupts() {
+        let mut timer = Timer::new_dmg();
+
+        timer.write(0xFF04, 0x00);
+        timer.write(0xFF06, 0xAB);
+        timer.write(0xFF05, 0xFF);
+        // Enabled, incrementing every 4 m-cycles
+        timer.write(0xFF07, 0x05);
+
+        let requested: Vec<u8> = (0..5).map(|_| timer.tick()).collect();
+
+        // TIMA reads 0x00 for an m-cycle before being reloaded and requesting the interrupt
+        assert_eq!(
+            vec![0x00, 0x00, 0x00, 0x00, Interrupt::Timer.mask()],
+            requested
+        );
+        assert_eq!(0xAB, timer.read(0xFF05));
+    }
+
+    #[test]
+    fn writing_tima_cancels_reload() {
+        let mut timer = Timer::new_dmg();
+
+        timer.write(0xFF04, 0x00);
+        timer.write(0xFF05, 0xFF);
+        timer.write(0xFF07, 0x05);
+
+        for _ in 0..4 {
+            timer.tick();
+        }
+
+        timer.write(0xFF05, 0x42);
+
+        assert_eq!(0x00, timer.tick());
+        assert_eq!(0x42, timer.read(0xFF05));
+    }
+
+    #[test]
+    fn state_round_trips() {
+        let mut timer = Timer::new_dmg();
+
+        timer.write(0xFF05, 0xFF);
+        timer.write(0xFF06, 0x12);
+        timer.write(0xFF07, 0x05);
+
+        for _ in 0..3 {
+            timer.tick();
+        }
+
+        let mut restored = Timer::new_dmg();
+        restored.load_state(&timer.save_state()).unwrap();
+
+        for _ in 0..0x100 {
+            assert_eq!(timer.tick(), restored.tick());
+            assert_eq!(timer.read(0xFF04), restored.read(0xFF04));
+            assert_eq!(timer.read(0xFF05), restored.read(0xFF05));
+        }
+
+        assert_eq!(Err(StateError::Layout), restored.load_state(&[0x00]));
+    }
+
+    #[test]
+    fn resetting_div_can_increment_tima() {
+        let mut timer = Timer::new_dmg();
+
+        timer.write(0xFF04, 0x00);
+        timer.write(0xFF07, 0x05);
+
+        // Bit 3 of the counter is set after 2 m-cycles, so resetting it is a falling edge
+        timer.tick();
+        timer.tick();
+        timer.write(0xFF04, 0x00);
+
+        assert_eq!(0x01, timer.read(0xFF05));
+    }
+}
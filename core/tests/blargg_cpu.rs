@@ -5,16 +5,119 @@ fn blargg_cpu_01_special() {
     let mut dmg = DotMatrix::new_dmg();
     let rom = include_bytes!("../../test_data/blargg/cpu_instrs/individual/01-special.gb");
 
-    dmg.load(rom.as_slice().into());
+    dmg.load(rom.as_slice()).unwrap();
 
     loop {
         dmg.exec_instruction();
 
+        assert!(
+            !dmg.cpu.is_locked(),
+            "CPU locked at PC={:04X} ({})",
+            dmg.cpu.pc.wrapping_sub(1),
+            dmg.cpu.ir
+        );
+
         // This is the address of the final instruction of the test ROM.
         // I expect to replace this with a run condition system.
         let self_loop_addr: u16 = 0xFE_18;
-        if self_loop_addr == dmg.bus.read16(dmg.cpu.pc) {
+        if self_loop_addr == dmg.bus.peek16(dmg.cpu.pc) {
             break;
         }
     }
 }
+
+#[test]
+fn blargg_cpu_01_special_save_state() {
+    let rom = include_bytes!("../../test_data/blargg/cpu_instrs/individual/01-special.gb");
+    let mut dmg = DotMatrix::new_dmg();
+
+    dmg.load(rom.as_slice()).unwrap();
+
+    for _ in 0..5_000 {
+        dmg.exec_instruction();
+    }
+
+    // Restore into a fresh system, with the same cartridge inserted
+    let mut restored = DotMatrix::new_dmg();
+
+    restored.load(rom.as_slice()).unwrap();
+    restored.bus.load_state(&dmg.bus.save_state()).unwrap();
+    restored.cpu = dmg.cpu.clone();
+
+    for _ in 0..5_000 {
+        assert_eq!(dmg.exec_instruction(), restored.exec_instruction());
+        assert_eq!(dmg.cpu, restored.cpu);
+    }
+
+    assert_eq!(dmg.bus.save_state(), restored.bus.save_state());
+}
+
+#[test]
+fn blargg_cpu_01_special_header() {
+    let rom = include_bytes!("../../test_data/blargg/cpu_instrs/individual/01-special.gb");
+    let mut dmg = DotMatrix::new_dmg();
+
+    dmg.load(rom.as_slice()).unwrap();
+
+    let cartridge = dmg.cartridge().unwrap();
+    assert!(cartridge.logo_valid());
+    assert!(cartridge.header_checksum_valid());
+    assert!(cartridge.header_warnings().is_empty());
+
+    // A single flipped bit in the title breaks the header checksum, but without a boot ROM to
+    // check it the ROM still runs
+    let mut corrupt = rom.to_vec();
+    corrupt[0x0134] ^= 0x01;
+
+    let mut dmg = DotMatrix::new_dmg();
+    dmg.load(corrupt).unwrap();
+
+    let cartridge = dmg.cartridge().unwrap();
+    assert!(!cartridge.header_checksum_valid());
+    assert!(!cartridge.global_checksum_valid());
+
+    for _ in 0..1_000 {
+        dmg.exec_instruction();
+        assert!(!dmg.cpu.is_locked());
+    }
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn blargg_cpu_01_special_from_file() {
+    let path = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../test_data/blargg/cpu_instrs/individual/01-special.gb"
+    );
+    let rom = include_bytes!("../../test_data/blargg/cpu_instrs/individual/01-special.gb");
+
+    let mut from_file = DotMatrix::new_dmg();
+    from_file.load_rom_file(path).unwrap();
+
+    let mut from_memory = DotMatrix::new_dmg();
+    from_memory.load(rom.as_slice()).unwrap();
+
+    assert_eq!(
+        from_memory.bus.read_range(0x0000, 0x8000),
+        from_file.bus.read_range(0x0000, 0x8000)
+    );
+
+    // A ROM without a battery has nothing to save
+    let save_path =
+        std::env::temp_dir().join(format!("dotmatrix-{}-01-special.sav", std::process::id()));
+    assert!(from_file.save_battery_to(&save_path).is_err());
+    assert!(!save_path.exists());
+}
+
+#[test]
+fn blargg_cpu_01_special_metadata() {
+    let rom = include_bytes!("../../test_data/blargg/cpu_instrs/individual/01-special.gb");
+    let mut dmg = DotMatrix::new_dmg();
+
+    assert_eq!(None, dmg.rom_title());
+
+    dmg.load(rom.as_slice()).unwrap();
+
+    assert_eq!(Some("CPU_INSTRS"), dmg.rom_title());
+    assert!(!dmg.cartridge().unwrap().sgb_support());
+}
@@ -0,0 +1,81 @@
+use std::fs;
+
+use dotmatrix::{DotMatrix, IoRegister};
+use dotmatrix_opcodes::Opcode;
+
+/// Where the ROMs end up after running `make` in the Mooneye Test Suite submodule.
+const ROMS: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../test_data/mooneye/build/emulator-only/mbc1"
+);
+
+/// The address of LY, the line the PPU is drawing.
+const LY: u16 = 0xFF44;
+
+/// Run one of Mooneye's MBC1 test ROMs until it finishes with `LD B, B`, which passing tests do
+/// with the Fibonacci numbers in BCDEHL, and failing tests with 0x42 in each. Skipped if the ROM
+/// hasn't been built.
+fn mooneye_mbc1(name: &str) {
+    let path = format!("{ROMS}/{name}.gb");
+    let Ok(rom) = fs::read(&path) else {
+        eprintln!("skipping {name}, {path} hasn't been built");
+        return;
+    };
+    let mut dmg = DotMatrix::new_dmg();
+
+    dmg.load(rom).unwrap();
+
+    // Every test waits for VBlank before turning off the LCD, which won't come until there's a PPU
+    // to advance LY, so hold it on the first line of VBlank
+    dmg.bus.map_io(LY, IoRegister::masked(0xFF, 0x00, 0x90));
+
+    for _ in 0..10_000_000 {
+        if dmg.bus.peek(dmg.cpu.pc) == Opcode::LD_B_B as u8 {
+            let registers = &dmg.cpu.registers;
+            let bcdehl = [
+                registers.b(),
+                registers.c(),
+                registers.d(),
+                registers.e(),
+                registers.h(),
+                registers.l(),
+            ];
+
+            assert_eq!([3, 5, 8, 13, 21, 34], bcdehl, "{name} failed");
+            return;
+        }
+
+        dmg.exec_instruction();
+        assert!(!dmg.cpu.is_locked(), "CPU locked at PC={:04X}", dmg.cpu.pc);
+    }
+
+    panic!("{name} never finished");
+}
+
+/// A test for each ROM in the MBC1 directory of the Mooneye Test Suite.
+macro_rules! mooneye_mbc1 {
+    ($($test:ident: $name:literal,)*) => {
+        $(
+            #[test]
+            fn $test() {
+                mooneye_mbc1($name);
+            }
+        )*
+    };
+}
+
+mooneye_mbc1! {
+    mooneye_mbc1_bits_bank1: "bits_bank1",
+    mooneye_mbc1_bits_bank2: "bits_bank2",
+    mooneye_mbc1_bits_mode: "bits_mode",
+    mooneye_mbc1_bits_ramg: "bits_ramg",
+    mooneye_mbc1_ram_64kb: "ram_64kb",
+    mooneye_mbc1_ram_256kb: "ram_256kb",
+    mooneye_mbc1_rom_512kb: "rom_512kb",
+    mooneye_mbc1_rom_1mb: "rom_1Mb",
+    mooneye_mbc1_rom_2mb: "rom_2Mb",
+    mooneye_mbc1_rom_4mb: "rom_4Mb",
+    mooneye_mbc1_rom_8mb: "rom_8Mb",
+    mooneye_mbc1_rom_16mb: "rom_16Mb",
+    mooneye_mbc1_multicart_rom_8mb: "multicart_rom_8Mb",
+}
@@ -4,9 +4,10 @@
 use std::{fmt::Debug, fs, path::Path};
 
 use serde::{Deserialize, Serialize};
-use serde_with::serde_as;
+use serde_with::{serde_as, BoolFromInt};
 
-use dotmatrix::DotMatrix;
+use dotmatrix::{BusActivity, DotMatrix, IE};
+use dotmatrix_opcodes::{CbOpcode, FlagEffects, Opcode};
 
 /// Generate separate test fns for each SingleStepTest in the JSON data, so that each test result
 /// shows up separately when running tests via cargo.
@@ -27,7 +28,68 @@ macro_rules! single_step_test_opcodes {
 
 // Invoke the above macro with the implemented opcodes.
 single_step_test_opcodes! {
-    "00"
+    "00", "01", "02", "03", "04", "05", "06", "07",
+    "08", "0a", "0b", "0c", "0d", "0e", "0f", "11",
+    "12", "13", "14", "15", "16", "17", "18", "1a",
+    "1b", "1c", "1d", "1e", "1f", "20", "21", "22",
+    "23", "24", "25", "26", "27", "28", "2a", "2b",
+    "2c", "2d", "2e", "2f", "30", "31", "32", "33",
+    "34", "35", "36", "37", "38", "3a", "3b", "3c",
+    "3d", "3e", "3f", "40", "41", "42", "43", "44",
+    "45", "46", "47", "48", "49", "4a", "4b", "4c",
+    "4d", "4e", "4f", "50", "51", "52", "53", "54",
+    "55", "56", "57", "58", "59", "5a", "5b", "5c",
+    "5d", "5e", "5f", "60", "61", "62", "63", "64",
+    "65", "66", "67", "68", "69", "6a", "6b", "6c",
+    "6d", "6e", "6f", "70", "71", "72", "73", "74",
+    "75", "77", "78", "79", "7a", "7b", "7c", "7d",
+    "7e", "7f", "80", "81", "82", "83", "84", "85",
+    "86", "87", "88", "89", "8a", "8b", "8c", "8d",
+    "8e", "8f", "90", "91", "92", "93", "94", "95",
+    "96", "97", "98", "99", "9a", "9b", "9c", "9d",
+    "9e", "9f", "a0", "a1", "a2", "a3", "a4", "a5",
+    "a6", "a7", "a8", "a9", "aa", "ab", "ac", "ad",
+    "ae", "af", "b0", "b1", "b2", "b3", "b4", "b5",
+    "b6", "b7", "b8", "b9", "ba", "bb", "bc", "bd",
+    "be", "bf", "c0", "c1", "c2", "c3", "c4", "c5",
+    "c6", "c7", "c8", "c9", "ca", "cc", "cd", "ce",
+    "cf", "d0", "d1", "d2", "d4", "d5", "d6", "d7",
+    "d8", "d9", "da", "dc", "de", "df", "e0", "e1",
+    "e2", "e5", "e6", "e7", "e9", "ea", "ee", "ef",
+    "f0", "f1", "f2", "f3", "f5", "f6", "f7", "fa",
+    "fe", "ff",
+    "cb_00", "cb_01", "cb_02", "cb_03", "cb_04", "cb_05", "cb_06", "cb_07",
+    "cb_08", "cb_09", "cb_0a", "cb_0b", "cb_0c", "cb_0d", "cb_0e", "cb_0f",
+    "cb_10", "cb_11", "cb_12", "cb_13", "cb_14", "cb_15", "cb_16", "cb_17",
+    "cb_18", "cb_19", "cb_1a", "cb_1b", "cb_1c", "cb_1d", "cb_1e", "cb_1f",
+    "cb_20", "cb_21", "cb_22", "cb_23", "cb_24", "cb_25", "cb_26", "cb_27",
+    "cb_28", "cb_29", "cb_2a", "cb_2b", "cb_2c", "cb_2d", "cb_2e", "cb_2f",
+    "cb_30", "cb_31", "cb_32", "cb_33", "cb_34", "cb_35", "cb_36", "cb_37",
+    "cb_38", "cb_39", "cb_3a", "cb_3b", "cb_3c", "cb_3d", "cb_3e", "cb_3f",
+    "cb_40", "cb_41", "cb_42", "cb_43", "cb_44", "cb_45", "cb_46", "cb_47",
+    "cb_48", "cb_49", "cb_4a", "cb_4b", "cb_4c", "cb_4d", "cb_4e", "cb_4f",
+    "cb_50", "cb_51", "cb_52", "cb_53", "cb_54", "cb_55", "cb_56", "cb_57",
+    "cb_58", "cb_59", "cb_5a", "cb_5b", "cb_5c", "cb_5d", "cb_5e", "cb_5f",
+    "cb_60", "cb_61", "cb_62", "cb_63", "cb_64", "cb_65", "cb_66", "cb_67",
+    "cb_68", "cb_69", "cb_6a", "cb_6b", "cb_6c", "cb_6d", "cb_6e", "cb_6f",
+    "cb_70", "cb_71", "cb_72", "cb_73", "cb_74", "cb_75", "cb_76", "cb_77",
+    "cb_78", "cb_79", "cb_7a", "cb_7b", "cb_7c", "cb_7d", "cb_7e", "cb_7f",
+    "cb_80", "cb_81", "cb_82", "cb_83", "cb_84", "cb_85", "cb_86", "cb_87",
+    "cb_88", "cb_89", "cb_8a", "cb_8b", "cb_8c", "cb_8d", "cb_8e", "cb_8f",
+    "cb_90", "cb_91", "cb_92", "cb_93", "cb_94", "cb_95", "cb_96", "cb_97",
+    "cb_98", "cb_99", "cb_9a", "cb_9b", "cb_9c", "cb_9d", "cb_9e", "cb_9f",
+    "cb_a0", "cb_a1", "cb_a2", "cb_a3", "cb_a4", "cb_a5", "cb_a6", "cb_a7",
+    "cb_a8", "cb_a9", "cb_aa", "cb_ab", "cb_ac", "cb_ad", "cb_ae", "cb_af",
+    "cb_b0", "cb_b1", "cb_b2", "cb_b3", "cb_b4", "cb_b5", "cb_b6", "cb_b7",
+    "cb_b8", "cb_b9", "cb_ba", "cb_bb", "cb_bc", "cb_bd", "cb_be", "cb_bf",
+    "cb_c0", "cb_c1", "cb_c2", "cb_c3", "cb_c4", "cb_c5", "cb_c6", "cb_c7",
+    "cb_c8", "cb_c9", "cb_ca", "cb_cb", "cb_cc", "cb_cd", "cb_ce", "cb_cf",
+    "cb_d0", "cb_d1", "cb_d2", "cb_d3", "cb_d4", "cb_d5", "cb_d6", "cb_d7",
+    "cb_d8", "cb_d9", "cb_da", "cb_db", "cb_dc", "cb_dd", "cb_de", "cb_df",
+    "cb_e0", "cb_e1", "cb_e2", "cb_e3", "cb_e4", "cb_e5", "cb_e6", "cb_e7",
+    "cb_e8", "cb_e9", "cb_ea", "cb_eb", "cb_ec", "cb_ed", "cb_ee", "cb_ef",
+    "cb_f0", "cb_f1", "cb_f2", "cb_f3", "cb_f4", "cb_f5", "cb_f6", "cb_f7",
+    "cb_f8", "cb_f9", "cb_fa", "cb_fb", "cb_fc", "cb_fd", "cb_fe", "cb_ff"
 }
 
 /// The actual meat of the tests. Iterates through a SingleStepTest JSON file and for each test case
@@ -38,26 +100,91 @@ single_step_test_opcodes! {
 /// difficult to reason about than just showing the full before and after.
 fn test_opcode(opcode: &str) {
     let cases = load_test(opcode);
+    let flag_effects = flag_effects(opcode);
 
     for case in cases {
+        // The test data is the ground truth, so disagreeing with it means the metadata is wrong.
+        if let Some((flag, effect)) =
+            flag_effects.contradiction(case.initial_state.f, case.final_state.f)
+        {
+            panic!(
+                "Opcode {}\n  opcodes.json says {flag} is {effect:?} ({flag_effects}), but the test \
+                 data disagrees\n  initial: {:?}\n  expected: {:?}",
+                &case.name, &case.initial_state, &case.final_state,
+            );
+        }
+
         let mut dmg: DotMatrix = case.initial_state.clone().into();
 
-        dmg.exec_instruction();
+        let trace = dmg.exec_instruction();
+
+        assert!(
+            !dmg.cpu.is_locked(),
+            "Opcode {}\n  CPU locked at PC={:04X}",
+            &case.name,
+            dmg.cpu.pc.wrapping_sub(1),
+        );
+
+        let dmg_state = State::new(&dmg, &case.final_state);
 
-        let addrs: Vec<u16> = case.final_state.ram.iter().map(|(addr, _)| *addr).collect();
-        let dmg_state = State::new(&dmg, &addrs);
+        // The metadata agrees with the test data, so disagreeing with it means the ALU is wrong.
+        if let Some((flag, effect)) = flag_effects.contradiction(case.initial_state.f, dmg_state.f)
+        {
+            panic!(
+                "Opcode {}\n  {flag} should be {effect:?} ({flag_effects}), but the CPU disagrees\n  \
+                 initial: {:?}\n  expected: {:?}\n  result: {:?}",
+                &case.name, &case.initial_state, &case.final_state, &dmg_state,
+            );
+        }
+
+        // A little memory either side of PC and SP, which is usually where it went wrong
+        let around = |addr: u16| {
+            let start = addr.saturating_sub(0x10) & 0xFFF0;
+
+            start..=start.saturating_add(0x2F)
+        };
 
         assert!(
             case.final_state == dmg_state,
-            "Opcode {}\n  initial: {:?}\n  expected: {:?}\n  result: {:?}",
+            "Opcode {}\n  initial: {:?}\n  expected: {:?}\n  result: {:?}\n  around PC:\n{}  \
+             around SP:\n{}",
             &case.name,
             &case.initial_state,
             &case.final_state,
             &dmg_state,
+            dmg.bus.hexdump(around(dmg.cpu.pc)),
+            dmg.bus.hexdump(around(dmg.cpu.sp)),
+        );
+
+        // The last m-cycle of an instruction overlaps the next fetch, which isn't part of the test.
+        let expected: Vec<_> = case
+            .cycles
+            .iter()
+            .map(|cycle| cycle.as_ref().and_then(Cycle::activity))
+            .collect();
+        let trace: Vec<_> = trace.into_iter().take(expected.len()).collect();
+
+        assert!(
+            expected == trace,
+            "Opcode {}\n  initial: {:?}\n  expected cycles: {:?}\n  result cycles: {:?}",
+            &case.name,
+            &case.initial_state,
+            &expected,
+            &trace,
         );
     }
 }
 
+/// Look up the documented flag effects of an opcode by its test name, such as `3e` or `cb 7c`.
+fn flag_effects(opcode: &str) -> FlagEffects {
+    match opcode.split_once(' ') {
+        Some((_, cb_opcode)) => {
+            CbOpcode::from(u8::from_str_radix(cb_opcode, 16).unwrap()).flag_effects()
+        }
+        None => Opcode::from(u8::from_str_radix(opcode, 16).unwrap()).flag_effects(),
+    }
+}
+
 /// Load a test file from disk matching the given opcode.
 fn load_test(opcode: &str) -> Vec<SM83TestCase> {
     let path = format!(
@@ -85,9 +212,24 @@ struct SM83TestCase {
     #[serde(rename = "final")]
     final_state: State,
 
-    // TODO: Handle cycles
     /// A list of all cycles
-    cycles: Vec<(u16, u8, String)>,
+    cycles: Vec<Option<Cycle>>,
+}
+
+/// The address bus, data bus, and `r`/`w`/`m` pins during a single m-cycle of a test. Cycles that
+/// don't access memory may leave out the buses or be entirely `null`.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+struct Cycle(Option<u16>, Option<u8>, String);
+
+impl Cycle {
+    /// The access the CPU is expected to make on the bus this cycle, if any.
+    fn activity(&self) -> Option<BusActivity> {
+        match (self.0, self.1, self.2.as_bytes()) {
+            (Some(addr), Some(value), [b'r', ..]) => Some(BusActivity::read(addr, value)),
+            (Some(addr), Some(value), [_, b'w', ..]) => Some(BusActivity::write(addr, value)),
+            _ => None,
+        }
+    }
 }
 
 /// The state of the system, before or after a test.
@@ -124,25 +266,24 @@ struct State {
     /// The status of the `L` register.
     l: u8,
 
-    // TODO: Handle IME
     /// The status of the `IME` register.
-    // #[serde_as(as = "BoolFromInt")]
-    // ime: bool,
+    #[serde_as(as = "BoolFromInt")]
+    ime: bool,
 
-    // TODO: Handle IE
-    /// The status of the `IME` register, usually only on `initial` state.
-    // ie: Option<u8>,
+    /// The status of the `IE` register, usually only on `initial` state.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ie: Option<u8>,
 
     /// A tuple of memory addresses to values in that address.
     ram: Vec<(u16, u8)>,
 }
 
 impl State {
-    /// Pull out comparable state from an instance of DotMatrix.
+    /// Pull out the state from an instance of DotMatrix that's comparable with `expected`.
     ///
-    /// The only reason this isn't `From<DotMatrix>` is because we only want to compare specific
-    /// addresses.
-    fn new(dmg: &DotMatrix, ram_addrs: &[u16]) -> Self {
+    /// The only reason this isn't `From<DotMatrix>` is because we only want to compare the
+    /// addresses, and `IE` if present, that `expected` has.
+    fn new(dmg: &DotMatrix, expected: &State) -> Self {
         Self {
             pc: dmg.cpu.pc,
             sp: dmg.cpu.sp,
@@ -154,9 +295,12 @@ impl State {
             f: dmg.cpu.registers.f(),
             h: dmg.cpu.registers.h(),
             l: dmg.cpu.registers.l(),
-            ram: ram_addrs
+            ime: dmg.cpu.ime,
+            ie: expected.ie.map(|_| dmg.bus.peek(IE)),
+            ram: expected
+                .ram
                 .iter()
-                .map(|&addr| (addr, dmg.bus.read(addr)))
+                .map(|&(addr, _)| (addr, dmg.bus.peek(addr)))
                 .collect(),
         }
     }
@@ -185,6 +329,11 @@ impl Debug for State {
 
         write!(f, "SP:{:04X} ", self.sp)?;
         write!(f, "PC:{:04X} ", self.pc)?;
+        write!(f, "IME:{:01} ", self.ime as u8)?;
+
+        if let Some(ie) = self.ie {
+            write!(f, "IE:{ie:02X} ")?;
+        }
 
         writeln!(f, "}}")?;
         write!(f, "\tRAM {{ ")?;
@@ -213,11 +362,17 @@ impl From<State> for DotMatrix {
 
         dmg.cpu.pc = state.pc;
         dmg.cpu.sp = state.sp;
+        dmg.cpu.ime = state.ime;
 
         for (address, value) in state.ram {
             dmg.bus.write(address, value);
         }
 
+        // After RAM, in case the test data also lists 0xFFFF there
+        if let Some(ie) = state.ie {
+            dmg.bus.write(IE, ie);
+        }
+
         dmg
     }
 }
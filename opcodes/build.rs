@@ -1,19 +1,48 @@
 use std::{fs::File, io::Write};
 
 use anyhow::Result;
-use proc_macro2::TokenStream;
+use proc_macro2::{Ident, TokenStream};
 use quote::{format_ident, quote};
-use serde::{Deserialize, Serialize};
+use table::{enum_variants, load_opcodes, validate_table, Cycles, Opcode};
+
+#[path = "build/table.rs"]
+mod table;
 
 fn main() -> Result<()> {
-    // Regenerate if `opcodes.json` changes
+    // Regenerate if either opcode table or the m-code they refer to changes
     println!("cargo:rerun-if-changed=opcodes.json");
+    println!("cargo:rerun-if-changed=cb_opcodes.json");
+    println!("cargo:rerun-if-changed=src/mcode.rs");
+    println!("cargo:rerun-if-changed=src/operand.rs");
+
+    let opcode_json = load_opcodes("opcodes.json", include_bytes!("./opcodes.json"))?;
+    let cb_opcodes = load_opcodes("cb_opcodes.json", include_bytes!("./cb_opcodes.json"))?;
+
+    let mcode_variants = enum_variants(include_str!("./src/mcode.rs"), "src/mcode.rs", "MCode")?;
+    let operand_variants = enum_variants(
+        include_str!("./src/operand.rs"),
+        "src/operand.rs",
+        "Operand",
+    )?;
 
-    let opcode_json: Vec<Opcode> = serde_json::from_slice(include_bytes!("./opcodes.json"))?;
+    validate_table(
+        "opcodes.json",
+        &opcode_json,
+        0,
+        &mcode_variants,
+        &operand_variants,
+    )?;
 
-    assert_eq!(256, opcode_json.len(), "Must have exactly 256 opcodes");
+    // The CB prefix takes a byte and an m-cycle of its own before the CB opcode's m-code starts
+    validate_table(
+        "cb_opcodes.json",
+        &cb_opcodes,
+        1,
+        &mcode_variants,
+        &operand_variants,
+    )?;
 
-    let syn_file = build_opcodes_file(&opcode_json)?;
+    let syn_file = build_opcodes_file(&opcode_json, &cb_opcodes)?;
     let mut file = File::create("./src/opcodes.rs")?;
 
     write!(file, "{}", prettyplease::unparse(&syn_file))?;
@@ -21,43 +50,144 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-fn build_opcodes_file(opcodes: &[Opcode]) -> Result<syn::File> {
+fn build_opcodes_file(opcodes: &[Opcode], cb_opcodes: &[Opcode]) -> Result<syn::File> {
+    let mut items = syn::parse2::<syn::File>(build_imports())?.items;
+
+    for (name, opcodes) in [("Opcode", opcodes), ("CbOpcode", cb_opcodes)] {
+        let name = format_ident!("{}", name);
+
+        items.push(syn::parse2(build_enum(&name, opcodes))?);
+        items.push(syn::parse2(build_from(&name, opcodes))?);
+        items.push(syn::parse2(build_display(&name, opcodes))?);
+        items.push(syn::parse2(build_impl(&name, opcodes)?)?);
+        items.extend(syn::parse2::<syn::File>(build_serde(&name))?.items);
+    }
+
+    items.push(syn::parse2(build_cb_prefix())?);
+
     Ok(syn::File {
         shebang: None,
         attrs: vec![],
-        items: vec![
-            syn::parse2(build_imports())?,
-            syn::parse2(build_enum(opcodes))?,
-            syn::parse2(build_from(opcodes))?,
-            syn::parse2(build_display(opcodes))?,
-            syn::parse2(build_impl(opcodes))?,
-        ],
+        items,
     })
 }
 
 fn build_imports() -> TokenStream {
-    quote! { use crate::mcode::MCode; }
+    quote! {
+        use crate::cycles::Cycles;
+        use crate::flags::{FlagEffect, FlagEffects};
+        use crate::mcode::{AluOp::*, Condition::*, MCode, UnaryOp::*, R16::*, R8::*};
+        use crate::operand::Operand;
+    }
 }
 
-fn build_enum(opcodes: &[Opcode]) -> TokenStream {
+/// Opcodes are serialized as their byte rather than the name of their variant, which keeps the
+/// format compact and stable if the ids in the opcode tables change.
+fn build_serde(name: &Ident) -> TokenStream {
+    quote! {
+        #[cfg(feature = "serde")]
+        impl serde::Serialize for #name {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_u8(*self as u8)
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de> serde::Deserialize<'de> for #name {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                u8::deserialize(deserializer).map(Self::from)
+            }
+        }
+    }
+}
+
+fn build_cb_prefix() -> TokenStream {
+    quote! {
+        impl Opcode {
+            /// The prefix of every opcode in [CbOpcode]. Its only m-code fetches the second byte
+            /// and queues the m-code of that [CbOpcode].
+            pub const CB_PREFIX: Self = Self::PrefixCB;
+
+            /// Whether this is [Opcode::CB_PREFIX].
+            pub const fn is_cb_prefix(&self) -> bool {
+                *self as u8 == Self::CB_PREFIX as u8
+            }
+        }
+    }
+}
+
+fn build_enum(name: &Ident, opcodes: &[Opcode]) -> TokenStream {
     let opcodes = opcodes.iter().map(|op| {
         let id = format_ident!("{}", op.id);
         let opcode = op.opcode;
+        let doc = variant_doc(name, op);
 
-        quote! { #id = #opcode }
+        quote! {
+            #(#[doc = #doc])*
+            #id = #opcode
+        }
     });
 
     quote! {
         #[allow(non_camel_case_types)]
         #[derive(Clone, Copy, Debug, Eq, PartialEq)]
         #[repr(u8)]
-        pub enum Opcode {
+        pub enum #name {
             #(#opcodes),*
         }
     }
 }
 
-fn build_from(opcodes: &[Opcode]) -> TokenStream {
+/// The doc comment of an opcode's variant, one line per string: its mnemonics, then its encoding,
+/// timing, and flag effects.
+fn variant_doc(name: &Ident, op: &Opcode) -> Vec<String> {
+    let (first, others) = op.mnemonic.split_first().unwrap();
+    let bytes = match name == "CbOpcode" {
+        true => format!("`0xCB 0x{:02X}`", op.opcode),
+        false => format!("`0x{:02X}`", op.opcode),
+    };
+    let plural = |count: u8, unit: &str| match count {
+        1 => format!("{count} {unit}"),
+        _ => format!("{count} {unit}s"),
+    };
+    let cycles = match op.cycles {
+        Cycles::Fixed(cycles) => plural(cycles, "m-cycle"),
+        Cycles::Conditional(taken, not_taken) => {
+            format!("{} if taken, {not_taken} if not", plural(taken, "m-cycle"))
+        }
+    };
+
+    let mut doc = vec![format!(" `{first}`")];
+
+    if !others.is_empty() {
+        let others: Vec<String> = others.iter().map(|other| format!("`{other}`")).collect();
+
+        doc.extend([
+            String::new(),
+            format!(" Also written as {}.", others.join(", ")),
+        ]);
+    }
+
+    doc.extend([
+        String::new(),
+        format!(" - Encoding: {bytes}, {}", plural(op.length, "byte")),
+        format!(" - Cycles: {cycles}"),
+        format!(" - Flags: `{}`", op.flags),
+    ]);
+
+    if op.mcode == ["Illegal"] {
+        let note = match is_illegal(op) {
+            true => " Undefined on the SM83, executing it locks up the CPU.",
+            false => " Not implemented yet, executing it locks up the CPU.",
+        };
+
+        doc.extend([String::new(), note.to_string()]);
+    }
+
+    doc
+}
+
+fn build_from(name: &Ident, opcodes: &[Opcode]) -> TokenStream {
     let opcodes = opcodes.iter().map(|op| {
         let id = format_ident!("{}", op.id);
         let opcode = op.opcode;
@@ -66,7 +196,7 @@ fn build_from(opcodes: &[Opcode]) -> TokenStream {
     });
 
     quote! {
-        impl From<u8> for Opcode {
+        impl From<u8> for #name {
             fn from(opcode: u8) -> Self {
                 match opcode {
                     #(#opcodes),*
@@ -76,7 +206,25 @@ fn build_from(opcodes: &[Opcode]) -> TokenStream {
     }
 }
 
-fn build_impl(opcodes: &[Opcode]) -> TokenStream {
+fn build_impl(name: &Ident, opcodes: &[Opcode]) -> Result<TokenStream> {
+    let all = opcodes.iter().map(|op| format_ident!("{}", op.id));
+
+    let illegal = opcodes
+        .iter()
+        .filter(|op| is_illegal(op))
+        .map(|op| format_ident!("{}", op.id))
+        .collect::<Vec<_>>();
+
+    // Opcodes still waiting on their m-code are marked `Illegal` in the meantime
+    let unimplemented = opcodes
+        .iter()
+        .filter(|op| !is_illegal(op) && op.mcode == ["Illegal"])
+        .map(|op| format_ident!("{}", op.id))
+        .collect::<Vec<_>>();
+
+    let is_illegal = build_matches(&illegal, true);
+    let is_implemented = build_matches(&unimplemented, false);
+
     let lengths = opcodes.iter().map(|op| {
         let id = format_ident!("{}", op.id);
         let length = op.length;
@@ -84,42 +232,140 @@ fn build_impl(opcodes: &[Opcode]) -> TokenStream {
         quote! { Self::#id => #length }
     });
 
-    let mcodes = opcodes.iter().map(|op| {
+    let cycles = opcodes.iter().map(|op| {
         let id = format_ident!("{}", op.id);
-        let mcodes = op.mcode.iter().map(|mcode| format_ident!("{}", mcode));
+        let cycles = match op.cycles {
+            Cycles::Fixed(cycles) => quote! { Cycles::Fixed(#cycles) },
+            Cycles::Conditional(taken, not_taken) => {
+                quote! { Cycles::Conditional { taken: #taken, not_taken: #not_taken } }
+            }
+        };
 
-        quote! { Self::#id => vec![#(MCode::#mcodes),*] }
+        quote! { Self::#id => #cycles }
     });
 
-    quote! {
-        impl Opcode {
-            pub fn length(&self) -> u16 {
+    let flag_effects = opcodes.iter().map(|op| {
+        let id = format_ident!("{}", op.id);
+        let [z, n, h, c] = [0, 1, 2, 3].map(|index| match op.flags.as_bytes()[index] {
+            b'-' => quote! { FlagEffect::Unaffected },
+            b'1' => quote! { FlagEffect::Set },
+            b'0' => quote! { FlagEffect::Reset },
+            _ => quote! { FlagEffect::Computed },
+        });
+
+        quote! { Self::#id => FlagEffects { z: #z, n: #n, h: #h, c: #c } }
+    });
+
+    let operands = opcodes
+        .iter()
+        .map(|op| {
+            let id = format_ident!("{}", op.id);
+            let operands = op
+                .operands
+                .iter()
+                .map(|operand| syn::parse_str::<syn::Expr>(operand))
+                .collect::<syn::Result<Vec<_>>>()?;
+
+            Ok(quote! { Self::#id => &[#(Operand::#operands),*] })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mcodes = opcodes
+        .iter()
+        .map(|op| {
+            let id = format_ident!("{}", op.id);
+            let mcodes = op
+                .mcode
+                .iter()
+                .map(|mcode| syn::parse_str::<syn::Expr>(mcode))
+                .collect::<syn::Result<Vec<_>>>()?;
+
+            Ok(quote! { Self::#id => &[#(MCode::#mcodes),*] })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(quote! {
+        impl #name {
+            /// Every opcode, in order.
+            pub const ALL: [Self; 256] = [#(Self::#all),*];
+
+            /// Whether the opcode is undefined on the SM83, locking up the CPU when executed.
+            pub const fn is_illegal(&self) -> bool {
+                #is_illegal
+            }
+
+            /// Whether the opcode has m-code to execute. Illegal opcodes count as implemented, as
+            /// locking up the CPU is all they do.
+            pub const fn is_implemented(&self) -> bool {
+                #is_implemented
+            }
+
+            /// The length of the instruction in bytes, including the opcode and any operands.
+            pub const fn length(&self) -> u8 {
                 match self {
                     #(#lengths),*
                 }
             }
 
-            pub fn mcode(&self) -> Vec<MCode> {
+            /// The operands of the instruction, in the order they appear in its mnemonic.
+            pub const fn operands(&self) -> &'static [Operand] {
+                match self {
+                    #(#operands),*
+                }
+            }
+
+            /// What the instruction does to each flag, as documented.
+            pub const fn flag_effects(&self) -> FlagEffects {
+                match self {
+                    #(#flag_effects),*
+                }
+            }
+
+            /// The number of m-cycles the instruction takes, including its fetch.
+            pub const fn m_cycles(&self) -> Cycles {
+                match self {
+                    #(#cycles),*
+                }
+            }
+
+            /// The number of t-cycles the instruction takes, including its fetch.
+            pub const fn t_cycles(&self) -> Cycles {
+                self.m_cycles().to_t_cycles()
+            }
+
+            /// The m-code executed for the instruction, one per m-cycle after it's fetched.
+            pub const fn mcode(&self) -> &'static [MCode] {
                 match self {
                     #(#mcodes),*
                 }
             }
         }
+    })
+}
+
+/// Evaluates to `matched` if `self` is any of `ids`, or the opposite otherwise. `matches!` needs at
+/// least one pattern, so an empty `ids` is handled separately.
+fn build_matches(ids: &[Ident], matched: bool) -> TokenStream {
+    let unmatched = !matched;
+
+    match (ids.is_empty(), matched) {
+        (true, _) => quote! { #unmatched },
+        (false, true) => quote! { matches!(self, #(Self::#ids)|*) },
+        (false, false) => quote! { !matches!(self, #(Self::#ids)|*) },
     }
 }
 
-fn build_display(opcodes: &[Opcode]) -> TokenStream {
+fn build_display(name: &Ident, opcodes: &[Opcode]) -> TokenStream {
     let opcodes = opcodes.iter().map(|op| {
         let id = format_ident!("{}", op.id);
-        println!("{id}");
         let mnemonic = op.mnemonic.first().unwrap();
 
         quote! { Self::#id => #mnemonic }
     });
 
     quote! {
-        impl std::fmt::Display for Opcode {
-            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        impl core::fmt::Display for #name {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
                 write!(f, "{}", match self {
                     #(#opcodes),*
                 })
@@ -128,11 +374,7 @@ fn build_display(opcodes: &[Opcode]) -> TokenStream {
     }
 }
 
-#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
-struct Opcode {
-    opcode: u8,
-    id: String,
-    mnemonic: Vec<String>,
-    length: u16,
-    mcode: Vec<String>,
+/// The opcodes undefined on the SM83 are all named after their byte, such as `ILLEGAL_D3`.
+fn is_illegal(op: &Opcode) -> bool {
+    op.id.starts_with("ILLEGAL_")
 }
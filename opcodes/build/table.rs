@@ -0,0 +1,465 @@
+//! Loading and validating the opcode tables. Shared by the build script, and by the library's tests
+//! so the validation itself can be tested against bad tables.
+
+use std::collections::HashMap;
+
+use anyhow::{bail, Context, Result};
+use quote::quote;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Opcode {
+    pub opcode: u8,
+    pub id: String,
+    pub mnemonic: Vec<String>,
+    pub operands: Vec<String>,
+    pub length: u8,
+    pub cycles: Cycles,
+    pub flags: String,
+    pub mcode: Vec<String>,
+}
+
+/// Either a fixed number of m-cycles, or `[taken, not_taken]` for conditional opcodes.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum Cycles {
+    Fixed(u8),
+    Conditional(u8, u8),
+}
+
+/// Parse an opcode table, which must have exactly one entry for each of the 256 opcodes.
+pub fn load_opcodes(name: &str, json: &[u8]) -> Result<Vec<Opcode>> {
+    let opcodes: Vec<Opcode> =
+        serde_json::from_slice(json).with_context(|| format!("Failed to parse {name}"))?;
+
+    if opcodes.len() != 256 {
+        bail!(
+            "{name} must have exactly 256 opcodes, found {}",
+            opcodes.len()
+        );
+    }
+
+    Ok(opcodes)
+}
+
+/// Collect the names of every variant of an enum, so the m-code and operands in the opcode tables
+/// can be checked against them.
+pub fn enum_variants(source: &str, path: &str, name: &str) -> Result<Vec<String>> {
+    let file = syn::parse_file(source).with_context(|| format!("Failed to parse {path}"))?;
+
+    file.items
+        .iter()
+        .find_map(|item| match item {
+            syn::Item::Enum(item) if item.ident == name => Some(
+                item.variants
+                    .iter()
+                    .map(|variant| variant.ident.to_string())
+                    .collect(),
+            ),
+            _ => None,
+        })
+        .with_context(|| format!("No `{name}` enum in {path}"))
+}
+
+/// Check every entry of an opcode table, failing on the first problem with the table, index, id,
+/// and field it was found in, such as ``opcodes.json[62] `LD_A_n8` (0x3E), field `mcode`: ...``.
+///
+/// `prefix` is the number of prefix bytes, and m-cycles, that come before the table's opcodes.
+pub fn validate_table(
+    name: &str,
+    opcodes: &[Opcode],
+    prefix: u8,
+    mcode_variants: &[String],
+    operand_variants: &[String],
+) -> Result<()> {
+    let mut ids = HashMap::new();
+
+    for (index, op) in opcodes.iter().enumerate() {
+        let checks = [
+            ("opcode", validate_opcode(op, index)),
+            ("id", validate_id(op, index, &mut ids)),
+            ("mnemonic", validate_mnemonic(op)),
+            ("length", validate_length(op)),
+            ("mcode", validate_mcode(op, mcode_variants)),
+            ("flags", validate_flags(op)),
+            ("operands", validate_operands(op, operand_variants, prefix)),
+            ("cycles", validate_cycles(op, prefix)),
+        ];
+
+        for (field, check) in checks {
+            if let Err(message) = check {
+                bail!(
+                    "{name}[{index}] `{}` ({:#04X}), field `{field}`: {message}",
+                    op.id,
+                    op.opcode
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that an opcode sits at the index of its own value, which with exactly 256 entries means
+/// every value is covered once.
+fn validate_opcode(op: &Opcode, index: usize) -> Result<(), String> {
+    if usize::from(op.opcode) != index {
+        return Err(format!(
+            "{:#04X} is out of place, entries must be in order with no gaps or duplicates",
+            op.opcode
+        ));
+    }
+
+    Ok(())
+}
+
+/// Check that an id can name an enum variant, and isn't already used by an earlier opcode.
+fn validate_id<'a>(
+    op: &'a Opcode,
+    index: usize,
+    ids: &mut HashMap<&'a str, usize>,
+) -> Result<(), String> {
+    if syn::parse_str::<syn::Ident>(&op.id).is_err() {
+        return Err(format!("`{}` is not a valid Rust identifier", op.id));
+    }
+
+    if let Some(first) = ids.insert(&op.id, index) {
+        return Err(format!("`{}` is already used by entry {first}", op.id));
+    }
+
+    Ok(())
+}
+
+/// Check that an opcode has at least one mnemonic, and none of them are blank.
+fn validate_mnemonic(op: &Opcode) -> Result<(), String> {
+    if op.mnemonic.is_empty() {
+        return Err("there must be at least one mnemonic".to_string());
+    }
+
+    if op
+        .mnemonic
+        .iter()
+        .any(|mnemonic| mnemonic.trim().is_empty())
+    {
+        return Err(format!("{:?} contains an empty mnemonic", op.mnemonic));
+    }
+
+    Ok(())
+}
+
+/// Check that an opcode is between 1 and 3 bytes long, including any prefix.
+fn validate_length(op: &Opcode) -> Result<(), String> {
+    if !(1..=3).contains(&op.length) {
+        return Err(format!("{} should be between 1 and 3", op.length));
+    }
+
+    Ok(())
+}
+
+/// Check that every m-code of an opcode parses and names a real `MCode` variant, so mistakes in
+/// the tables fail the build with the offending opcode rather than in the generated code.
+fn validate_mcode(op: &Opcode, variants: &[String]) -> Result<(), String> {
+    for mcode in &op.mcode {
+        match parse_variant(mcode) {
+            Some((name, _)) if variants.contains(&name) => (),
+            _ => return Err(format!("`{mcode}` is not an MCode variant")),
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that the operands of an opcode name real `Operand` variants, spell out the operands of its
+/// first mnemonic, and take up the bytes of the instruction after the opcode and any prefix.
+fn validate_operands(op: &Opcode, variants: &[String], prefix: u8) -> Result<(), String> {
+    let mut tokens = vec![];
+    let mut size = 0;
+
+    for operand in &op.operands {
+        let (token, bytes) = match parse_variant(operand) {
+            Some((name, args)) if variants.contains(&name) => operand_token(&name, &args),
+            _ => None,
+        }
+        .ok_or_else(|| format!("`{operand}` is not an Operand variant"))?;
+
+        tokens.push(token);
+        size += bytes;
+    }
+
+    let mnemonic = op.mnemonic.first().map_or("", String::as_str);
+    let expected: Vec<&str> = match mnemonic.split_once(' ') {
+        Some((_, operands)) => operands.split(", ").collect(),
+        None => vec![],
+    };
+
+    if tokens != expected {
+        return Err(format!(
+            "{:?} don't match mnemonic `{mnemonic}`",
+            op.operands
+        ));
+    }
+
+    if 1 + prefix + size != op.length {
+        return Err(format!(
+            "{:?} take {size} bytes, which doesn't fit a length of {}",
+            op.operands, op.length
+        ));
+    }
+
+    Ok(())
+}
+
+/// Split an m-code or operand into the name of its variant and its arguments, if it parses as one.
+fn parse_variant(source: &str) -> Option<(String, Vec<syn::Expr>)> {
+    let (path, args) = match syn::parse_str::<syn::Expr>(source).ok()? {
+        syn::Expr::Path(path) => (path.path, vec![]),
+        syn::Expr::Call(syn::ExprCall { func, args, .. }) => match *func {
+            syn::Expr::Path(path) => (path.path, args.into_iter().collect()),
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    Some((path.segments.last()?.ident.to_string(), args))
+}
+
+/// How an operand is written in a mnemonic, and how many bytes it takes up after the opcode.
+fn operand_token(name: &str, args: &[syn::Expr]) -> Option<(String, u8)> {
+    let arg = args.first().map(|arg| quote!(#arg).to_string());
+
+    let token = match (name, arg.as_deref()) {
+        ("R8" | "R16" | "Bit", Some(arg)) => arg.to_string(),
+        ("Ptr", Some(r16)) => format!("[{r16}]"),
+        ("PtrInc", Some(r16)) => format!("[{r16}+]"),
+        ("PtrDec", Some(r16)) => format!("[{r16}-]"),
+        ("PtrHighC", None) => "[$FF00+C]".to_string(),
+        ("Imm8", None) => "n8".to_string(),
+        ("Imm16", None) => "n16".to_string(),
+        ("Offset", None) => "e8".to_string(),
+        ("SpOffset", None) => "SP+e8".to_string(),
+        ("Addr", None) => "a16".to_string(),
+        ("PtrAddr", None) => "[a16]".to_string(),
+        ("PtrHigh", None) => "[$FF00+a8]".to_string(),
+        ("Cond", Some("NotZero")) => "NZ".to_string(),
+        ("Cond", Some("Zero")) => "Z".to_string(),
+        ("Cond", Some("NotCarry")) => "NC".to_string(),
+        ("Cond", Some("Carry")) => "C".to_string(),
+        ("Vector", Some(vector)) => {
+            let vector = syn::parse_str::<syn::LitInt>(vector).ok()?;
+
+            format!("${:02X}", vector.base10_parse::<u8>().ok()?)
+        }
+        _ => return None,
+    };
+
+    let size = match name {
+        "Imm8" | "Offset" | "SpOffset" | "PtrHigh" => 1,
+        "Imm16" | "Addr" | "PtrAddr" => 2,
+        _ => 0,
+    };
+
+    Some((token, size))
+}
+
+/// Check that the flags of an opcode are written in `znhc` notation, such as `Z0H-`.
+fn validate_flags(op: &Opcode) -> Result<(), String> {
+    let valid = op.flags.len() == 4
+        && op
+            .flags
+            .chars()
+            .zip("ZNHC".chars())
+            .all(|(effect, flag)| matches!(effect, '-' | '0' | '1') || effect == flag);
+
+    if !valid {
+        return Err(format!(
+            "`{}` should be in the form `ZNHC`, with `-`, `0`, or `1` in place of any flag that \
+             isn't computed",
+            op.flags
+        ));
+    }
+
+    Ok(())
+}
+
+/// Check that the declared cycles of an opcode agree with its m-code, one m-cycle per m-code. A
+/// conditional opcode skips everything after its `MCode::CheckCondition` when not taken.
+///
+/// Opcodes that are still `MCode::Illegal` aren't checked, their cycles are what the real
+/// instruction takes.
+fn validate_cycles(op: &Opcode, prefix: u8) -> Result<(), String> {
+    if op.mcode == ["Illegal"] {
+        return Ok(());
+    }
+
+    let len = op.mcode.len() as u8 + prefix;
+    let check = op
+        .mcode
+        .iter()
+        .position(|mcode| mcode.starts_with("CheckCondition("))
+        .map(|index| index as u8 + 1 + prefix);
+
+    match (op.cycles, check) {
+        (Cycles::Fixed(cycles), None) if cycles == len => Ok(()),
+        (Cycles::Conditional(taken, not_taken), Some(check))
+            if taken == len && not_taken == check =>
+        {
+            Ok(())
+        }
+        (cycles, _) => Err(format!("{cycles:?} don't match m-code {:?}", op.mcode)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn tables() -> (Vec<Opcode>, Vec<Opcode>) {
+        (
+            load_opcodes("opcodes.json", include_bytes!("../opcodes.json")).unwrap(),
+            load_opcodes("cb_opcodes.json", include_bytes!("../cb_opcodes.json")).unwrap(),
+        )
+    }
+
+    fn variants() -> (Vec<String>, Vec<String>) {
+        (
+            enum_variants(include_str!("../src/mcode.rs"), "src/mcode.rs", "MCode").unwrap(),
+            enum_variants(
+                include_str!("../src/operand.rs"),
+                "src/operand.rs",
+                "Operand",
+            )
+            .unwrap(),
+        )
+    }
+
+    /// Validate the base table after `corrupt` has been applied to it, returning the error.
+    fn error(corrupt: impl FnOnce(&mut Vec<Opcode>)) -> String {
+        let (mut opcodes, _) = tables();
+
+        let (mcode, operands) = variants();
+
+        corrupt(&mut opcodes);
+
+        validate_table("opcodes.json", &opcodes, 0, &mcode, &operands)
+            .unwrap_err()
+            .to_string()
+    }
+
+    #[test]
+    fn tables_are_valid() {
+        let (opcodes, cb_opcodes) = tables();
+        let (mcode, operands) = variants();
+
+        validate_table("opcodes.json", &opcodes, 0, &mcode, &operands).unwrap();
+        validate_table("cb_opcodes.json", &cb_opcodes, 1, &mcode, &operands).unwrap();
+    }
+
+    #[test]
+    fn table_must_have_256_opcodes() {
+        let error = load_opcodes("opcodes.json", b"[]").unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "opcodes.json must have exactly 256 opcodes, found 0"
+        );
+    }
+
+    #[test]
+    fn opcodes_must_be_in_order() {
+        assert_eq!(
+            error(|opcodes| opcodes.swap(0x3E, 0x3F)),
+            "opcodes.json[62] `CCF` (0x3F), field `opcode`: 0x3F is out of place, entries must be \
+             in order with no gaps or duplicates"
+        );
+        assert_eq!(
+            error(|opcodes| opcodes[0x3E].opcode = 0x3D),
+            "opcodes.json[62] `LD_A_n8` (0x3D), field `opcode`: 0x3D is out of place, entries \
+             must be in order with no gaps or duplicates"
+        );
+    }
+
+    #[test]
+    fn ids_must_be_unique_identifiers() {
+        assert_eq!(
+            error(|opcodes| opcodes[0x3E].id = "LD A n8".to_string()),
+            "opcodes.json[62] `LD A n8` (0x3E), field `id`: `LD A n8` is not a valid Rust \
+             identifier"
+        );
+        assert_eq!(
+            error(|opcodes| opcodes[0x3E].id = "NOP".to_string()),
+            "opcodes.json[62] `NOP` (0x3E), field `id`: `NOP` is already used by entry 0"
+        );
+    }
+
+    #[test]
+    fn mnemonics_must_not_be_empty() {
+        assert_eq!(
+            error(|opcodes| opcodes[0x3E].mnemonic.clear()),
+            "opcodes.json[62] `LD_A_n8` (0x3E), field `mnemonic`: there must be at least one \
+             mnemonic"
+        );
+        assert_eq!(
+            error(|opcodes| opcodes[0x3E].mnemonic = vec![String::new()]),
+            "opcodes.json[62] `LD_A_n8` (0x3E), field `mnemonic`: [\"\"] contains an empty \
+             mnemonic"
+        );
+    }
+
+    #[test]
+    fn length_must_be_1_to_3() {
+        assert_eq!(
+            error(|opcodes| opcodes[0x3E].length = 4),
+            "opcodes.json[62] `LD_A_n8` (0x3E), field `length`: 4 should be between 1 and 3"
+        );
+    }
+
+    #[test]
+    fn mcode_must_be_variants() {
+        assert_eq!(
+            error(|opcodes| opcodes[0x3E].mcode[0] = "ReadImm(".to_string()),
+            "opcodes.json[62] `LD_A_n8` (0x3E), field `mcode`: `ReadImm(` is not an MCode variant"
+        );
+        assert_eq!(
+            error(|opcodes| opcodes[0x3E].mcode[0] = "ReadImmediate(A)".to_string()),
+            "opcodes.json[62] `LD_A_n8` (0x3E), field `mcode`: `ReadImmediate(A)` is not an MCode \
+             variant"
+        );
+    }
+
+    #[test]
+    fn flags_must_be_znhc() {
+        assert_eq!(
+            error(|opcodes| opcodes[0x3E].flags = "Z0H".to_string()),
+            "opcodes.json[62] `LD_A_n8` (0x3E), field `flags`: `Z0H` should be in the form \
+             `ZNHC`, with `-`, `0`, or `1` in place of any flag that isn't computed"
+        );
+    }
+
+    #[test]
+    fn operands_must_match_mnemonic_and_length() {
+        assert_eq!(
+            error(|opcodes| opcodes[0x3E].operands[0] = "R9(A)".to_string()),
+            "opcodes.json[62] `LD_A_n8` (0x3E), field `operands`: `R9(A)` is not an Operand \
+             variant"
+        );
+        assert_eq!(
+            error(|opcodes| opcodes[0x3E].operands.reverse()),
+            "opcodes.json[62] `LD_A_n8` (0x3E), field `operands`: [\"Imm8\", \"R8(A)\"] don't \
+             match mnemonic `LD A, n8`"
+        );
+        assert_eq!(
+            error(|opcodes| opcodes[0x3E].length = 3),
+            "opcodes.json[62] `LD_A_n8` (0x3E), field `operands`: [\"R8(A)\", \"Imm8\"] take 1 \
+             bytes, which doesn't fit a length of 3"
+        );
+    }
+
+    #[test]
+    fn cycles_must_match_mcode() {
+        assert_eq!(
+            error(|opcodes| opcodes[0x3E].cycles = Cycles::Fixed(3)),
+            "opcodes.json[62] `LD_A_n8` (0x3E), field `cycles`: Fixed(3) don't match m-code \
+             [\"ReadImm(A)\", \"InternalDelay\"]"
+        );
+    }
+}
@@ -0,0 +1,24 @@
+//! A `no_std` consumer of the opcodes crate, checking that it builds without std or an allocator.
+//!
+//! Build it on its own so the `alloc` feature isn't unified in from the rest of the workspace:
+//! `cargo build -p dotmatrix_opcodes_no_std_check`.
+
+#![no_std]
+
+use core::fmt::Write;
+
+use dotmatrix_opcodes::{CbOpcode, MCode, Opcode};
+
+/// Decode an opcode the way a hardware debugger watching the bus would, following the CB prefix
+/// when the next byte is known.
+pub fn decode(opcode: u8, next: Option<u8>) -> &'static [MCode] {
+    match (Opcode::from(opcode), next) {
+        (Opcode::CB_PREFIX, Some(cb_opcode)) => CbOpcode::from(cb_opcode).mcode(),
+        (opcode, _) => opcode.mcode(),
+    }
+}
+
+/// Write the mnemonic of an opcode with nothing but [core::fmt].
+pub fn mnemonic(opcode: u8, out: &mut impl Write) -> core::fmt::Result {
+    write!(out, "{}", Opcode::from(opcode))
+}
@@ -0,0 +1,219 @@
+use alloc::string::{String, ToString};
+use alloc::{vec, vec::Vec};
+use core::fmt::Display;
+
+use crate::{CbOpcode, Opcode, Operand};
+
+/// An instruction that no opcode matches.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AssembleError {
+    /// The line of the instruction, starting from 1.
+    pub line: usize,
+
+    /// The instruction as it was written.
+    pub instruction: String,
+}
+
+impl Display for AssembleError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "line {}: no opcode matches `{}`",
+            self.line, self.instruction
+        )
+    }
+}
+
+impl core::error::Error for AssembleError {}
+
+/// Assemble instructions written the way [crate::format_instruction] renders them, one per line,
+/// such as `LD HL, $1234`. Blank lines are skipped, and labels are not supported.
+///
+/// Immediates are written in hex as `$3C` or `0x3C`, or in decimal, with `JR` and `SP+e8` offsets
+/// being signed. Mnemonics are matched against the first mnemonic of each opcode, ignoring case.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AssembleError> {
+    let mut bytes = vec![];
+
+    for (index, line) in source.lines().enumerate() {
+        let instruction = line.trim();
+
+        if instruction.is_empty() {
+            continue;
+        }
+
+        let encoded = assemble_instruction(instruction).ok_or_else(|| AssembleError {
+            line: index + 1,
+            instruction: instruction.to_string(),
+        })?;
+
+        bytes.extend(encoded);
+    }
+
+    Ok(bytes)
+}
+
+fn assemble_instruction(instruction: &str) -> Option<Vec<u8>> {
+    let instruction = instruction.to_uppercase();
+    let (name, operands) = match instruction.split_once(char::is_whitespace) {
+        Some((name, operands)) => (name, operands.split(',').map(str::trim).collect()),
+        None => (instruction.as_str(), vec![]),
+    };
+
+    let opcodes = Opcode::ALL
+        .into_iter()
+        .filter(|opcode| !opcode.is_cb_prefix())
+        .map(|opcode| (vec![opcode as u8], opcode.to_string(), opcode.operands()));
+
+    let cb_opcodes = CbOpcode::ALL.into_iter().map(|cb_opcode| {
+        (
+            vec![Opcode::CB_PREFIX as u8, cb_opcode as u8],
+            cb_opcode.to_string(),
+            cb_opcode.operands(),
+        )
+    });
+
+    opcodes
+        .chain(cb_opcodes)
+        .find_map(|(mut bytes, mnemonic, expected)| {
+            if mnemonic.split(' ').next() != Some(name) || expected.len() != operands.len() {
+                return None;
+            }
+
+            for (operand, token) in expected.iter().zip(&operands) {
+                bytes.extend(encode_operand(*operand, token)?);
+            }
+
+            Some(bytes)
+        })
+}
+
+/// Encode the bytes of an operand following the opcode, or [None] if `token` isn't that operand.
+fn encode_operand(operand: Operand, token: &str) -> Option<Vec<u8>> {
+    let bytes = match operand {
+        Operand::Imm8 => vec![u8::try_from(parse_number(token)?).ok()?],
+        Operand::Imm16 | Operand::Addr => u16::try_from(parse_number(token)?)
+            .ok()?
+            .to_le_bytes()
+            .to_vec(),
+        Operand::Offset => vec![i8::try_from(parse_number(token)?).ok()? as u8],
+        Operand::SpOffset => {
+            let offset = token.strip_prefix("SP")?;
+
+            vec![
+                i8::try_from(parse_number(offset.strip_prefix('+').unwrap_or(offset))?).ok()? as u8,
+            ]
+        }
+        Operand::PtrAddr => {
+            let addr = token.strip_prefix('[')?.strip_suffix(']')?;
+
+            u16::try_from(parse_number(addr)?)
+                .ok()?
+                .to_le_bytes()
+                .to_vec()
+        }
+        Operand::PtrHigh => {
+            let addr = token.strip_prefix("[$FF00+")?.strip_suffix(']')?;
+
+            vec![u8::try_from(parse_number(addr)?).ok()?]
+        }
+        Operand::Vector(vector) if parse_number(token)? == vector as i32 => vec![],
+        Operand::Bit(bit) if parse_number(token)? == bit as i32 => vec![],
+        // Registers and conditions are written the same as in the mnemonic
+        _ if token == operand.to_string() => vec![],
+        _ => return None,
+    };
+
+    Some(bytes)
+}
+
+/// Parse a hex number written as `$3C` or `0x3C`, or a decimal number, optionally negative.
+fn parse_number(token: &str) -> Option<i32> {
+    let (negative, token) = match token.strip_prefix('-') {
+        Some(token) => (true, token),
+        None => (false, token),
+    };
+
+    let value = if let Some(hex) = token.strip_prefix('$').or(token.strip_prefix("0X")) {
+        i32::from_str_radix(hex, 16).ok()?
+    } else if token.starts_with(|c: char| c.is_ascii_digit()) {
+        token.parse().ok()?
+    } else {
+        return None;
+    };
+
+    Some(if negative { -value } else { value })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{format_cb_instruction, format_instruction};
+
+    #[test]
+    fn assemble_round_trips_every_opcode() {
+        for opcode in Opcode::ALL {
+            if opcode.is_cb_prefix() {
+                continue;
+            }
+
+            let operands = [0x12, 0x34];
+            let length = opcode.length() as usize;
+            let source = format_instruction(opcode, &operands);
+
+            let mut expected = vec![opcode as u8];
+            expected.extend(&operands[..length - 1]);
+
+            assert_eq!(Ok(expected), assemble(&source), "{source}");
+        }
+
+        for cb_opcode in CbOpcode::ALL {
+            let source = format_cb_instruction(cb_opcode);
+
+            assert_eq!(
+                Ok(vec![0xCB, cb_opcode as u8]),
+                assemble(&source),
+                "{source}"
+            );
+        }
+    }
+
+    #[test]
+    fn assemble_program() {
+        let program = "
+            ld sp, $FFFE
+            LD HL, 0xC000
+            LD A, 60
+            ld [hl+], a
+
+            JR NZ, -5
+            LD HL, SP-2
+            BIT 7, H
+        ";
+
+        #[rustfmt::skip]
+        let expected = vec![
+            0x31, 0xFE, 0xFF,
+            0x21, 0x00, 0xC0,
+            0x3E, 0x3C,
+            0x22,
+            0x20, 0xFB,
+            0xF8, 0xFE,
+            0xCB, 0x7C,
+        ];
+
+        assert_eq!(Ok(expected), assemble(program));
+    }
+
+    #[test]
+    fn assemble_errors() {
+        let error = AssembleError {
+            line: 2,
+            instruction: "LD A, $100".to_string(),
+        };
+
+        assert_eq!(Err(error), assemble("NOP\nLD A, $100"));
+        assert!(assemble("JR 128").is_err());
+        assert!(assemble("LD Q, A").is_err());
+        assert!(assemble("RST $01").is_err());
+    }
+}
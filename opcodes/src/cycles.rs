@@ -0,0 +1,43 @@
+/// How many cycles an instruction takes to execute. Conditional instructions take longer when the
+/// branch is taken.
+///
+/// Counts include the m-cycle the instruction is fetched in, and for CB-prefixed instructions the
+/// m-cycle of the prefix as well.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Cycles {
+    /// The instruction always takes the same number of cycles.
+    Fixed(u8),
+
+    /// The instruction takes `taken` cycles when its condition passes, `not_taken` otherwise.
+    Conditional { taken: u8, not_taken: u8 },
+}
+
+impl Cycles {
+    /// The number of cycles when the branch is taken, or the fixed number of cycles.
+    pub const fn taken(&self) -> u8 {
+        match self {
+            Self::Fixed(cycles) | Self::Conditional { taken: cycles, .. } => *cycles,
+        }
+    }
+
+    /// The number of cycles when the branch is not taken, or the fixed number of cycles.
+    pub const fn not_taken(&self) -> u8 {
+        match self {
+            Self::Fixed(cycles)
+            | Self::Conditional {
+                not_taken: cycles, ..
+            } => *cycles,
+        }
+    }
+
+    /// Convert m-cycles to t-cycles, of which there are 4 per m-cycle.
+    pub const fn to_t_cycles(self) -> Self {
+        match self {
+            Self::Fixed(cycles) => Self::Fixed(cycles * 4),
+            Self::Conditional { taken, not_taken } => Self::Conditional {
+                taken: taken * 4,
+                not_taken: not_taken * 4,
+            },
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use alloc::string::{String, ToString};
+
+use crate::{format_cb_instruction, format_instruction, CbOpcode, Opcode};
+
+/// Disassemble `bytes` one instruction at a time, where `base_addr` is the address of the first
+/// byte. CB-prefixed instructions are decoded as a single instruction.
+///
+/// This never panics, when `bytes` ends partway through an instruction the last item is marked as
+/// incomplete instead.
+pub fn disassemble(bytes: &[u8], base_addr: u16) -> Disassembler<'_> {
+    Disassembler {
+        bytes,
+        base_addr,
+        offset: 0,
+    }
+}
+
+/// An [Iterator] over the instructions in a byte slice, see [disassemble].
+#[derive(Clone, Debug)]
+pub struct Disassembler<'a> {
+    bytes: &'a [u8],
+    base_addr: u16,
+    offset: usize,
+}
+
+/// Either kind of opcode an instruction can be decoded as.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AnyOpcode {
+    /// An opcode without a prefix.
+    Opcode(Opcode),
+
+    /// An opcode following the CB prefix.
+    Cb(CbOpcode),
+}
+
+/// A single instruction decoded by a [Disassembler].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Disassembly {
+    /// The address of the first byte of the instruction.
+    pub addr: u16,
+
+    /// The number of bytes consumed, which is less than the length of the opcode if incomplete.
+    pub length: u8,
+
+    /// The decoded opcode. An incomplete CB-prefixed instruction is just [Opcode::CB_PREFIX].
+    pub opcode: AnyOpcode,
+
+    /// The instruction formatted with its operands, see [format_instruction].
+    pub text: String,
+
+    /// Whether every byte of the instruction was present.
+    pub complete: bool,
+}
+
+impl Iterator for Disassembler<'_> {
+    type Item = Disassembly;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let bytes = &self.bytes[self.offset..];
+        let opcode = Opcode::from(*bytes.first()?);
+
+        let (opcode, expected, text) = match (opcode, bytes.get(1)) {
+            (Opcode::CB_PREFIX, Some(&cb_opcode)) => {
+                let cb_opcode = CbOpcode::from(cb_opcode);
+
+                (
+                    AnyOpcode::Cb(cb_opcode),
+                    cb_opcode.length(),
+                    format_cb_instruction(cb_opcode),
+                )
+            }
+            (Opcode::CB_PREFIX, None) => (AnyOpcode::Opcode(opcode), 2, "PREFIX".to_string()),
+            _ => {
+                let end = bytes.len().min(opcode.length() as usize);
+
+                (
+                    AnyOpcode::Opcode(opcode),
+                    opcode.length(),
+                    format_instruction(opcode, &bytes[1..end]),
+                )
+            }
+        };
+
+        let length = bytes.len().min(expected as usize) as u8;
+        let addr = self.base_addr.wrapping_add(self.offset as u16);
+
+        self.offset += length as usize;
+
+        Some(Disassembly {
+            addr,
+            length,
+            opcode,
+            text,
+            complete: length == expected,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn disassemble_program() {
+        #[rustfmt::skip]
+        let program = [
+            0x31, 0xFE, 0xFF,   // LD SP, $FFFE
+            0x21, 0x00, 0xC0,   // LD HL, $C000
+            0xAF,               // XOR A, A
+            0x22,               // LD [HL+], A
+            0xCB, 0x7C,         // BIT 7, H
+            0x28, 0xFB,         // JR Z, -5
+            0xE0, 0x40,         // LD [$FF00+$40], A
+            0xC3, 0x50, 0x01,   // JP $0150
+        ];
+
+        let expected = [
+            (0x0100, 3, "LD SP, $FFFE"),
+            (0x0103, 3, "LD HL, $C000"),
+            (0x0106, 1, "XOR A, A"),
+            (0x0107, 1, "LD [HL+], A"),
+            (0x0108, 2, "BIT 7, H"),
+            (0x010A, 2, "JR Z, -5"),
+            (0x010C, 2, "LD [$FF00+$40], A"),
+            (0x010E, 3, "JP $0150"),
+        ];
+
+        let disassembly = disassemble(&program, 0x0100).collect::<Vec<_>>();
+
+        assert_eq!(expected.len(), disassembly.len());
+
+        for (item, (addr, length, text)) in disassembly.iter().zip(expected) {
+            assert_eq!(
+                (addr, length, text),
+                (item.addr, item.length, item.text.as_str())
+            );
+            assert!(item.complete);
+        }
+
+        assert_eq!(AnyOpcode::Cb(CbOpcode::BIT_7_H), disassembly[4].opcode);
+    }
+
+    #[test]
+    fn disassemble_truncated() {
+        let items = disassemble(&[0x00, 0xC3, 0x50], 0xFFFF).collect::<Vec<_>>();
+
+        assert_eq!(2, items.len());
+        assert_eq!(0x0000, items[1].addr);
+        assert_eq!(2, items[1].length);
+        assert_eq!("JP a16", items[1].text);
+        assert!(!items[1].complete);
+
+        let items = disassemble(&[0xCB], 0x0000).collect::<Vec<_>>();
+
+        assert_eq!(AnyOpcode::Opcode(Opcode::CB_PREFIX), items[0].opcode);
+        assert_eq!(1, items[0].length);
+        assert!(!items[0].complete);
+    }
+}
@@ -0,0 +1,116 @@
+use core::fmt::Display;
+
+/// What an instruction does to a single flag, as documented.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FlagEffect {
+    /// The flag is left as it was.
+    Unaffected,
+
+    /// The flag is always set.
+    Set,
+
+    /// The flag is always cleared.
+    Reset,
+
+    /// The flag depends on the result of the instruction.
+    Computed,
+}
+
+impl FlagEffect {
+    /// Whether a flag changing from `before` to `after` is consistent with this effect.
+    pub const fn allows(&self, before: bool, after: bool) -> bool {
+        match self {
+            Self::Unaffected => before == after,
+            Self::Set => after,
+            Self::Reset => !after,
+            Self::Computed => true,
+        }
+    }
+}
+
+/// What an instruction does to each of the flags in `F`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct FlagEffects {
+    /// The effect on the `z` flag, bit 7 of `F`.
+    pub z: FlagEffect,
+
+    /// The effect on the `n` flag, bit 6 of `F`.
+    pub n: FlagEffect,
+
+    /// The effect on the `h` flag, bit 5 of `F`.
+    pub h: FlagEffect,
+
+    /// The effect on the `c` flag, bit 4 of `F`.
+    pub c: FlagEffect,
+}
+
+impl FlagEffects {
+    /// The name, bit in `F`, and effect of each flag.
+    pub const fn flags(&self) -> [(char, u8, FlagEffect); 4] {
+        [
+            ('z', 7, self.z),
+            ('n', 6, self.n),
+            ('h', 5, self.h),
+            ('c', 4, self.c),
+        ]
+    }
+
+    /// The first flag whose change from `before` to `after`, both values of `F`, contradicts its
+    /// effect.
+    pub fn contradiction(&self, before: u8, after: u8) -> Option<(char, FlagEffect)> {
+        self.flags()
+            .into_iter()
+            .find(|(_, bit, effect)| {
+                !effect.allows(before & (1 << bit) != 0, after & (1 << bit) != 0)
+            })
+            .map(|(name, _, effect)| (name, effect))
+    }
+}
+
+/// Displayed in the usual `znhc` notation, such as `Z0H-`: `-` for unaffected, `1` for set, `0`
+/// for reset, and the name of the flag for computed.
+impl Display for FlagEffects {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for (name, _, effect) in self.flags() {
+            let symbol = match effect {
+                FlagEffect::Unaffected => '-',
+                FlagEffect::Set => '1',
+                FlagEffect::Reset => '0',
+                FlagEffect::Computed => name.to_ascii_uppercase(),
+            };
+
+            write!(f, "{symbol}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{CbOpcode, Opcode};
+
+    #[test]
+    fn flag_effects_display() {
+        assert_eq!("----", Opcode::NOP.flag_effects().to_string());
+        assert_eq!("Z0H-", Opcode::INC_A.flag_effects().to_string());
+        assert_eq!("-001", Opcode::SCF.flag_effects().to_string());
+        assert_eq!("Z01-", CbOpcode::BIT_7_H.flag_effects().to_string());
+    }
+
+    #[test]
+    fn flag_effects_contradiction() {
+        let inc = Opcode::INC_A.flag_effects();
+
+        assert_eq!(None, inc.contradiction(0x10, 0xB0));
+        assert_eq!(
+            Some(('n', FlagEffect::Reset)),
+            inc.contradiction(0x00, 0x40)
+        );
+        assert_eq!(
+            Some(('c', FlagEffect::Unaffected)),
+            inc.contradiction(0x10, 0x00)
+        );
+    }
+}
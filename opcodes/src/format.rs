@@ -0,0 +1,125 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::{CbOpcode, Opcode, Operand};
+
+/// Render an instruction with the actual values of its operands, such as `LD A, $3C`. `operands`
+/// are the bytes following the opcode, any extra bytes are ignored.
+///
+/// Immediates missing from `operands` are left as they're written in the mnemonic, such as `n8`.
+/// The CB prefix is rendered as the CB-prefixed instruction that follows it.
+pub fn format_instruction(opcode: Opcode, operands: &[u8]) -> String {
+    if opcode == Opcode::CB_PREFIX {
+        return match operands.first() {
+            Some(&cb_opcode) => format_cb_instruction(cb_opcode.into()),
+            None => "PREFIX".to_string(),
+        };
+    }
+
+    format(&opcode.to_string(), opcode.operands(), operands)
+}
+
+/// Render a CB-prefixed instruction, such as `BIT 7, H`. These never have immediate operands.
+pub fn format_cb_instruction(opcode: CbOpcode) -> String {
+    format(&opcode.to_string(), opcode.operands(), &[])
+}
+
+fn format(mnemonic: &str, operands: &[Operand], bytes: &[u8]) -> String {
+    let name = mnemonic.split(' ').next().unwrap_or_default();
+    let mut bytes = bytes.iter().copied();
+
+    let operands = operands
+        .iter()
+        .map(|operand| format_operand(*operand, &mut bytes))
+        .collect::<Vec<_>>();
+
+    if operands.is_empty() {
+        name.to_string()
+    } else {
+        format!("{name} {}", operands.join(", "))
+    }
+}
+
+fn format_operand(operand: Operand, bytes: &mut impl Iterator<Item = u8>) -> String {
+    let value = match operand.size() {
+        1 => bytes.next().map(u16::from),
+        2 => bytes
+            .next()
+            .zip(bytes.next())
+            .map(|(low, high)| u16::from_le_bytes([low, high])),
+        _ => None,
+    };
+
+    let Some(value) = value else {
+        return operand.to_string();
+    };
+
+    match operand {
+        Operand::Imm8 => format!("${value:02X}"),
+        Operand::Imm16 | Operand::Addr => format!("${value:04X}"),
+        Operand::Offset => format!("{}", value as i8),
+        Operand::SpOffset => format!("SP{:+}", value as i8),
+        Operand::PtrAddr => format!("[${value:04X}]"),
+        Operand::PtrHigh => format!("[$FF00+${value:02X}]"),
+        _ => operand.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn format_instruction_snapshots() {
+        let snapshots = [
+            (Opcode::NOP, &[][..], "NOP"),
+            (Opcode::LD_A_n8, &[0x3C], "LD A, $3C"),
+            (Opcode::LD_BC_n16, &[0x34, 0x12], "LD BC, $1234"),
+            (Opcode::LD_a16_SP, &[0x00, 0xC0], "LD [$C000], SP"),
+            (Opcode::LD_A_a16, &[0x10, 0xFF], "LD A, [$FF10]"),
+            (Opcode::LDH_a8_A, &[0x40], "LD [$FF00+$40], A"),
+            (Opcode::JR_NZ, &[0xFB], "JR NZ, -5"),
+            (Opcode::JR, &[0x10], "JR 16"),
+            (Opcode::JP_a16, &[0x50, 0x01], "JP $0150"),
+            (Opcode::CALL_a16, &[0x34, 0x12], "CALL $1234"),
+            (Opcode::CALL_NC_a16, &[0x00, 0x40], "CALL NC, $4000"),
+            (Opcode::RET_Z, &[], "RET Z"),
+            (Opcode::RST_38, &[], "RST $38"),
+            (Opcode::LD_HL_SP_Offset, &[0xFE], "LD HL, SP-2"),
+            (Opcode::ADD_SP_Offset, &[0x7F], "ADD SP, 127"),
+            (Opcode::LD_PTR_HLI_A, &[], "LD [HL+], A"),
+            (Opcode::PUSH_AF, &[], "PUSH AF"),
+            (Opcode::CP_n8, &[0x00, 0xFF], "CP A, $00"),
+            (Opcode::CB_PREFIX, &[0x7C], "BIT 7, H"),
+            (Opcode::CB_PREFIX, &[], "PREFIX"),
+            (Opcode::LD_A_n8, &[], "LD A, n8"),
+            (Opcode::JP_a16, &[0x50], "JP a16"),
+        ];
+
+        for (opcode, operands, expected) in snapshots {
+            assert_eq!(expected, format_instruction(opcode, operands), "{opcode:?}");
+        }
+    }
+
+    #[test]
+    fn format_cb_instruction_snapshots() {
+        assert_eq!("RLC B", format_cb_instruction(CbOpcode::RLC_B));
+        assert_eq!("SWAP [HL]", format_cb_instruction(CbOpcode::SWAP_PTR_HL));
+        assert_eq!("RES 0, A", format_cb_instruction(CbOpcode::RES_0_A));
+        assert_eq!("SET 7, [HL]", format_cb_instruction(CbOpcode::SET_7_PTR_HL));
+    }
+
+    #[test]
+    fn operands_display_as_mnemonic() {
+        for opcode in Opcode::ALL {
+            if opcode.is_cb_prefix() {
+                continue;
+            }
+
+            let mnemonic = opcode.to_string();
+
+            assert_eq!(mnemonic, format_instruction(opcode, &[]), "{opcode:?}");
+        }
+    }
+}
@@ -1,7 +1,41 @@
+//! The opcodes of the SM83, generated from `opcodes.json` and `cb_opcodes.json`.
+//!
+//! The opcode tables themselves are `no_std` and need no allocator. Formatting, disassembling,
+//! and assembling instructions produce strings, so they need the `alloc` feature, which is on by
+//! default.
+
+#![cfg_attr(not(test), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "alloc")]
+mod assemble;
+mod cycles;
+#[cfg(feature = "alloc")]
+mod disassemble;
+mod flags;
+#[cfg(feature = "alloc")]
+mod format;
 mod mcode;
+mod operand;
 
 // Generated by build.rs
 mod opcodes;
 
-pub use mcode::MCode;
-pub use opcodes::Opcode;
+// Build scripts can't have tests of their own, so the opcode table validation is tested here
+#[cfg(test)]
+#[path = "../build/table.rs"]
+mod table;
+
+#[cfg(feature = "alloc")]
+pub use assemble::{assemble, AssembleError};
+pub use cycles::Cycles;
+#[cfg(feature = "alloc")]
+pub use disassemble::{disassemble, AnyOpcode, Disassembler, Disassembly};
+pub use flags::{FlagEffect, FlagEffects};
+#[cfg(feature = "alloc")]
+pub use format::{format_cb_instruction, format_instruction};
+pub use mcode::{AluOp, Condition, MCode, UnaryOp, R16, R8};
+pub use opcodes::{CbOpcode, Opcode};
+pub use operand::Operand;
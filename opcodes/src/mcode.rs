@@ -5,11 +5,303 @@
 /// These are not based directly on any SM83 microcode, but are instead pulled from diagrams in the
 /// [Gameboy Complete Technical Reference](https://github.com/Gekkio/gb-ctr) by
 /// [Gekkio](https://github.com/Gekkio).
-#[derive(Clone, Copy, Eq, PartialEq)]
+///
+/// Each m-code touches the bus at most once, the same as the SM83 in a single m-cycle. This keeps
+/// the bus activity of each m-cycle comparable to the `cycles` in the SingleStepTests.
+///
+/// The [Debug] output of each m-code is the same as how it's written in `opcodes.json`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 pub enum MCode {
-    /// Perform no action.
+    /// The m-code of the `NOP` instruction, performs no action.
     Nop,
 
+    /// An m-cycle spent on internal work, or simply waiting, without touching the bus. Kept apart
+    /// from [MCode::Nop] so the idle cycles of other instructions aren't mistaken for a `NOP`.
+    InternalDelay,
+
     /// An illegal instruction, halts execution immediately.
     Illegal,
+
+    /// Copy the value of the second register into the first.
+    Ld(R8, R8),
+
+    /// Read the byte at `PC` into a register, then increment `PC`.
+    ReadImm(R8),
+
+    /// Read the byte at the address held in a register pair into a register.
+    Read(R16, R8),
+
+    /// Write the value of a register to the address held in a register pair.
+    Write(R16, R8),
+
+    /// Read the byte at the address held in a register pair into a register, then increment the
+    /// register pair.
+    ReadInc(R16, R8),
+
+    /// Read the byte at the address held in a register pair into a register, then decrement the
+    /// register pair.
+    ReadDec(R16, R8),
+
+    /// Write the value of a register to the address held in a register pair, then increment the
+    /// register pair.
+    WriteInc(R16, R8),
+
+    /// Write the value of a register to the address held in a register pair, then decrement the
+    /// register pair.
+    WriteDec(R16, R8),
+
+    /// Decrement `SP`, then write the value of a register to the address in `SP`.
+    Push(R8),
+
+    /// Read the byte at the address in `SP` into a register, then increment `SP`.
+    Pop(R8),
+
+    /// Read the byte at `$FF00` plus the value of the first register into the second register.
+    ReadHigh(R8, R8),
+
+    /// Write the value of the second register to `$FF00` plus the value of the first register.
+    WriteHigh(R8, R8),
+
+    /// Perform an 8-bit ALU operation with `A` and a register, storing the result in `A` (except for
+    /// [AluOp::Cp]).
+    Alu(AluOp, R8),
+
+    /// Increment a register pair using the 16-bit incrementer/decrementer (IDU), without affecting
+    /// flags.
+    Inc16(R16),
+
+    /// Decrement a register pair using the 16-bit incrementer/decrementer (IDU), without affecting
+    /// flags.
+    Dec16(R16),
+
+    /// Perform a single-operand ALU operation on a register in place.
+    Unary(UnaryOp, R8),
+
+    /// Perform a single-operand ALU operation on `Z`, then write the result to the address held in
+    /// a register pair.
+    WriteUnary(UnaryOp, R16),
+
+    /// Decimal-adjust `A` to binary-coded decimal after an addition or subtraction.
+    Daa,
+
+    /// Complement every bit of `A`.
+    Cpl,
+
+    /// Set the `c` flag.
+    Scf,
+
+    /// Complement the `c` flag.
+    Ccf,
+
+    /// Rotate `A` left, copying bit 7 into bit 0 and the `c` flag. Always clears the `z` flag.
+    Rlca,
+
+    /// Rotate `A` right, copying bit 0 into bit 7 and the `c` flag. Always clears the `z` flag.
+    Rrca,
+
+    /// Rotate `A` left through the `c` flag. Always clears the `z` flag.
+    Rla,
+
+    /// Rotate `A` right through the `c` flag. Always clears the `z` flag.
+    Rra,
+
+    /// Check a condition against the flags. If it fails, the rest of the queued m-code for the
+    /// instruction is dropped and the next instruction is fetched in the same m-cycle, making the
+    /// branch not taken shorter.
+    CheckCondition(Condition),
+
+    /// Load `PC` from a register pair.
+    Jp(R16),
+
+    /// Add the signed offset in `Z` to `PC`.
+    Jr,
+
+    /// Load `PC` with the reset vector encoded in bits 3-5 of the current opcode.
+    Rst,
+
+    /// Set or clear the interrupt master enable flag immediately.
+    Ime(bool),
+
+    /// Set the interrupt master enable flag after the next instruction is fetched, so no interrupt
+    /// can be serviced between `EI` and the instruction after it.
+    Ei,
+
+    /// Stop fetching and executing instructions until an interrupt is pending.
+    Halt,
+
+    /// Reset `DIV`, and switch the CPU's speed if a switch was requested through `KEY1`. Otherwise
+    /// stop like [MCode::Halt].
+    Stop,
+
+    /// Latch the vector of the highest priority pending interrupt into `WZ` and clear its bit in
+    /// `IF`, then push the low byte of `PC`. If pushing the high byte cancelled the interrupt by
+    /// overwriting `IE`, the vector is `$0000` instead. Only used to dispatch interrupts.
+    Dispatch,
+
+    /// Read the CB-prefixed opcode at `PC`, increment `PC`, and queue the m-code of that opcode.
+    FetchCb,
+
+    /// Test a bit of a register, setting the `z` flag if it is clear.
+    Bit(u8, R8),
+}
+
+/// The 8-bit operations the ALU can perform with `A` and another value.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub enum AluOp {
+    /// Addition.
+    Add,
+
+    /// Addition, plus one if the `c` flag is set.
+    Adc,
+
+    /// Subtraction.
+    Sub,
+
+    /// Subtraction, minus one if the `c` flag is set.
+    Sbc,
+
+    /// Bitwise AND.
+    And,
+
+    /// Bitwise exclusive OR.
+    Xor,
+
+    /// Bitwise OR.
+    Or,
+
+    /// Compare, a subtraction that updates the flags but discards the result.
+    Cp,
+}
+
+/// The 8-bit operations the ALU can perform on a single value in place.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub enum UnaryOp {
+    /// Increment by one, leaving the `c` flag untouched.
+    Inc,
+
+    /// Decrement by one, leaving the `c` flag untouched.
+    Dec,
+
+    /// Rotate left, copying bit 7 into bit 0 and the `c` flag.
+    Rlc,
+
+    /// Rotate right, copying bit 0 into bit 7 and the `c` flag.
+    Rrc,
+
+    /// Rotate left through the `c` flag.
+    Rl,
+
+    /// Rotate right through the `c` flag.
+    Rr,
+
+    /// Shift left, shifting bit 7 into the `c` flag.
+    Sla,
+
+    /// Shift right, keeping bit 7 and shifting bit 0 into the `c` flag.
+    Sra,
+
+    /// Shift right, clearing bit 7 and shifting bit 0 into the `c` flag.
+    Srl,
+
+    /// Swap the high and low nibbles, clearing the `c` flag.
+    Swap,
+
+    /// Clear a single bit, leaving the flags untouched.
+    Res(u8),
+
+    /// Set a single bit, leaving the flags untouched.
+    Set(u8),
+}
+
+/// The flag conditions a [MCode::CheckCondition] can test.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub enum Condition {
+    /// The `z` flag is clear.
+    NotZero,
+
+    /// The `z` flag is set.
+    Zero,
+
+    /// The `c` flag is clear.
+    NotCarry,
+
+    /// The `c` flag is set.
+    Carry,
+}
+
+/// The 8-bit registers an [MCode] can operate on.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub enum R8 {
+    /// The accumulator.
+    A,
+
+    /// The flags. Writes always clear the low 4 bits.
+    F,
+
+    /// The high byte of `BC`.
+    B,
+
+    /// The low byte of `BC`.
+    C,
+
+    /// The high byte of `DE`.
+    D,
+
+    /// The low byte of `DE`.
+    E,
+
+    /// The high byte of `HL`.
+    H,
+
+    /// The low byte of `HL`.
+    L,
+
+    /// The internal `W` register, which holds the high byte of a 16-bit operand between m-cycles.
+    W,
+
+    /// The internal `Z` register, which holds an operand between m-cycles.
+    Z,
+
+    /// The low byte of `SP`.
+    SpLow,
+
+    /// The high byte of `SP`.
+    SpHigh,
+
+    /// The low byte of `PC`.
+    PcLow,
+
+    /// The high byte of `PC`.
+    PcHigh,
+}
+
+/// The 16-bit register pairs an [MCode] can operate on.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub enum R16 {
+    /// The `A` register and the flags combined.
+    AF,
+
+    /// The `B` and `C` registers combined.
+    BC,
+
+    /// The `D` and `E` registers combined.
+    DE,
+
+    /// The `H` and `L` registers combined.
+    HL,
+
+    /// The stack pointer.
+    SP,
+
+    /// The internal `W` and `Z` registers combined.
+    WZ,
+
+    /// The program counter.
+    PC,
 }
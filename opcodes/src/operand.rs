@@ -0,0 +1,94 @@
+use core::fmt::Display;
+
+use crate::mcode::{Condition, R16, R8};
+
+/// The operands of an instruction, in the order they appear in its mnemonic. Operands are either
+/// implied by the opcode, like registers and conditions, or read from the bytes following it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Operand {
+    /// An 8-bit register, such as `A`.
+    R8(R8),
+
+    /// A 16-bit register pair, such as `BC`.
+    R16(R16),
+
+    /// The byte at the address held in a register pair, such as `[HL]`.
+    Ptr(R16),
+
+    /// The byte at the address held in a register pair, which is then incremented, `[HL+]`.
+    PtrInc(R16),
+
+    /// The byte at the address held in a register pair, which is then decremented, `[HL-]`.
+    PtrDec(R16),
+
+    /// The byte at `$FF00` plus the value of `C`, `[$FF00+C]`.
+    PtrHighC,
+
+    /// An 8-bit immediate, `n8`.
+    Imm8,
+
+    /// A 16-bit little-endian immediate, `n16`.
+    Imm16,
+
+    /// A signed 8-bit immediate offset, `e8`.
+    Offset,
+
+    /// `SP` plus a signed 8-bit immediate offset, `SP+e8`.
+    SpOffset,
+
+    /// A 16-bit little-endian immediate address, `a16`.
+    Addr,
+
+    /// The byte at a 16-bit little-endian immediate address, `[a16]`.
+    PtrAddr,
+
+    /// The byte at `$FF00` plus an 8-bit immediate, `[$FF00+a8]`.
+    PtrHigh,
+
+    /// A flag condition, such as `NZ`.
+    Cond(Condition),
+
+    /// The reset vector of `RST`, such as `$38`.
+    Vector(u8),
+
+    /// The bit index of `BIT`, `RES`, and `SET`.
+    Bit(u8),
+}
+
+impl Operand {
+    /// How many bytes of the instruction following the opcode this operand takes up.
+    pub const fn size(&self) -> u8 {
+        match self {
+            Self::Imm8 | Self::Offset | Self::SpOffset | Self::PtrHigh => 1,
+            Self::Imm16 | Self::Addr | Self::PtrAddr => 2,
+            _ => 0,
+        }
+    }
+}
+
+/// Operands are displayed as they're written in mnemonics, such as `[HL+]` or `n8`.
+impl Display for Operand {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::R8(r8) => write!(f, "{r8:?}"),
+            Self::R16(r16) => write!(f, "{r16:?}"),
+            Self::Ptr(r16) => write!(f, "[{r16:?}]"),
+            Self::PtrInc(r16) => write!(f, "[{r16:?}+]"),
+            Self::PtrDec(r16) => write!(f, "[{r16:?}-]"),
+            Self::PtrHighC => write!(f, "[$FF00+C]"),
+            Self::Imm8 => write!(f, "n8"),
+            Self::Imm16 => write!(f, "n16"),
+            Self::Offset => write!(f, "e8"),
+            Self::SpOffset => write!(f, "SP+e8"),
+            Self::Addr => write!(f, "a16"),
+            Self::PtrAddr => write!(f, "[a16]"),
+            Self::PtrHigh => write!(f, "[$FF00+a8]"),
+            Self::Cond(Condition::NotZero) => write!(f, "NZ"),
+            Self::Cond(Condition::Zero) => write!(f, "Z"),
+            Self::Cond(Condition::NotCarry) => write!(f, "NC"),
+            Self::Cond(Condition::Carry) => write!(f, "C"),
+            Self::Vector(vector) => write!(f, "${vector:02X}"),
+            Self::Bit(bit) => write!(f, "{bit}"),
+        }
+    }
+}
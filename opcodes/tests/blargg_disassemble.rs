@@ -0,0 +1,25 @@
+use dotmatrix_opcodes::{disassemble, AnyOpcode, Opcode};
+
+#[test]
+fn blargg_cpu_01_special_disassembles() {
+    let rom = include_bytes!("../../test_data/blargg/cpu_instrs/individual/01-special.gb");
+
+    // Every cartridge starts with `NOP; JP a16` at the entry point
+    let entry = disassemble(&rom[0x0100..0x0104], 0x0100).collect::<Vec<_>>();
+
+    assert_eq!(AnyOpcode::Opcode(Opcode::NOP), entry[0].opcode);
+    assert_eq!(AnyOpcode::Opcode(Opcode::JP_a16), entry[1].opcode);
+    assert!(entry[1].complete);
+
+    // The whole first bank decodes without gaps or overlaps
+    let bank = &rom[..0x4000];
+    let mut addr = 0x0000;
+
+    for item in disassemble(bank, 0x0000) {
+        assert_eq!(addr, item.addr);
+
+        addr += item.length as u16;
+    }
+
+    assert_eq!(0x4000, addr);
+}
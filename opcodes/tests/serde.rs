@@ -0,0 +1,34 @@
+#![cfg(feature = "serde")]
+
+use dotmatrix_opcodes::{CbOpcode, MCode, Opcode};
+
+#[test]
+fn opcodes_serialize_as_bytes() {
+    for (byte, opcode) in Opcode::ALL.into_iter().enumerate() {
+        let json = serde_json::to_string(&opcode).unwrap();
+
+        assert_eq!(json, byte.to_string());
+        assert_eq!(serde_json::from_str::<Opcode>(&json).unwrap(), opcode);
+    }
+
+    for (byte, opcode) in CbOpcode::ALL.into_iter().enumerate() {
+        let json = serde_json::to_string(&opcode).unwrap();
+
+        assert_eq!(json, byte.to_string());
+        assert_eq!(serde_json::from_str::<CbOpcode>(&json).unwrap(), opcode);
+    }
+}
+
+#[test]
+fn mcode_round_trips() {
+    let mcodes = Opcode::ALL
+        .into_iter()
+        .flat_map(|opcode| opcode.mcode())
+        .chain(CbOpcode::ALL.into_iter().flat_map(|opcode| opcode.mcode()));
+
+    for mcode in mcodes {
+        let json = serde_json::to_string(mcode).unwrap();
+
+        assert_eq!(&serde_json::from_str::<MCode>(&json).unwrap(), mcode);
+    }
+}